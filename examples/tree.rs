@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+use bevy_rts::tree::GrowTree;
+use bevy_rts::TreeGenPlugin;
+use rand::Rng;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(TreeGenPlugin)
+        .add_systems(Startup, setup)
+        .add_systems(Update, rotate_tree)
+        .run();
+}
+
+#[derive(Component)]
+struct RotatingTree;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // Camera
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(-5.0, 7.0, 12.0).looking_at(Vec3::new(0., 3., 0.), Vec3::Y),
+        ..default()
+    });
+
+    // Light
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance: 10000.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_rotation(Quat::from_euler(EulerRot::XYZ, -0.5, 0.5, 0.0)),
+        ..default()
+    });
+
+    // Tree
+    commands.spawn((
+        SpatialBundle::default(),
+        GrowTree {
+            seed: rand::thread_rng().gen(),
+        },
+        RotatingTree,
+    ));
+
+    // Ground plane
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Mesh::from(Plane3d::new(
+            Vec3::new(0., 1., 0.),
+            Vec2::new(10., 10.),
+        ))),
+        material: materials.add(Color::srgb(0.3, 0.5, 0.3)),
+        ..default()
+    });
+}
+
+fn rotate_tree(time: Res<Time>, mut query: Query<&mut Transform, With<RotatingTree>>) {
+    for mut transform in &mut query {
+        transform.rotate_y(0.1 * time.delta_seconds());
+    }
+}
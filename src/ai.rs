@@ -0,0 +1,1140 @@
+use std::str::FromStr;
+
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::building::{Building, BuildingDef, ConstructionSite, PlacementObstacles};
+use crate::cli;
+use crate::combat::Weapon;
+use crate::command::{Action, PlayerCommand};
+use crate::defs::{BuildingDefs, UnitDefs};
+use crate::economy::{
+    DropOff, Gatherer, IncomeMultipliers, ResourceKind, ResourceNode, Stockpile, Stockpiles,
+};
+use crate::formation::FormationKind;
+use crate::navigation::NavGrid;
+use crate::orders::{ActiveOrder, Destination, Order, UnitOrder};
+use crate::production::ProductionQueue;
+use crate::replay::Playback;
+use crate::script::nearest;
+use crate::sim::{SimClock, SimId, SimSet};
+use crate::team::Team;
+use crate::unit::{Unit, UnitDef};
+
+// Gatherers to have before spending on an army
+const MIN_WORKERS_FOR_ARMY: usize = 4;
+// Builders sent to each construction site
+const BUILDERS_PER_SITE: usize = 2;
+// Units each building is kept training at once
+const QUEUE_AHEAD: usize = 2;
+// Part of the gatherers kept on stone while there's stone left
+const STONE_SHARE: f32 = 0.3;
+// Once this much more stone than wood is banked, gatherers move off stone
+const STONE_SURPLUS: u32 = 100;
+// Resources further than this from every drop-off are worth a new one
+const DROP_OFF_RANGE: f32 = 8.0;
+// Furthest from where it's wanted, in build grid steps, a building is placed
+const MAX_PLACEMENT_RING: i32 = 12;
+// Room left around the AI's buildings on top of the usual clearance, so nothing gets walled in
+const BUILDING_GAP: f32 = 1.0;
+// Armed enemies this close to one of its buildings are attacking the base
+const DEFEND_RADIUS: f32 = 12.0;
+// The army gathers this far from the base, towards the nearest enemy
+const RALLY_DISTANCE: f32 = 6.0;
+// Fighters this close to the rally point have arrived
+const RALLY_RADIUS: f32 = 3.0;
+
+/// Computer opponents for the teams in `players`. Like scripts they see the whole map and play
+/// through `PlayerCommand`s, so replays record them and they run headless.
+#[derive(Clone, Default)]
+pub struct SkirmishAiPlugin {
+    pub players: Vec<AiPlayer>,
+}
+
+/// A team played by the AI.
+#[derive(Clone, Copy, Debug)]
+pub struct AiPlayer {
+    pub team: Team,
+    pub difficulty: Difficulty,
+}
+
+/// How well an AI plays: how quickly it reacts, how big it grows and how much it cheats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Ticks between the AI's turns.
+    pub fn reaction_ticks(self) -> u64 {
+        match self {
+            Self::Easy => 90,
+            Self::Normal => 45,
+            Self::Hard => 15,
+        }
+    }
+
+    /// The AI's `IncomeMultipliers`.
+    pub fn income_multiplier(self) -> f32 {
+        match self {
+            Self::Easy => 0.75,
+            Self::Normal => 1.0,
+            Self::Hard => 1.5,
+        }
+    }
+
+    fn workers(self) -> usize {
+        match self {
+            Self::Easy => 6,
+            Self::Normal => 10,
+            Self::Hard => 14,
+        }
+    }
+
+    // Buildings that train fighters
+    fn military_buildings(self) -> usize {
+        match self {
+            Self::Easy | Self::Normal => 1,
+            Self::Hard => 2,
+        }
+    }
+
+    // Fighters gathered before they go on the attack
+    fn attack_size(self) -> usize {
+        match self {
+            Self::Easy => 6,
+            Self::Normal => 8,
+            Self::Hard => 12,
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, ()> {
+        match text {
+            "easy" => Ok(Self::Easy),
+            "normal" => Ok(Self::Normal),
+            "hard" => Ok(Self::Hard),
+            _ => Err(()),
+        }
+    }
+}
+
+impl SkirmishAiPlugin {
+    /// The teams listed with `--ai <team>[:easy|normal|hard],...` on the command line, if any.
+    pub fn from_args() -> Self {
+        let players = cli::arg("ai")
+            .into_iter()
+            .flat_map(|players| {
+                players
+                    .split(',')
+                    .filter_map(|player| {
+                        let parsed = parse_player(player.trim());
+                        if parsed.is_none() {
+                            eprintln!("Ignoring --ai player {player}, it isn't valid");
+                        }
+                        parsed
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Self { players }
+    }
+}
+
+fn parse_player(text: &str) -> Option<AiPlayer> {
+    let (team, difficulty) = match text.split_once(':') {
+        Some((team, difficulty)) => (team, difficulty.parse().ok()?),
+        None => (text, Difficulty::default()),
+    };
+    Some(AiPlayer {
+        team: Team(team.parse().ok()?),
+        difficulty,
+    })
+}
+
+impl Plugin for SkirmishAiPlugin {
+    fn build(&self, app: &mut App) {
+        // A replay being played back brings the multipliers it was recorded with
+        if !app.world().contains_resource::<Playback>() {
+            let mut multipliers = app
+                .world_mut()
+                .get_resource_or_insert_with(IncomeMultipliers::default);
+            for player in &self.players {
+                multipliers
+                    .0
+                    .insert(player.team, player.difficulty.income_multiplier());
+            }
+        }
+        app.insert_resource(Brains(
+            self.players
+                .iter()
+                .map(|&player| Brain {
+                    player,
+                    squads: Vec::new(),
+                })
+                .collect(),
+        ))
+        .add_systems(
+            FixedUpdate,
+            run_ai
+                .in_set(SimSet::Last)
+                .run_if(|brains: Res<Brains>| !brains.0.is_empty()),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct Brains(Vec<Brain>);
+
+struct Brain {
+    player: AiPlayer,
+    squads: Vec<Squad>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Squad {
+    units: Vec<Entity>,
+    task: Task,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Task {
+    // Waiting at the rally point until there are enough to attack
+    Rallying,
+    // Seeing off enemies at the base
+    Defending,
+    // Out hunting the enemy
+    Attacking,
+}
+
+/// An AI player's squads as they were saved, made up of the entities in the save.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedSquads {
+    team: Team,
+    squads: Vec<Squad>,
+}
+
+/// Every AI player's squads, to be saved with the match.
+pub(crate) fn save_squads(world: &World) -> Vec<SavedSquads> {
+    let Some(brains) = world.get_resource::<Brains>() else {
+        return Vec::new();
+    };
+    brains
+        .0
+        .iter()
+        .map(|brain| SavedSquads {
+            team: brain.player.team,
+            squads: brain.squads.clone(),
+        })
+        .collect()
+}
+
+/// Hand the AI players back the squads they had when the match was saved. `entity_map` takes
+/// saved entities to restored ones; units that weren't restored are left out.
+pub(crate) fn restore_squads(
+    world: &mut World,
+    saved: Vec<SavedSquads>,
+    entity_map: &EntityHashMap<Entity>,
+) {
+    let Some(mut brains) = world.get_resource_mut::<Brains>() else {
+        return;
+    };
+    for brain in brains.0.iter_mut() {
+        brain.squads = saved
+            .iter()
+            .filter(|saved| saved.team == brain.player.team)
+            .flat_map(|saved| saved.squads.iter())
+            .map(|squad| Squad {
+                units: squad
+                    .units
+                    .iter()
+                    .filter_map(|unit| entity_map.get(unit).copied())
+                    .collect(),
+                task: squad.task,
+            })
+            .filter(|squad| !squad.units.is_empty())
+            .collect();
+    }
+}
+
+// Everything an AI looks at to take its turn
+#[allow(clippy::type_complexity)]
+#[derive(SystemParam)]
+struct Battlefield<'w, 's> {
+    units: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static SimId,
+            &'static Unit,
+            &'static Team,
+            &'static Transform,
+            Option<&'static ActiveOrder>,
+            Has<Gatherer>,
+            Has<Weapon>,
+        ),
+    >,
+    buildings: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static SimId,
+            &'static Building,
+            &'static Team,
+            &'static Transform,
+            Option<&'static ProductionQueue>,
+            Has<ConstructionSite>,
+            Has<DropOff>,
+        ),
+    >,
+    nodes: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static SimId,
+            &'static Transform,
+            &'static ResourceNode,
+        ),
+    >,
+    stockpiles: Res<'w, Stockpiles>,
+    unit_defs: UnitDefs<'w>,
+    building_defs: BuildingDefs<'w>,
+    nav_grid: Res<'w, NavGrid>,
+    obstacles: PlacementObstacles<'w, 's>,
+}
+
+// A unit as the AI sees it
+#[derive(Clone, Copy)]
+struct Piece<'a> {
+    entity: Entity,
+    id: SimId,
+    def: &'a str,
+    team: Team,
+    position: Vec3,
+    idle: bool,
+    gatherer: bool,
+    armed: bool,
+}
+
+// A building as the AI sees it
+#[derive(Clone, Copy)]
+struct Site<'a> {
+    entity: Entity,
+    id: SimId,
+    def: &'a str,
+    team: Team,
+    position: Vec3,
+    queue: Option<&'a ProductionQueue>,
+    finished: bool,
+    drop_off: bool,
+}
+
+// One team's view of the battlefield for a turn, in `SimId` order so it plays the same way
+// every time
+struct Turn<'a> {
+    team: Team,
+    difficulty: Difficulty,
+    units: Vec<Piece<'a>>,
+    buildings: Vec<Site<'a>>,
+    enemy_units: Vec<Piece<'a>>,
+    enemy_buildings: Vec<Site<'a>>,
+    commands: Vec<PlayerCommand>,
+}
+
+impl<'a> Turn<'a> {
+    fn new(player: AiPlayer, units: &[Piece<'a>], buildings: &[Site<'a>]) -> Self {
+        let (own_units, enemy_units) = units.iter().partition(|unit| unit.team == player.team);
+        let (own_buildings, enemy_buildings) = buildings
+            .iter()
+            .partition(|building| building.team == player.team);
+        Self {
+            team: player.team,
+            difficulty: player.difficulty,
+            units: own_units,
+            buildings: own_buildings,
+            enemy_units,
+            enemy_buildings,
+            commands: Vec::new(),
+        }
+    }
+
+    fn order(&mut self, units: Vec<Entity>, order: Order) {
+        if !units.is_empty() {
+            self.commands
+                .push(PlayerCommand::order(self.team, units, order, false));
+        }
+    }
+
+    // Where the base is: its first building, or wherever its units are if it has none
+    fn base(&self) -> Option<Vec3> {
+        if let Some(building) = self.buildings.first() {
+            return Some(building.position);
+        }
+        let count = self.units.len();
+        (count > 0)
+            .then(|| self.units.iter().map(|unit| unit.position).sum::<Vec3>() / count as f32)
+    }
+
+    // The nearest enemy building to `to`, or the nearest enemy unit if there are none
+    fn nearest_enemy(&self, to: Vec3) -> Option<Vec3> {
+        let buildings = self
+            .enemy_buildings
+            .iter()
+            .map(|building| (building.position, building.id, building.position));
+        let units = self
+            .enemy_units
+            .iter()
+            .map(|unit| (unit.position, unit.id, unit.position));
+        nearest(to, buildings).or_else(|| nearest(to, units))
+    }
+
+    // The nearest enemy unit to `to`, or the nearest enemy building to knock down if there are
+    // none. Fighters attack-moving there open fire on it once they're in range
+    fn nearest_target(&self, to: Vec3) -> Option<Vec3> {
+        let units = self
+            .enemy_units
+            .iter()
+            .map(|unit| (unit.position, unit.id, unit.position));
+        let buildings = self
+            .enemy_buildings
+            .iter()
+            .map(|building| (building.position, building.id, building.position));
+        nearest(to, units).or_else(|| nearest(to, buildings))
+    }
+}
+
+// Every unit and building on the map, in `SimId` order
+fn survey<'a>(battlefield: &'a Battlefield) -> (Vec<Piece<'a>>, Vec<Site<'a>>) {
+    let mut units: Vec<_> = battlefield
+        .units
+        .iter()
+        .map(
+            |(entity, id, unit, team, transform, order, gatherer, armed)| Piece {
+                entity,
+                id: *id,
+                def: &unit.0,
+                team: *team,
+                position: transform.translation,
+                idle: order.is_none(),
+                gatherer,
+                armed,
+            },
+        )
+        .collect();
+    units.sort_by_key(|unit| unit.id);
+    let mut buildings: Vec<_> = battlefield
+        .buildings
+        .iter()
+        .map(
+            |(entity, id, building, team, transform, queue, site, drop_off)| Site {
+                entity,
+                id: *id,
+                def: &building.0,
+                team: *team,
+                position: transform.translation,
+                queue,
+                finished: !site,
+                drop_off,
+            },
+        )
+        .collect();
+    buildings.sort_by_key(|building| building.id);
+    (units, buildings)
+}
+
+fn attack_move(target: Vec3) -> Order {
+    Order::AttackMove(Destination {
+        target,
+        facing: None,
+        formation: FormationKind::default(),
+    })
+}
+
+fn run_ai(
+    clock: Res<SimClock>,
+    mut brains: ResMut<Brains>,
+    battlefield: Battlefield,
+    mut commands: EventWriter<PlayerCommand>,
+) {
+    let acting = |brain: &Brain| {
+        clock
+            .tick
+            .is_multiple_of(brain.player.difficulty.reaction_ticks())
+    };
+    if !brains.0.iter().any(acting) {
+        return;
+    }
+
+    let (units, buildings) = survey(&battlefield);
+    for brain in brains.0.iter_mut() {
+        if !acting(brain) {
+            continue;
+        }
+        let mut turn = Turn::new(brain.player, &units, &buildings);
+        let Some(base) = turn.base() else {
+            // Nothing left to play with
+            continue;
+        };
+        run_economy(&mut turn, base, &battlefield);
+        run_army(brain, &mut turn, base);
+        commands.send_batch(turn.commands);
+    }
+}
+
+// What a team has, counting what's being trained
+struct Census {
+    gatherers: usize,
+    // Fighters by unit definition
+    fighters: Vec<(String, usize)>,
+}
+
+impl Census {
+    fn count(&mut self, id: &str, def: &UnitDef) {
+        if is_gatherer(def) {
+            self.gatherers += 1;
+        } else if is_fighter(def) {
+            match self.fighters.iter_mut().find(|(other, _)| other == id) {
+                Some((_, count)) => *count += 1,
+                None => self.fighters.push((id.to_string(), 1)),
+            }
+        }
+    }
+
+    fn fighters_of(&self, id: &str) -> usize {
+        self.fighters
+            .iter()
+            .find(|(other, _)| other == id)
+            .map_or(0, |(_, count)| *count)
+    }
+}
+
+fn take_census(turn: &Turn, battlefield: &Battlefield) -> Census {
+    let mut census = Census {
+        gatherers: 0,
+        fighters: Vec::new(),
+    };
+    let queued = turn
+        .buildings
+        .iter()
+        .filter_map(|building| building.queue)
+        .flat_map(|queue| queue.queue.iter().map(String::as_str));
+    for id in turn.units.iter().map(|unit| unit.def).chain(queued) {
+        if let Some(def) = battlefield.unit_defs.get(id) {
+            census.count(id, def);
+        }
+    }
+    census
+}
+
+// The cheapest building that trains a unit `wanted` says yes to
+fn cheapest_building<'a>(
+    battlefield: &'a Battlefield,
+    wanted: impl Fn(&BuildingDef) -> bool,
+) -> Option<(&'a str, &'a BuildingDef)> {
+    battlefield
+        .building_defs
+        .ids()
+        .filter_map(|id| Some((id, battlefield.building_defs.get(id)?)))
+        .filter(|(_, def)| wanted(def))
+        .min_by_key(|(_, def)| def.cost.wood + def.cost.stone)
+}
+
+fn trains(battlefield: &Battlefield, building: &BuildingDef, wanted: fn(&UnitDef) -> bool) -> bool {
+    building
+        .trains
+        .iter()
+        .any(|id| battlefield.unit_defs.get(id).is_some_and(wanted))
+}
+
+fn is_gatherer(unit: &UnitDef) -> bool {
+    unit.gatherer.is_some()
+}
+
+fn is_fighter(unit: &UnitDef) -> bool {
+    unit.gatherer.is_none() && unit.weapon.is_some()
+}
+
+// `budget` once `reserved` is set aside can still pay `cost`
+fn affordable(budget: &Stockpile, reserved: &Stockpile, cost: &Stockpile) -> bool {
+    let mut needed = *reserved;
+    needed.refund(cost);
+    budget.can_afford(&needed)
+}
+
+// Put up what the base is missing, keep the town hall training workers and the barracks
+// training fighters, and set idle workers gathering
+fn run_economy(turn: &mut Turn, base: Vec3, battlefield: &Battlefield) {
+    let mut budget = battlefield.stockpiles.get(turn.team);
+    let mut census = take_census(turn, battlefield);
+    let mut reserved = Stockpile::default();
+
+    // Buildings, most needed first. Each waits for the one before it to be affordable.
+    let mut wanted = Vec::new();
+    let has = |wants: &dyn Fn(&BuildingDef) -> bool| {
+        turn.buildings
+            .iter()
+            .filter(|building| {
+                battlefield
+                    .building_defs
+                    .get(building.def)
+                    .is_some_and(wants)
+            })
+            .count()
+    };
+    let trains_workers = |def: &BuildingDef| trains(battlefield, def, is_gatherer);
+    let trains_fighters = |def: &BuildingDef| trains(battlefield, def, is_fighter);
+    if has(&trains_workers) == 0 {
+        if let Some(hall) = cheapest_building(battlefield, trains_workers) {
+            wanted.push((hall, base));
+        }
+    }
+    if census.gatherers >= MIN_WORKERS_FOR_ARMY
+        && has(&trains_fighters) < turn.difficulty.military_buildings()
+    {
+        if let Some(barracks) = cheapest_building(battlefield, trains_fighters) {
+            wanted.push((barracks, base));
+        }
+    }
+    if let Some(node) = uncovered_resource(turn, base, battlefield) {
+        let store = |def: &BuildingDef| def.drop_off && def.trains.is_empty();
+        if let Some(storehouse) = cheapest_building(battlefield, store) {
+            wanted.push((storehouse, node));
+        }
+    }
+    for ((id, def), near) in wanted {
+        // One of each at a time
+        let building_up = turn
+            .buildings
+            .iter()
+            .any(|building| building.def == id && !building.finished);
+        if building_up {
+            continue;
+        }
+        if !budget.can_afford(&def.cost) {
+            reserved = def.cost;
+            break;
+        }
+        let Some(position) = find_building_spot(def, near, battlefield) else {
+            continue;
+        };
+        let builders = nearest_workers(turn, position, BUILDERS_PER_SITE);
+        if builders.is_empty() {
+            break;
+        }
+        // Off to build, not to gather
+        for unit in turn.units.iter_mut() {
+            if builders.contains(&unit.entity) {
+                unit.idle = false;
+            }
+        }
+        budget.spend(&def.cost);
+        turn.commands.push(PlayerCommand {
+            team: turn.team,
+            action: Action::Place {
+                building: id.to_string(),
+                position,
+                builders,
+                queue: false,
+            },
+        });
+    }
+
+    // Training
+    let mut training = Vec::new();
+    for building in turn.buildings.iter().filter(|building| building.finished) {
+        let (Some(queue), Some(def)) =
+            (building.queue, battlefield.building_defs.get(building.def))
+        else {
+            continue;
+        };
+        for _ in queue.queue.len()..QUEUE_AHEAD {
+            let next = train_next(turn, def, &census, &budget, &reserved, battlefield);
+            let Some((slot, id, unit)) = next else {
+                break;
+            };
+            budget.spend(&unit.cost);
+            census.count(id, unit);
+            training.push((building.entity, slot));
+        }
+    }
+    for (building, slot) in training {
+        turn.commands.push(PlayerCommand {
+            team: turn.team,
+            action: Action::Train {
+                buildings: vec![building],
+                slot,
+            },
+        });
+    }
+
+    set_gathering(turn, &budget, battlefield);
+}
+
+// What slot of `building` to train next: workers until there are enough, then whichever
+// fighter there are fewest of that the budget covers
+fn train_next<'a>(
+    turn: &Turn,
+    building: &'a BuildingDef,
+    census: &Census,
+    budget: &Stockpile,
+    reserved: &Stockpile,
+    battlefield: &'a Battlefield,
+) -> Option<(usize, &'a str, &'a UnitDef)> {
+    let options = building
+        .trains
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, id)| Some((slot, id.as_str(), battlefield.unit_defs.get(id)?)));
+    let mut best: Option<(usize, &str, &UnitDef, usize)> = None;
+    for (slot, id, def) in options {
+        let count = if is_gatherer(def) {
+            // Workers come before anything else is saved up for
+            if census.gatherers >= turn.difficulty.workers() || !budget.can_afford(&def.cost) {
+                continue;
+            }
+            return Some((slot, id, def));
+        } else if is_fighter(def) {
+            if census.gatherers < MIN_WORKERS_FOR_ARMY || !affordable(budget, reserved, &def.cost) {
+                continue;
+            }
+            census.fighters_of(id)
+        } else {
+            continue;
+        };
+        if best.is_none_or(|(.., fewest)| count < fewest) {
+            best = Some((slot, id, def, count));
+        }
+    }
+    best.map(|(slot, id, def, _)| (slot, id, def))
+}
+
+// A resource near the base that's a long walk from every drop-off
+fn uncovered_resource(turn: &Turn, base: Vec3, battlefield: &Battlefield) -> Option<Vec3> {
+    let node = nearest(
+        base,
+        battlefield
+            .nodes
+            .iter()
+            .filter(|(.., node)| node.amount > 0)
+            .map(|(_, id, transform, _)| (transform.translation, *id, transform.translation)),
+    )?;
+    let covered = turn
+        .buildings
+        .iter()
+        .filter(|building| building.drop_off)
+        .any(|building| building.position.xz().distance(node.xz()) <= DROP_OFF_RANGE);
+    (!covered).then_some(node)
+}
+
+// The first spot on rings spreading out from `near` where `building` fits, snapped to the
+// build grid as a player would place it
+fn find_building_spot(
+    building: &BuildingDef,
+    near: Vec3,
+    battlefield: &Battlefield,
+) -> Option<Vec3> {
+    let near = near.xz();
+    for ring in 0..=MAX_PLACEMENT_RING {
+        let edge = (-ring..=ring).flat_map(|x| [IVec2::new(x, -ring), IVec2::new(x, ring)]);
+        let sides = (1 - ring..ring).flat_map(|z| [IVec2::new(-ring, z), IVec2::new(ring, z)]);
+        for offset in edge.chain(sides) {
+            let area = building.snapped_footprint(near + offset.as_vec2());
+            if !battlefield.nav_grid.is_buildable(area)
+                || !battlefield.obstacles.clear(area.inflate(BUILDING_GAP))
+            {
+                continue;
+            }
+            let center = area.center();
+            if let Some(height) = battlefield.nav_grid.surface_height(center) {
+                return Some(Vec3::new(center.x, height, center.y));
+            }
+        }
+    }
+    None
+}
+
+// Up to `count` workers closest to `position`, idle ones first
+fn nearest_workers(turn: &Turn, position: Vec3, count: usize) -> Vec<Entity> {
+    let mut workers: Vec<_> = turn.units.iter().filter(|unit| unit.gatherer).collect();
+    workers.sort_by(|a, b| {
+        b.idle.cmp(&a.idle).then_with(|| {
+            let a_distance = a.position.distance_squared(position);
+            let b_distance = b.position.distance_squared(position);
+            a_distance.total_cmp(&b_distance).then(a.id.cmp(&b.id))
+        })
+    });
+    workers
+        .into_iter()
+        .take(count)
+        .map(|unit| unit.entity)
+        .collect()
+}
+
+// Send idle workers to the nearest node, keeping some on stone unless there's plenty of it
+fn set_gathering(turn: &mut Turn, stockpile: &Stockpile, battlefield: &Battlefield) {
+    let kind_of = |node: Entity| battlefield.nodes.get(node).ok().map(|(.., node)| node.kind);
+    let nodes_of = |kind| {
+        battlefield
+            .nodes
+            .iter()
+            .filter(move |(.., node)| node.kind == kind && node.amount > 0)
+            .map(|(entity, id, transform, _)| (entity, *id, transform.translation))
+    };
+    let mut on_stone = Vec::new();
+    let mut gathering = 0;
+    for unit in turn.units.iter().filter(|unit| unit.gatherer) {
+        let Ok((.., Some(ActiveOrder(UnitOrder::Gather(node))), _, _)) =
+            battlefield.units.get(unit.entity)
+        else {
+            continue;
+        };
+        gathering += 1;
+        if kind_of(*node) == Some(ResourceKind::Stone) {
+            on_stone.push((unit.entity, unit.position));
+        }
+    }
+
+    let stone_surplus = stockpile.stone >= stockpile.wood + STONE_SURPLUS;
+    if stone_surplus {
+        // One at a time, so it doesn't swing back and forth
+        if let Some((worker, position)) = on_stone.pop() {
+            if let Some(node) = nearest(position, nodes_of(ResourceKind::Wood)) {
+                turn.order(vec![worker], Order::Gather(node));
+            }
+        }
+    }
+
+    let idle: Vec<_> = turn
+        .units
+        .iter()
+        .filter(|unit| unit.gatherer && unit.idle)
+        .map(|unit| (unit.entity, unit.position))
+        .collect();
+    let mut on_stone = on_stone.len();
+    for (worker, position) in idle {
+        gathering += 1;
+        let wants_stone = !stone_surplus && (on_stone as f32) < gathering as f32 * STONE_SHARE;
+        let (first, second) = match wants_stone {
+            true => (ResourceKind::Stone, ResourceKind::Wood),
+            false => (ResourceKind::Wood, ResourceKind::Stone),
+        };
+        let node =
+            nearest(position, nodes_of(first)).or_else(|| nearest(position, nodes_of(second)));
+        let Some(node) = node else {
+            continue;
+        };
+        if kind_of(node) == Some(ResourceKind::Stone) {
+            on_stone += 1;
+        }
+        turn.order(vec![worker], Order::Gather(node));
+    }
+}
+// Gather new fighters at the rally point, send them to see off anyone attacking the base and
+// go on the attack once there are enough of them
+fn run_army(brain: &mut Brain, turn: &mut Turn, base: Vec3) {
+    let fighters: Vec<_> = turn
+        .units
+        .iter()
+        .filter(|unit| unit.armed && !unit.gatherer)
+        .copied()
+        .collect();
+    let alive: EntityHashSet = fighters.iter().map(|unit| unit.entity).collect();
+    for squad in &mut brain.squads {
+        squad.units.retain(|unit| alive.contains(unit));
+    }
+    brain.squads.retain(|squad| !squad.units.is_empty());
+    let is_idle = |entity: &Entity| {
+        fighters
+            .iter()
+            .any(|unit| unit.entity == *entity && unit.idle)
+    };
+    let centre = |squad: &Squad| {
+        let positions = fighters
+            .iter()
+            .filter(|unit| squad.units.contains(&unit.entity))
+            .map(|unit| unit.position);
+        positions.sum::<Vec3>() / squad.units.len() as f32
+    };
+
+    // The base comes first
+    let near_base = |position: Vec3| {
+        turn.buildings
+            .iter()
+            .any(|building| building.position.distance(position) <= DEFEND_RADIUS)
+    };
+    let threat = nearest(
+        base,
+        turn.enemy_units
+            .iter()
+            .filter(|enemy| enemy.armed && near_base(enemy.position))
+            .map(|enemy| (enemy.position, enemy.id, enemy.position)),
+    );
+    let mut rallying = Squad {
+        units: Vec::new(),
+        task: Task::Rallying,
+    };
+    let mut squads = Vec::new();
+    for mut squad in brain.squads.drain(..) {
+        match (squad.task, threat) {
+            (Task::Attacking, _) => squads.push(squad),
+            (Task::Defending, Some(_)) => squads.push(squad),
+            (Task::Rallying, Some(threat)) => {
+                turn.order(squad.units.clone(), attack_move(threat));
+                squad.task = Task::Defending;
+                squads.push(squad);
+            }
+            (_, None) => rallying.units.append(&mut squad.units),
+        }
+    }
+    let assigned: EntityHashSet = squads
+        .iter()
+        .chain([&rallying])
+        .flat_map(|squad| squad.units.iter().copied())
+        .collect();
+    let newcomers = fighters
+        .iter()
+        .map(|unit| unit.entity)
+        .filter(|unit| !assigned.contains(unit));
+    rallying.units.extend(newcomers);
+
+    if let Some(threat) = threat {
+        // Everyone not already out there joins in
+        if !rallying.units.is_empty() {
+            turn.order(rallying.units.clone(), attack_move(threat));
+            rallying.task = Task::Defending;
+        }
+        for squad in squads.iter().filter(|squad| squad.task == Task::Defending) {
+            let idle: Vec<Entity> = squad.units.iter().copied().filter(is_idle).collect();
+            turn.order(idle, attack_move(threat));
+        }
+    } else if rallying.units.len() >= turn.difficulty.attack_size() {
+        if let Some(target) = turn.nearest_target(centre(&rallying)) {
+            turn.order(rallying.units.clone(), attack_move(target));
+            rallying.task = Task::Attacking;
+        }
+    } else {
+        let towards = turn
+            .nearest_enemy(base)
+            .map_or(Vec3::ZERO, |enemy| (enemy - base).normalize_or_zero());
+        let rally = base + towards * RALLY_DISTANCE;
+        let stragglers = rallying
+            .units
+            .iter()
+            .copied()
+            .filter(is_idle)
+            .filter(|unit| {
+                fighters.iter().any(|fighter| {
+                    fighter.entity == *unit
+                        && fighter.position.xz().distance(rally.xz()) > RALLY_RADIUS
+                })
+            })
+            .collect();
+        turn.order(
+            stragglers,
+            Order::Move(Destination {
+                target: rally,
+                facing: None,
+                formation: FormationKind::default(),
+            }),
+        );
+    }
+
+    // Squads on the attack move on to the next target once they're done with the last
+    for squad in squads.iter().filter(|squad| squad.task == Task::Attacking) {
+        // Half will do, the rest may be stuck behind them
+        let idle = squad.units.iter().filter(|unit| is_idle(unit)).count();
+        if idle * 2 < squad.units.len() {
+            continue;
+        }
+        if let Some(target) = turn.nearest_target(centre(squad)) {
+            turn.order(squad.units.clone(), attack_move(target));
+        }
+    }
+
+    if !rallying.units.is_empty() {
+        squads.push(rallying);
+    }
+    brain.squads = squads;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::headless::HeadlessPlugin;
+    use crate::lockstep::LockstepPlugin;
+    use crate::replay::ReplayPlugin;
+    use crate::scenario::ScenarioPlugin;
+    use crate::script::ScriptPlugin;
+    use crate::sim::SimPlugin;
+    use crate::RtsGamePlugin;
+
+    // The skirmish once its scenario is in the world, with nobody playing it
+    fn skirmish() -> App {
+        let mut app = App::new();
+        app.add_plugins(HeadlessPlugin {
+            ticks: u64::MAX,
+            report: None,
+        })
+        .add_plugins(RtsGamePlugin {
+            sim: SimPlugin::default(),
+            scenario: ScenarioPlugin::named("skirmish"),
+            replay: ReplayPlugin::Record(std::env::temp_dir().join("ai_skirmish.replay.ron")),
+            lockstep: LockstepPlugin::default(),
+            script: ScriptPlugin { teams: Vec::new() },
+            ai: SkirmishAiPlugin::default(),
+        });
+        app.finish();
+        app.cleanup();
+        while app.world().resource::<SimClock>().tick < 5 {
+            app.update();
+        }
+        app
+    }
+
+    fn stockpile(wood: u32, stone: u32) -> Stockpile {
+        Stockpile { wood, stone }
+    }
+
+    fn player(team: u8) -> AiPlayer {
+        AiPlayer {
+            team: Team(team),
+            difficulty: Difficulty::Normal,
+        }
+    }
+
+    #[test]
+    fn affordable_leaves_what_is_reserved() {
+        let budget = stockpile(100, 20);
+        assert!(affordable(&budget, &stockpile(0, 0), &stockpile(100, 20)));
+        assert!(affordable(&budget, &stockpile(50, 0), &stockpile(50, 20)));
+        assert!(!affordable(&budget, &stockpile(50, 0), &stockpile(60, 0)));
+        assert!(!affordable(&budget, &stockpile(0, 10), &stockpile(0, 20)));
+    }
+
+    #[test]
+    fn census_counts_workers_and_fighters() {
+        let mut app = skirmish();
+        let census = app.world_mut().run_system_once(|battlefield: Battlefield| {
+            let (units, buildings) = survey(&battlefield);
+            let turn = Turn::new(player(0), &units, &buildings);
+            take_census(&turn, &battlefield)
+        });
+        assert_eq!(census.gatherers, 4);
+        assert_eq!(census.fighters, vec![("soldier".to_string(), 3)]);
+        assert_eq!(census.fighters_of("soldier"), 3);
+        assert_eq!(census.fighters_of("archer"), 0);
+    }
+
+    #[test]
+    fn census_counts_units_in_training() {
+        let mut app = skirmish();
+        let world = app.world_mut();
+        let mut halls = world.query::<(&Team, &mut ProductionQueue)>();
+        for (team, mut queue) in halls.iter_mut(world) {
+            if *team == Team(0) {
+                queue
+                    .queue
+                    .extend(["worker".to_string(), "worker".to_string()]);
+            }
+        }
+        let census = world.run_system_once(|battlefield: Battlefield| {
+            let (units, buildings) = survey(&battlefield);
+            let turn = Turn::new(player(0), &units, &buildings);
+            take_census(&turn, &battlefield)
+        });
+        assert_eq!(census.gatherers, 6);
+    }
+
+    #[test]
+    fn cheapest_building_picks_the_cheapest_that_fits() {
+        let mut app = skirmish();
+        let picked = app.world_mut().run_system_once(|battlefield: Battlefield| {
+            let pick = |wanted: &dyn Fn(&BuildingDef) -> bool| {
+                cheapest_building(&battlefield, wanted).map(|(id, _)| id.to_string())
+            };
+            [
+                pick(&|def| def.drop_off),
+                pick(&|def| trains(&battlefield, def, is_fighter)),
+                pick(&|def| trains(&battlefield, def, is_gatherer)),
+                pick(&|def| def.size().x > 10.0),
+            ]
+        });
+        assert_eq!(
+            picked,
+            [
+                Some("storehouse".to_string()),
+                Some("barracks".to_string()),
+                Some("town_hall".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn building_spots_are_clear_and_buildable() {
+        let mut app = skirmish();
+        app.world_mut().run_system_once(|battlefield: Battlefield| {
+            let storehouse = battlefield.building_defs.get("storehouse").unwrap();
+            // Right on top of the town hall, and beside the hill in the middle
+            for near in [Vec3::new(-25.0, 0.0, -25.0), Vec3::new(4.0, 0.0, 0.0)] {
+                let spot = find_building_spot(storehouse, near, &battlefield).unwrap();
+                let area = storehouse.snapped_footprint(spot.xz());
+                assert!(battlefield.nav_grid.is_buildable(area));
+                assert!(battlefield.obstacles.clear(area.inflate(BUILDING_GAP)));
+                assert!(spot.xz().distance(near.xz()) < 8.0);
+            }
+        });
+    }
+
+    #[test]
+    fn saved_squads_come_back_with_the_restored_units() {
+        let mut world = World::new();
+        let saved: Vec<Entity> = (0..3).map(|_| world.spawn_empty().id()).collect();
+        let squad = |units: &[Entity], task| Squad {
+            units: units.to_vec(),
+            task,
+        };
+        world.insert_resource(Brains(vec![
+            Brain {
+                player: player(0),
+                squads: vec![
+                    squad(&saved[..2], Task::Attacking),
+                    squad(&saved[2..], Task::Rallying),
+                ],
+            },
+            Brain {
+                player: player(1),
+                squads: Vec::new(),
+            },
+        ]));
+        let squads = save_squads(&world);
+
+        // The second unit didn't make it into the save, and the third's squad goes with it
+        let restored = world.spawn_empty().id();
+        let entity_map = EntityHashMap::from_iter([(saved[0], restored)]);
+        for brain in world.resource_mut::<Brains>().0.iter_mut() {
+            brain.squads.clear();
+        }
+        restore_squads(&mut world, squads, &entity_map);
+        let brains = &world.resource::<Brains>().0;
+        let [only] = &brains[0].squads[..] else {
+            panic!("expected one squad");
+        };
+        assert_eq!(only.units, vec![restored]);
+        assert!(only.task == Task::Attacking);
+        assert!(brains[1].squads.is_empty());
+    }
+}
@@ -0,0 +1,603 @@
+use bevy::ecs::system::{EntityCommands, SystemParam};
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+use bevy_mod_raycast::prelude::Raycast;
+use bevy_rts_camera::Ground;
+use serde::Deserialize;
+
+use crate::combat::{Armor, Health};
+use crate::command::{Action, CommandSet, PlayerCommand, TickCommands};
+use crate::defs::{AwaitingDef, BuildingDefs, DefsSet};
+use crate::economy::{walk_towards, DropOff, ResourceNode, Stockpile, Stockpiles};
+use crate::fog::Vision;
+use crate::minimap::MinimapCursor;
+use crate::navigation::{Footprint, NavGrid, Path};
+use crate::orders::{
+    cursor_hit, ActiveOrder, IssueOrder, Order, OrderSet, TargetedOrder, Targeting, UnitOrder,
+};
+use crate::production::{ProductionQueue, RallyPoint};
+use crate::selection::{select_unit, Selectable, Selected};
+use crate::sim::{assign_sim_id, SimSet};
+use crate::steering::{Steering, SteeringSet};
+use crate::team::{PlayerId, Team, TeamPalette};
+
+// Building corners snap to a grid this coarse
+const BUILD_GRID: f32 = 1.0;
+// Builders this close to the edge of a construction site can work on it
+const BUILD_DISTANCE: f32 = 1.0;
+// Units and resources closer than this to a footprint are in the way of placing it
+const CLEARANCE: f32 = 0.4;
+// Construction sites start out this fraction of their full height
+const SITE_MIN_HEIGHT: f32 = 0.1;
+
+pub struct BuildingPlugin;
+
+impl Plugin for BuildingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Building>()
+            .register_type::<ConstructionSite>()
+            .register_type::<Builder>()
+            .add_systems(Startup, (spawn_ghost, spawn_placement_text))
+            .add_systems(PreUpdate, reload_building_defs.after(DefsSet))
+            .add_systems(FixedUpdate, build_buildings.in_set(SimSet::Spawn))
+            .add_systems(
+                Update,
+                (
+                    (building_hotkeys, update_ghost, place_building).chain(),
+                    update_placement_text,
+                ),
+            )
+            .configure_sets(FixedUpdate, PlacementSet.in_set(CommandSet::Apply))
+            .add_systems(FixedUpdate, place_commands.in_set(PlacementSet))
+            .add_systems(
+                FixedUpdate,
+                construct
+                    .after(OrderSet)
+                    .before(SteeringSet)
+                    .in_set(SimSet::Update),
+            );
+    }
+}
+
+/// Lays down the buildings players ask for. Other commands on the same tick are carried out
+/// after it, so a building is paid for before anything else is.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct PlacementSet;
+
+/// Everything about a kind of building that doesn't change between buildings, read from
+/// assets/buildings/*.building.ron.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct BuildingDef {
+    pub name: String,
+    /// Width and depth of the footprint, in world units.
+    pub size: [f32; 2],
+    pub height: f32,
+    pub cost: Stockpile,
+    /// Seconds of work a single builder needs to put it up.
+    pub build_time: f32,
+    pub health: f32,
+    #[serde(default)]
+    pub armor: f32,
+    pub vision: f32,
+    /// Whether gatherers can bring resources here.
+    #[serde(default)]
+    pub drop_off: bool,
+    /// Ids of the units it can train once finished.
+    #[serde(default)]
+    pub trains: Vec<String>,
+    #[serde(skip)]
+    pub mesh: Handle<Mesh>,
+}
+
+impl BuildingDef {
+    pub fn size(&self) -> Vec2 {
+        Vec2::from(self.size)
+    }
+
+    fn footprint_at(&self, center: Vec2) -> Rect {
+        Rect::from_center_size(center, self.size())
+    }
+
+    /// The footprint nearest to being centred on `point` with its corner on the build grid.
+    pub fn snapped_footprint(&self, point: Vec2) -> Rect {
+        let size = self.size();
+        let corner = ((point - size * 0.5) / BUILD_GRID).round() * BUILD_GRID;
+        Rect::from_corners(corner, corner + size)
+    }
+}
+
+/// The id of the definition a building was made from.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Building(pub String);
+
+/// A building that's still going up. It does nothing until builders finish it.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct ConstructionSite {
+    /// Seconds of work put in so far.
+    pub progress: f32,
+}
+
+/// A unit that can work on construction sites.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Builder {
+    /// Seconds of work put in per second.
+    pub rate: f32,
+}
+
+// The translucent preview of a building being placed
+#[derive(Component, Default)]
+struct Ghost {
+    // Id of the definition the ghost's mesh is of
+    def_id: Option<String>,
+    // Where the building would stand, if the cursor is over the terrain
+    spot: Option<Vec3>,
+    valid: bool,
+}
+
+#[derive(Resource)]
+struct GhostMaterials {
+    valid: Handle<StandardMaterial>,
+    invalid: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct PlacementText;
+
+/// Spawn a building of the definition `def_id` standing on `position`, either finished or as a
+/// construction site for builders to complete. It's filled in from the definition once that has
+/// loaded.
+pub fn spawn_building(
+    commands: &mut Commands,
+    def_id: &str,
+    team: Team,
+    position: Vec3,
+    complete: bool,
+) -> Entity {
+    let height = if complete { 1.0 } else { SITE_MIN_HEIGHT };
+    let mut building = commands.spawn((
+        SpatialBundle::from_transform(
+            Transform::from_translation(position).with_scale(Vec3::new(1.0, height, 1.0)),
+        ),
+        Building(def_id.to_string()),
+        team,
+        AwaitingDef::default(),
+    ));
+    building.add(assign_sim_id);
+    if !complete {
+        building.insert(ConstructionSite::default());
+    }
+    building.id()
+}
+
+#[allow(clippy::type_complexity)]
+fn build_buildings(
+    building_defs: BuildingDefs,
+    palette: Res<TeamPalette>,
+    buildings: Query<(
+        Entity,
+        &Building,
+        &Team,
+        &Transform,
+        &AwaitingDef,
+        Has<ConstructionSite>,
+        Has<Health>,
+    )>,
+    mut commands: Commands,
+) {
+    for (entity, building, team, transform, awaiting, site, has_health) in buildings.iter() {
+        let Some(def) = building_defs.get(&building.0) else {
+            if building_defs.ready() {
+                warn!("There's no building called `{}`", building.0);
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        };
+        let mut building = commands.entity(entity);
+        building.remove::<AwaitingDef>().insert((
+            def.mesh.clone(),
+            palette.normal(*team),
+            Footprint(def.footprint_at(transform.translation.xz())),
+            Selectable,
+            PickableBundle::default(),
+            On::<Pointer<Click>>::run(select_unit),
+        ));
+        // New buildings start unharmed, as do any restored from saves made before buildings
+        // could be damaged
+        if !has_health {
+            building.insert((Health::new(def.health), Armor(def.armor)));
+        }
+        if !site && !awaiting.restored {
+            finish(&mut building, def);
+        }
+    }
+}
+
+// Carry edits to a definition file over to the buildings already standing
+#[allow(clippy::type_complexity)]
+fn reload_building_defs(
+    mut events: EventReader<AssetEvent<BuildingDef>>,
+    building_defs: BuildingDefs,
+    mut buildings: Query<
+        (
+            &Building,
+            &Transform,
+            &mut Footprint,
+            &mut Health,
+            &mut Armor,
+            Option<&mut Vision>,
+            Option<&mut DropOff>,
+        ),
+        Without<AwaitingDef>,
+    >,
+) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(def_id) = building_defs.id_of(*id) else {
+            continue;
+        };
+        let Some(def) = building_defs.get(def_id) else {
+            continue;
+        };
+        for (_, transform, mut footprint, mut health, mut armor, vision, drop_off) in buildings
+            .iter_mut()
+            .filter(|(building, ..)| building.0 == def_id)
+        {
+            footprint.0 = def.footprint_at(transform.translation.xz());
+            health.max = def.health;
+            health.current = health.current.min(def.health);
+            armor.0 = def.armor;
+            if let Some(mut vision) = vision {
+                vision.0 = def.vision;
+            }
+            if let Some(mut drop_off) = drop_off {
+                drop_off.radius = def.size().max_element() * 0.5;
+            }
+        }
+    }
+}
+
+// Give a finished building whatever it does
+fn finish(building: &mut EntityCommands, def: &BuildingDef) {
+    building
+        .remove::<ConstructionSite>()
+        .insert(Vision(def.vision));
+    if def.drop_off {
+        building.insert(DropOff {
+            radius: def.size().max_element() * 0.5,
+        });
+    }
+    if !def.trains.is_empty() {
+        building.insert((ProductionQueue::default(), RallyPoint::default()));
+    }
+}
+
+fn spawn_ghost(mut commands: Commands, mut materials: ResMut<Assets<StandardMaterial>>) {
+    let ghost_material = |color| StandardMaterial {
+        base_color: color,
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    };
+    commands.insert_resource(GhostMaterials {
+        valid: materials.add(ghost_material(Color::srgba(0.3, 0.9, 0.4, 0.4))),
+        invalid: materials.add(ghost_material(Color::srgba(0.9, 0.2, 0.2, 0.4))),
+    });
+    commands.spawn((
+        PbrBundle {
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Ghost::default(),
+        NotShadowCaster,
+        Pickable::IGNORE,
+    ));
+}
+
+fn spawn_placement_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(12.0),
+                top: Val::Px(36.0),
+                ..default()
+            },
+            ..default()
+        },
+        Pickable::IGNORE,
+        PlacementText,
+    ));
+}
+
+// B starts placing a building when builders are selected, and again switches to the next one
+fn building_hotkeys(
+    key_input: Res<ButtonInput<KeyCode>>,
+    builders: Query<(), (With<Selected>, With<Builder>)>,
+    building_defs: BuildingDefs,
+    mut targeting: ResMut<Targeting>,
+) {
+    if !key_input.just_pressed(KeyCode::KeyB) || builders.is_empty() {
+        return;
+    }
+    let ids: Vec<&str> = building_defs.ids().collect();
+    let next = match &targeting.0 {
+        Some(TargetedOrder::Place(id)) => {
+            let index = ids.iter().position(|other| other == id);
+            index.map_or(0, |i| (i + 1) % ids.len())
+        }
+        _ => 0,
+    };
+    if let Some(id) = ids.get(next) {
+        targeting.0 = Some(TargetedOrder::Place(id.to_string()));
+    }
+}
+
+// What stops a building going up somewhere
+#[derive(SystemParam)]
+pub(crate) struct PlacementObstacles<'w, 's> {
+    footprints: Query<'w, 's, &'static Footprint>,
+    units: Query<'w, 's, &'static Transform, (With<Steering>, Without<Ghost>)>,
+    nodes: Query<'w, 's, &'static Transform, (With<ResourceNode>, Without<Ghost>)>,
+}
+
+impl PlacementObstacles<'_, '_> {
+    // True if nothing stands in `area` or too close to it
+    pub(crate) fn clear(&self, area: Rect) -> bool {
+        let padded = area.inflate(CLEARANCE);
+        self.footprints
+            .iter()
+            .all(|footprint| footprint.0.intersect(area).is_empty())
+            && !self
+                .units
+                .iter()
+                .chain(self.nodes.iter())
+                .any(|transform| padded.contains(transform.translation.xz()))
+    }
+}
+
+// Snap the ghost to the grid under the cursor, red wherever the building can't go
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn update_ghost(
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut raycast: Raycast,
+    ground: Query<(), With<Ground>>,
+    minimap: Res<MinimapCursor>,
+    targeting: Res<Targeting>,
+    nav_grid: Res<NavGrid>,
+    stockpiles: Res<Stockpiles>,
+    player: Res<PlayerId>,
+    obstacles: PlacementObstacles,
+    building_defs: BuildingDefs,
+    ghost_materials: Res<GhostMaterials>,
+    mut ghost_q: Query<(
+        Entity,
+        &mut Ghost,
+        &mut Transform,
+        &mut Handle<StandardMaterial>,
+        &mut Visibility,
+    )>,
+    mut commands: Commands,
+) {
+    let Ok((entity, mut ghost, mut transform, mut material, mut visibility)) =
+        ghost_q.get_single_mut()
+    else {
+        return;
+    };
+    let placing = match &targeting.0 {
+        Some(TargetedOrder::Place(id)) => building_defs.get(id).map(|def| (id, def)),
+        _ => None,
+    };
+    // Buildings can't be placed from the minimap
+    let is_ground = |entity| ground.contains(entity);
+    let hit = placing.filter(|_| minimap.0.is_none()).and_then(|placing| {
+        cursor_hit(&windows, &camera_q, &mut raycast, &is_ground).map(|(_, p)| (placing, p))
+    });
+    let Some(((id, def), hit)) = hit else {
+        ghost.spot = None;
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    if ghost.def_id.as_ref() != Some(id) {
+        ghost.def_id = Some(id.clone());
+        // Raycasting reads every mesh handle, so swap it in with a command
+        commands.entity(entity).insert(def.mesh.clone());
+    }
+    let area = def.snapped_footprint(hit.xz());
+    let center = area.center();
+    let height = nav_grid.surface_height(center).unwrap_or(hit.y);
+
+    ghost.spot = Some(Vec3::new(center.x, height, center.y));
+    ghost.valid = nav_grid.is_buildable(area)
+        && obstacles.clear(area)
+        && stockpiles.get(player.0).can_afford(&def.cost);
+    transform.translation = Vec3::new(center.x, height, center.y);
+    *material = if ghost.valid {
+        ghost_materials.valid.clone()
+    } else {
+        ghost_materials.invalid.clone()
+    };
+    *visibility = Visibility::Visible;
+}
+
+// Left-click lays down a construction site where the ghost is and sends the selected builders
+// to work on it. Shift keeps placing more of the same
+fn place_building(
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut targeting: ResMut<Targeting>,
+    ghost_q: Query<&Ghost>,
+    player: Res<PlayerId>,
+    builders: Query<Entity, (With<Selected>, With<Builder>)>,
+    mut commands: EventWriter<PlayerCommand>,
+) {
+    // Fire on release, like picking any other order's target
+    let Some(TargetedOrder::Place(id)) = targeting.0.clone() else {
+        return;
+    };
+    if !mouse_button_input.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(position) = ghost_q
+        .get_single()
+        .ok()
+        .filter(|ghost| ghost.valid)
+        .and_then(|ghost| ghost.spot)
+    else {
+        return;
+    };
+
+    let queue = key_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !queue {
+        targeting.0 = None;
+    }
+    commands.send(PlayerCommand {
+        team: player.0,
+        action: Action::Place {
+            building: id,
+            position,
+            builders: builders.iter().collect(),
+            queue,
+        },
+    });
+}
+
+// Lay down the construction sites players asked for, wherever there's still room and they can
+// still pay for them
+#[allow(clippy::too_many_arguments)]
+fn place_commands(
+    player_commands: Res<TickCommands>,
+    mut stockpiles: ResMut<Stockpiles>,
+    building_defs: BuildingDefs,
+    nav_grid: Res<NavGrid>,
+    obstacles: PlacementObstacles,
+    teams: Query<&Team, With<Builder>>,
+    mut orders: EventWriter<IssueOrder>,
+    mut commands: Commands,
+) {
+    // Sites laid down this tick don't have a footprint yet
+    let mut placed: Vec<Rect> = Vec::new();
+    for command in &player_commands.0 {
+        let Action::Place {
+            building,
+            position,
+            builders,
+            queue,
+        } = &command.action
+        else {
+            continue;
+        };
+        let Some(def) = building_defs.get(building) else {
+            continue;
+        };
+        let area = def.footprint_at(position.xz());
+        let stockpile = stockpiles.get_mut(command.team);
+        if !stockpile.can_afford(&def.cost)
+            || !nav_grid.is_buildable(area)
+            || !obstacles.clear(area)
+            || placed.iter().any(|other| !other.intersect(area).is_empty())
+        {
+            continue;
+        }
+        placed.push(area);
+        stockpile.spend(&def.cost);
+        let site = spawn_building(&mut commands, building, command.team, *position, false);
+        orders.send(IssueOrder {
+            units: builders
+                .iter()
+                .copied()
+                .filter(|builder| teams.get(*builder).is_ok_and(|team| *team == command.team))
+                .collect(),
+            order: Order::Build(site),
+            queue: *queue,
+        });
+    }
+}
+
+fn update_placement_text(
+    targeting: Res<Targeting>,
+    building_defs: BuildingDefs,
+    mut text_q: Query<&mut Text, With<PlacementText>>,
+) {
+    if !targeting.is_changed() {
+        return;
+    }
+    let placing = match &targeting.0 {
+        Some(TargetedOrder::Place(id)) => building_defs.get(id),
+        _ => None,
+    };
+    let value = placing.map_or(String::new(), |def| {
+        format!(
+            "Placing {} ({} wood, {} stone)",
+            def.name, def.cost.wood, def.cost.stone
+        )
+    });
+    for mut text in text_q.iter_mut() {
+        *text = Text::from_section(value.clone(), TextStyle::default());
+    }
+}
+
+// Builders walk up to their construction site and put work in until it's finished
+#[allow(clippy::type_complexity)]
+fn construct(
+    time: Res<Time>,
+    nav_grid: Res<NavGrid>,
+    building_defs: BuildingDefs,
+    builders: Query<(Entity, &Transform, &Builder, &ActiveOrder, Has<Path>)>,
+    mut sites: Query<
+        (&Building, &Footprint, &mut ConstructionSite, &mut Transform),
+        Without<Builder>,
+    >,
+    mut commands: Commands,
+) {
+    for (entity, transform, builder, order, walking) in builders.iter() {
+        let UnitOrder::Build(site) = order.0 else {
+            continue;
+        };
+        let Ok((building, footprint, mut construction, mut site_transform)) = sites.get_mut(site)
+        else {
+            // Finished, or gone
+            commands.entity(entity).remove::<(ActiveOrder, Path)>();
+            continue;
+        };
+
+        let position = transform.translation;
+        let closest = position.xz().clamp(footprint.0.min, footprint.0.max);
+        if position.xz().distance(closest) > BUILD_DISTANCE {
+            if !walking {
+                let target = Vec3::new(closest.x, position.y, closest.y);
+                walk_towards(
+                    &mut commands,
+                    &nav_grid,
+                    entity,
+                    position,
+                    target,
+                    BUILD_DISTANCE,
+                );
+            }
+            continue;
+        }
+        if walking {
+            commands.entity(entity).remove::<Path>();
+        }
+
+        let Some(def) = building_defs.get(&building.0) else {
+            continue;
+        };
+        let before = construction.progress;
+        construction.progress += builder.rate * time.delta_seconds();
+        let done = (construction.progress / def.build_time).min(1.0);
+        site_transform.scale.y = SITE_MIN_HEIGHT + (1.0 - SITE_MIN_HEIGHT) * done;
+        // Only the builder that puts in the last bit of work finishes it
+        if construction.progress >= def.build_time && before < def.build_time {
+            finish(&mut commands.entity(site), def);
+        }
+    }
+}
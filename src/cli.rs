@@ -0,0 +1,35 @@
+/// The value of `--name value` or `--name=value`, if it was given. The last one wins if it's
+/// repeated.
+pub fn arg(name: &str) -> Option<String> {
+    let flag = format!("--{name}");
+    let prefix = format!("{flag}=");
+    let mut args = std::env::args().skip(1);
+    let mut value = None;
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            value = args.next();
+        } else if let Some(rest) = arg.strip_prefix(&prefix) {
+            value = Some(rest.to_string());
+        }
+    }
+    value
+}
+
+/// The value of the option `--name` parsed as a `T`. A value that doesn't parse is reported and
+/// ignored.
+pub fn parsed_arg<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = arg(name)?;
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            eprintln!("Ignoring --{name} {value}, it isn't valid");
+            None
+        }
+    }
+}
+
+/// Whether the switch `--name` was given.
+pub fn flag(name: &str) -> bool {
+    let flag = format!("--{name}");
+    std::env::args().skip(1).any(|arg| arg == flag)
+}
@@ -0,0 +1,389 @@
+use bevy::ecs::entity::{EntityMapper, MapEntities};
+use bevy::ecs::reflect::ReflectMapEntities;
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::Pickable;
+use serde::Deserialize;
+
+use crate::building::Building;
+use crate::fog::{FogOfWar, FogSet};
+use crate::navigation::Footprint;
+use crate::orders::{ActiveOrder, UnitOrder};
+use crate::sim::{Interpolated, SimId, SimSet};
+use crate::steering::SpatialHash;
+use crate::team::Team;
+
+// Projectiles this close to their target have hit it
+const PROJECTILE_HIT_DISTANCE: f32 = 0.3;
+// How long a hitscan shot stays on screen
+const TRACER_SECONDS: f32 = 0.08;
+// A target may drift this far out of range before it's dropped, so units don't flicker
+// between targets right at the edge
+const RANGE_SLACK: f32 = 0.5;
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Health>()
+            .register_type::<Armor>()
+            .register_type::<Weapon>()
+            .register_type::<AttackTarget>()
+            .add_event::<UnitDied>()
+            .add_event::<BuildingDestroyed>()
+            .add_systems(
+                FixedUpdate,
+                (acquire_targets, fire_weapons, move_projectiles, kill_units)
+                    .chain()
+                    .after(FogSet)
+                    .in_set(SimSet::Update),
+            )
+            .add_systems(Update, (draw_tracers, draw_health_bars));
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+}
+
+/// Flat reduction applied to every hit. A hit always does at least 1 damage.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Armor(pub f32);
+
+#[derive(Clone, Copy, Debug, Deserialize, Reflect)]
+pub enum Delivery {
+    /// Hits the moment it fires.
+    Hitscan,
+    /// Fires a homing projectile travelling at `speed`.
+    Projectile { speed: f32 },
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Weapon {
+    pub range: f32,
+    pub damage: f32,
+    /// Seconds between shots.
+    pub cooldown: f32,
+    pub delivery: Delivery,
+    /// Seconds until the weapon can fire again.
+    pub ready_in: f32,
+}
+
+impl Weapon {
+    pub fn new(range: f32, damage: f32, cooldown: f32, delivery: Delivery) -> Self {
+        Self {
+            range,
+            damage,
+            cooldown,
+            delivery,
+            ready_in: 0.0,
+        }
+    }
+}
+
+/// The unit this one's weapon is aimed at.
+#[derive(Component, Reflect)]
+#[reflect(Component, MapEntities)]
+pub struct AttackTarget(pub Entity);
+
+impl MapEntities for AttackTarget {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.0 = entity_mapper.map_entity(self.0);
+    }
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    pub target: Entity,
+    pub damage: f32,
+    pub speed: f32,
+}
+
+#[derive(Component)]
+struct Tracer {
+    from: Vec3,
+    to: Vec3,
+    remaining: f32,
+}
+
+/// Sent when a unit's health runs out. The entity is despawned in the same frame, which also
+/// takes it out of the selection.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct UnitDied {
+    pub entity: Entity,
+    pub team: Team,
+}
+
+/// Sent when a building's health runs out, like `UnitDied` is for units.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BuildingDestroyed {
+    pub entity: Entity,
+    pub team: Team,
+}
+
+/// The part of a target nearest to `from`: the edge of a building's footprint, or where a unit
+/// stands. Weapon range is measured to it.
+pub fn nearest_point(from: Vec2, position: Vec3, footprint: Option<&Footprint>) -> Vec2 {
+    match footprint {
+        Some(footprint) => from.clamp(footprint.0.min, footprint.0.max),
+        None => position.xz(),
+    }
+}
+
+// Damage left after armor
+fn mitigate(damage: f32, armor: Option<&Armor>) -> f32 {
+    (damage - armor.map_or(0.0, |a| a.0)).max(1.0)
+}
+
+// Keep the current target while it's alive and in range, otherwise pick the nearest enemy
+// unit, or failing that the nearest enemy building
+#[allow(clippy::type_complexity)]
+fn acquire_targets(
+    hash: Res<SpatialHash>,
+    attackers: Query<(
+        Entity,
+        &Transform,
+        &Weapon,
+        &Team,
+        Option<&AttackTarget>,
+        Option<&ActiveOrder>,
+    )>,
+    targets: Query<(&Transform, &Team, Option<&Footprint>), With<Health>>,
+    buildings: Query<(Entity, &Transform, &Team, &Footprint, &SimId), With<Health>>,
+    fog: Res<FogOfWar>,
+    mut commands: Commands,
+) {
+    for (entity, transform, weapon, team, current, order) in attackers.iter() {
+        let position = transform.translation.xz();
+        match order {
+            // An attack order chose its target already
+            Some(ActiveOrder(UnitOrder::Attack(_))) => continue,
+            // Units sent somewhere with a plain move or follow don't stop to fight
+            Some(ActiveOrder(UnitOrder::Move(_) | UnitOrder::Follow(_))) => {
+                if current.is_some() {
+                    commands.entity(entity).remove::<AttackTarget>();
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let keep = current.is_some_and(|AttackTarget(target)| {
+            targets
+                .get(*target)
+                .is_ok_and(|(target_transform, _, footprint)| {
+                    let closest = nearest_point(position, target_transform.translation, footprint);
+                    closest.distance(position) <= weapon.range + RANGE_SLACK
+                })
+        });
+        if keep {
+            continue;
+        }
+
+        let nearest_unit = hash
+            .query(position, weapon.range)
+            .filter(|n| {
+                // Only enemies the team can actually see
+                targets
+                    .get(n.entity)
+                    .is_ok_and(|(target_transform, other, _)| {
+                        other != team && fog.is_visible(*team, target_transform.translation)
+                    })
+            })
+            .min_by(|a, b| {
+                let a = a.position.distance_squared(position);
+                let b = b.position.distance_squared(position);
+                a.total_cmp(&b)
+            })
+            .map(|neighbour| neighbour.entity);
+        // Ties go to the oldest building, so every run picks the same one
+        let nearest_building = || {
+            buildings
+                .iter()
+                .filter(|(_, building_transform, other, ..)| {
+                    *other != team && fog.is_visible(*team, building_transform.translation)
+                })
+                .map(|(building, building_transform, _, footprint, id)| {
+                    let closest =
+                        nearest_point(position, building_transform.translation, Some(footprint));
+                    (closest.distance(position), *id, building)
+                })
+                .filter(|(distance, ..)| *distance <= weapon.range)
+                .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+                .map(|(.., building)| building)
+        };
+        match nearest_unit.or_else(nearest_building) {
+            Some(target) => {
+                commands.entity(entity).insert(AttackTarget(target));
+            }
+            None if current.is_some() => {
+                commands.entity(entity).remove::<AttackTarget>();
+            }
+            None => {}
+        }
+    }
+}
+
+fn fire_weapons(
+    time: Res<Time>,
+    mut attackers: Query<(&Transform, &mut Weapon, Option<&AttackTarget>)>,
+    mut targets: Query<(&Transform, &mut Health, Option<&Armor>, Option<&Footprint>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut projectile_assets: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_seconds();
+    for (transform, mut weapon, target) in attackers.iter_mut() {
+        weapon.ready_in = (weapon.ready_in - dt).max(0.0);
+        let Some(AttackTarget(target)) = target else {
+            continue;
+        };
+        if weapon.ready_in > 0.0 {
+            continue;
+        }
+        let Ok((target_transform, mut health, armor, footprint)) = targets.get_mut(*target) else {
+            continue;
+        };
+        let from = transform.translation;
+        let to = target_transform.translation;
+        if from.xz().distance(nearest_point(from.xz(), to, footprint)) > weapon.range + RANGE_SLACK
+        {
+            continue;
+        }
+
+        weapon.ready_in = weapon.cooldown;
+        match weapon.delivery {
+            Delivery::Hitscan => {
+                health.current -= mitigate(weapon.damage, armor);
+                commands.spawn(Tracer {
+                    from,
+                    to,
+                    remaining: TRACER_SECONDS,
+                });
+            }
+            Delivery::Projectile { speed } => {
+                let (mesh, material) = projectile_assets.get_or_insert_with(|| {
+                    (
+                        meshes.add(Sphere::new(0.08)),
+                        materials.add(StandardMaterial {
+                            base_color: Color::srgb(1.0, 0.8, 0.2),
+                            unlit: true,
+                            ..default()
+                        }),
+                    )
+                });
+                commands.spawn((
+                    PbrBundle {
+                        mesh: mesh.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_translation(from),
+                        ..default()
+                    },
+                    Projectile {
+                        target: *target,
+                        damage: weapon.damage,
+                        speed,
+                    },
+                    Interpolated::new(from),
+                    Pickable::IGNORE,
+                ));
+            }
+        }
+    }
+}
+
+fn move_projectiles(
+    time: Res<Time>,
+    mut projectiles: Query<(Entity, &mut Transform, &Projectile)>,
+    mut targets: Query<(&Transform, &mut Health, Option<&Armor>), Without<Projectile>>,
+    mut commands: Commands,
+) {
+    for (entity, mut transform, projectile) in projectiles.iter_mut() {
+        // Projectiles whose target died on the way simply fizzle out
+        let Ok((target_transform, mut health, armor)) = targets.get_mut(projectile.target) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let to_target = target_transform.translation - transform.translation;
+        let step = projectile.speed * time.delta_seconds();
+        if to_target.length() <= step + PROJECTILE_HIT_DISTANCE {
+            health.current -= mitigate(projectile.damage, armor);
+            commands.entity(entity).despawn();
+        } else {
+            transform.translation += to_target.normalize() * step;
+        }
+    }
+}
+
+fn kill_units(
+    units: Query<(Entity, &Health, &Team, Has<Building>)>,
+    mut deaths: EventWriter<UnitDied>,
+    mut destroyed: EventWriter<BuildingDestroyed>,
+    mut commands: Commands,
+) {
+    for (entity, health, team, building) in units.iter() {
+        if health.current > 0.0 {
+            continue;
+        }
+        if building {
+            destroyed.send(BuildingDestroyed {
+                entity,
+                team: *team,
+            });
+        } else {
+            deaths.send(UnitDied {
+                entity,
+                team: *team,
+            });
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn draw_tracers(
+    time: Res<Time>,
+    mut tracers: Query<(Entity, &mut Tracer)>,
+    mut gizmos: Gizmos,
+    mut commands: Commands,
+) {
+    for (entity, mut tracer) in tracers.iter_mut() {
+        gizmos.line(tracer.from, tracer.to, Color::srgb(1.0, 0.9, 0.5));
+        tracer.remaining -= time.delta_seconds();
+        if tracer.remaining <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// A bar above every damaged unit, green shrinking to red
+fn draw_health_bars(units: Query<(&Transform, &Health)>, mut gizmos: Gizmos) {
+    const WIDTH: f32 = 0.8;
+    for (transform, health) in units.iter() {
+        let fraction = (health.current / health.max).clamp(0.0, 1.0);
+        if fraction >= 1.0 {
+            continue;
+        }
+        let left = transform.translation + Vec3::new(-WIDTH / 2.0, 1.0, 0.0);
+        gizmos.line(
+            left,
+            left + Vec3::X * WIDTH,
+            Color::srgba(0.1, 0.1, 0.1, 0.8),
+        );
+        gizmos.line(
+            left,
+            left + Vec3::X * WIDTH * fraction,
+            Color::srgb(1.0 - fraction, fraction, 0.0),
+        );
+    }
+}
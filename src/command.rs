@@ -0,0 +1,131 @@
+use bevy::ecs::entity::{EntityHashMap, EntityMapper, MapEntities};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::orders::{IssueOrder, Order};
+use crate::sim::{SimId, SimSet};
+use crate::team::Team;
+
+/// Turns what players ask for into changes to the simulation, on the next tick.
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerCommand>()
+            .init_resource::<TickCommands>()
+            .configure_sets(
+                FixedUpdate,
+                (CommandSet::Local, CommandSet::Collect, CommandSet::Apply)
+                    .chain()
+                    .in_set(SimSet::Commands),
+            )
+            .add_systems(FixedUpdate, collect_commands.in_set(CommandSet::Local));
+    }
+}
+
+/// `TickCommands` is filled in `Local` and `Collect` and carried out in `Apply`, so anything
+/// between the two sees exactly what this tick will do.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum CommandSet {
+    /// Commands given here since the last tick.
+    Local,
+    /// Whatever else decides what this tick carries out, such as a replay or other players,
+    /// replaces or adds to them.
+    Collect,
+    Apply,
+}
+
+/// Something a player asks of the simulation. Input handling (or an AI) sends these rather than
+/// changing the world itself, so a match is its scenario plus the commands given in it.
+#[derive(Event, Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerCommand {
+    /// Who gave the command. Only that team's units and buildings take it.
+    pub team: Team,
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Action {
+    /// Give an order to a group of units.
+    Order(IssueOrder),
+    /// Pay for a construction site of `building` centred on `position` and send `builders` to
+    /// work on it.
+    Place {
+        building: String,
+        position: Vec3,
+        builders: Vec<Entity>,
+        /// Add the work to the end of the builders' queues.
+        queue: bool,
+    },
+    /// Queue the unit in slot `slot` of what the buildings train, at the least busy of them.
+    Train { buildings: Vec<Entity>, slot: usize },
+    /// Take back the unit queued last at the busiest of the buildings, with a full refund.
+    CancelTraining { buildings: Vec<Entity> },
+}
+
+impl PlayerCommand {
+    pub fn order(team: Team, units: Vec<Entity>, order: Order, queue: bool) -> Self {
+        Self {
+            team,
+            action: Action::Order(IssueOrder {
+                units,
+                order,
+                queue,
+            }),
+        }
+    }
+}
+
+impl MapEntities for PlayerCommand {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        let entities = match &mut self.action {
+            Action::Order(order) => {
+                order.map_entities(entity_mapper);
+                return;
+            }
+            Action::Place { builders, .. } => builders,
+            Action::Train { buildings, .. } | Action::CancelTraining { buildings } => buildings,
+        };
+        for entity in entities.iter_mut() {
+            *entity = entity_mapper.map_entity(*entity);
+        }
+    }
+}
+
+/// Swaps the entities in commands for stand-ins whose index is their `SimId`, which is how
+/// commands are written down and sent elsewhere, or back again. Entities with no counterpart
+/// become `Entity::PLACEHOLDER`.
+pub struct EntitySwap(EntityHashMap<Entity>);
+
+impl EntitySwap {
+    pub fn to_stand_ins(ids: &Query<(Entity, &SimId)>) -> Self {
+        Self(
+            ids.iter()
+                .map(|(entity, id)| (entity, Entity::from_raw(id.0)))
+                .collect(),
+        )
+    }
+
+    pub fn from_stand_ins(ids: &Query<(Entity, &SimId)>) -> Self {
+        Self(
+            ids.iter()
+                .map(|(entity, id)| (Entity::from_raw(id.0), entity))
+                .collect(),
+        )
+    }
+}
+
+impl EntityMapper for EntitySwap {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        self.0.get(&entity).copied().unwrap_or(Entity::PLACEHOLDER)
+    }
+}
+
+/// The commands this tick carries out, in the order they were given.
+#[derive(Resource, Default)]
+pub struct TickCommands(pub Vec<PlayerCommand>);
+
+fn collect_commands(mut events: EventReader<PlayerCommand>, mut commands: ResMut<TickCommands>) {
+    commands.0.clear();
+    commands.0.extend(events.read().cloned());
+}
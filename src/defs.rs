@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, LoadedFolder};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+use crate::building::BuildingDef;
+use crate::unit::UnitDef;
+
+pub struct DefsPlugin;
+
+impl Plugin for DefsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<UnitDef>()
+            .init_asset::<BuildingDef>()
+            .register_asset_loader(DefLoader::<UnitDef>::new(&["unit.ron"]))
+            .register_asset_loader(DefLoader::<BuildingDef>::new(&["building.ron"]))
+            .init_resource::<Defs>()
+            .add_systems(Startup, load_defs)
+            .add_systems(PreUpdate, index_defs.in_set(DefsSet));
+    }
+}
+
+/// Definitions are looked up by id during this set. Anything spawned from a definition is
+/// filled in after it.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct DefsSet;
+
+/// Something read from a RON file under assets/, with a mesh made from it while it loads.
+pub trait Def: Asset + DeserializeOwned {
+    fn build_mesh(&self) -> Mesh;
+    fn set_mesh(&mut self, mesh: Handle<Mesh>);
+    /// The definitions of this type in the registry, by id.
+    fn index(defs: &Defs) -> &BTreeMap<String, Handle<Self>>;
+}
+
+/// Marks an entity spawned from a definition that hasn't loaded yet. It only has a team and a
+/// position until then.
+#[derive(Component, Default)]
+pub struct AwaitingDef {
+    /// Restored from a save with its state already in place, so it only needs drawing.
+    pub restored: bool,
+}
+
+/// Every unit and building definition, by id. A definition's id is its file name without the
+/// extensions, so assets/units/worker.unit.ron is `worker`.
+#[derive(Resource, Default)]
+pub struct Defs {
+    folders: Vec<Handle<LoadedFolder>>,
+    units: BTreeMap<String, Handle<UnitDef>>,
+    buildings: BTreeMap<String, Handle<BuildingDef>>,
+    // Every folder and everything in it has loaded
+    ready: bool,
+}
+
+/// Read access to the loaded definitions of one type.
+#[derive(SystemParam)]
+pub struct DefAssets<'w, T: Def> {
+    defs: Res<'w, Defs>,
+    assets: Res<'w, Assets<T>>,
+}
+
+pub type UnitDefs<'w> = DefAssets<'w, UnitDef>;
+pub type BuildingDefs<'w> = DefAssets<'w, BuildingDef>;
+
+impl<T: Def> DefAssets<'_, T> {
+    pub fn get(&self, id: &str) -> Option<&T> {
+        self.assets.get(T::index(&self.defs).get(id)?)
+    }
+
+    /// Every id, in alphabetical order.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        T::index(&self.defs).keys().map(String::as_str)
+    }
+
+    /// The id of a loaded definition.
+    pub fn id_of(&self, asset: AssetId<T>) -> Option<&str> {
+        T::index(&self.defs)
+            .iter()
+            .find(|(_, handle)| handle.id() == asset)
+            .map(|(id, _)| id.as_str())
+    }
+
+    /// True once everything has loaded, so an id that isn't found doesn't exist.
+    pub fn ready(&self) -> bool {
+        self.defs.ready
+    }
+}
+
+impl Def for UnitDef {
+    fn build_mesh(&self) -> Mesh {
+        Capsule3d::new(self.radius, self.length).into()
+    }
+
+    fn set_mesh(&mut self, mesh: Handle<Mesh>) {
+        self.mesh = mesh;
+    }
+
+    fn index(defs: &Defs) -> &BTreeMap<String, Handle<Self>> {
+        &defs.units
+    }
+}
+
+impl Def for BuildingDef {
+    // A box with its origin at the middle of its base
+    fn build_mesh(&self) -> Mesh {
+        let size = self.size();
+        Mesh::from(Cuboid::new(size.x, self.height, size.y))
+            .translated_by(Vec3::Y * self.height * 0.5)
+    }
+
+    fn set_mesh(&mut self, mesh: Handle<Mesh>) {
+        self.mesh = mesh;
+    }
+
+    fn index(defs: &Defs) -> &BTreeMap<String, Handle<Self>> {
+        &defs.buildings
+    }
+}
+
+struct DefLoader<T> {
+    extensions: &'static [&'static str],
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> DefLoader<T> {
+    fn new(extensions: &'static [&'static str]) -> Self {
+        Self {
+            extensions,
+            marker: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DefLoaderError {
+    #[error("could not read definition: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse definition: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl<T: Def> AssetLoader for DefLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = DefLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<T, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut def: T = ron::de::from_bytes(&bytes)?;
+        let mesh = load_context.add_labeled_asset("mesh".to_string(), def.build_mesh());
+        def.set_mesh(mesh);
+        Ok(def)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+fn load_defs(asset_server: Res<AssetServer>, mut defs: ResMut<Defs>) {
+    defs.folders = vec![
+        asset_server.load_folder("units"),
+        asset_server.load_folder("buildings"),
+    ];
+}
+
+// Rebuild the ids whenever a folder of definitions finishes loading
+fn index_defs(
+    mut events: EventReader<AssetEvent<LoadedFolder>>,
+    asset_server: Res<AssetServer>,
+    folders: Res<Assets<LoadedFolder>>,
+    mut defs: ResMut<Defs>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let defs = &mut *defs;
+    defs.units.clear();
+    defs.buildings.clear();
+    let handles = defs
+        .folders
+        .iter()
+        .filter_map(|folder| folders.get(folder))
+        .flat_map(|folder| folder.handles.iter());
+    for handle in handles {
+        let Some(id) = handle.path().and_then(|path| {
+            let name = path.path().file_name()?.to_str()?;
+            name.split('.').next().map(str::to_string)
+        }) else {
+            continue;
+        };
+        if let Ok(unit) = handle.clone().try_typed::<UnitDef>() {
+            defs.units.insert(id, unit);
+        } else if let Ok(building) = handle.clone().try_typed::<BuildingDef>() {
+            defs.buildings.insert(id, building);
+        }
+    }
+    defs.ready = defs
+        .folders
+        .iter()
+        .all(|folder| asset_server.is_loaded_with_dependencies(folder));
+}
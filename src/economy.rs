@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::Pickable;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::navigation::{NavGrid, Path};
+use crate::orders::{ActiveOrder, OrderSet, UnitOrder};
+use crate::sim::{assign_sim_id, SimRng, SimSet};
+use crate::steering::SteeringSet;
+use crate::team::{PlayerId, Team};
+use crate::tree::GrowTree;
+
+// Gatherers this close to a node can harvest from it
+const GATHER_DISTANCE: f32 = 1.0;
+// Gatherers this close to the edge of a drop-off can unload there
+const DROP_OFF_DISTANCE: f32 = 1.0;
+// When a node runs out, its gatherers move on to another of the same kind within this distance
+const NODE_SEARCH_RADIUS: f32 = 12.0;
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ResourceNode>()
+            .register_type::<DropOff>()
+            .register_type::<Gatherer>()
+            .init_resource::<Stockpiles>()
+            .init_resource::<IncomeMultipliers>()
+            .add_systems(Startup, (spawn_stockpile_text, create_node_materials))
+            .add_systems(
+                FixedUpdate,
+                gather
+                    .after(OrderSet)
+                    .before(SteeringSet)
+                    .in_set(SimSet::Update),
+            )
+            .add_systems(Update, (update_stockpile_text, draw_resource_nodes));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Reflect)]
+pub enum ResourceKind {
+    Wood,
+    Stone,
+}
+
+/// Something gatherers can harvest, like a tree or a rock. Despawned once it runs out.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct ResourceNode {
+    pub kind: ResourceKind,
+    pub amount: u32,
+}
+
+/// A building gatherers bring their loads back to.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct DropOff {
+    /// Rough half-width of the building's footprint.
+    pub radius: f32,
+}
+
+/// A unit that can harvest resource nodes and carry what it gathers to a drop-off.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Gatherer {
+    /// Most the unit can carry at once.
+    pub capacity: u32,
+    /// Units of resource harvested per second.
+    pub rate: f32,
+    pub carrying: u32,
+    /// What the current load is, if any.
+    pub kind: Option<ResourceKind>,
+    progress: f32,
+    returning: bool,
+}
+
+impl Gatherer {
+    pub fn new(capacity: u32, rate: f32) -> Self {
+        Self {
+            capacity,
+            rate,
+            carrying: 0,
+            kind: None,
+            progress: 0.0,
+            returning: false,
+        }
+    }
+}
+
+/// Resources a player has banked.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stockpile {
+    pub wood: u32,
+    pub stone: u32,
+}
+
+impl Stockpile {
+    pub fn can_afford(&self, cost: &Stockpile) -> bool {
+        self.wood >= cost.wood && self.stone >= cost.stone
+    }
+
+    pub fn spend(&mut self, cost: &Stockpile) {
+        self.wood -= cost.wood;
+        self.stone -= cost.stone;
+    }
+
+    pub fn refund(&mut self, cost: &Stockpile) {
+        self.wood += cost.wood;
+        self.stone += cost.stone;
+    }
+
+    pub fn add(&mut self, kind: ResourceKind, amount: u32) {
+        match kind {
+            ResourceKind::Wood => self.wood += amount,
+            ResourceKind::Stone => self.stone += amount,
+        }
+    }
+}
+
+/// Every team's `Stockpile`.
+#[derive(Resource, Default)]
+pub struct Stockpiles(pub HashMap<Team, Stockpile>);
+
+impl Stockpiles {
+    pub fn get(&self, team: Team) -> Stockpile {
+        self.0.get(&team).copied().unwrap_or_default()
+    }
+
+    pub fn get_mut(&mut self, team: Team) -> &mut Stockpile {
+        self.0.entry(team).or_default()
+    }
+}
+
+/// How much of what a team's gatherers bring in it actually banks, for handicaps and AI
+/// difficulty. Teams that aren't listed bank what they bring. Part of a match's settings, so
+/// replays record it.
+#[derive(Resource, Default)]
+pub struct IncomeMultipliers(pub HashMap<Team, f32>);
+
+impl IncomeMultipliers {
+    pub fn get(&self, team: Team) -> f32 {
+        self.0.get(&team).copied().unwrap_or(1.0)
+    }
+
+    /// What `amount` brought in by `team` is worth.
+    pub fn apply(&self, team: Team, amount: u32) -> u32 {
+        (amount as f32 * self.get(team)).round() as u32
+    }
+}
+
+#[derive(Component)]
+struct StockpileText;
+
+#[derive(Resource)]
+struct NodeMaterials {
+    stone: Handle<StandardMaterial>,
+    rock: Handle<Mesh>,
+}
+
+/// Spawn a resource node on the ground at `position`. It's given a tree or a rock to look like
+/// once it's in the world.
+pub fn spawn_resource_node(
+    commands: &mut Commands,
+    kind: ResourceKind,
+    amount: u32,
+    position: Vec3,
+) -> Entity {
+    let transform = match kind {
+        ResourceKind::Wood => Transform::from_translation(position).with_scale(Vec3::splat(0.6)),
+        // Half buried
+        ResourceKind::Stone => Transform::from_translation(position + Vec3::Y * 0.3),
+    };
+    commands
+        .spawn((
+            SpatialBundle::from_transform(transform),
+            ResourceNode { kind, amount },
+        ))
+        .add(assign_sim_id)
+        .id()
+}
+
+fn create_node_materials(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(NodeMaterials {
+        stone: materials.add(Color::srgb(0.55, 0.55, 0.6)),
+        rock: meshes.add(Sphere::new(0.8)),
+    });
+}
+
+// Every tree grows its own shape
+#[allow(clippy::type_complexity)]
+fn draw_resource_nodes(
+    node_materials: Res<NodeMaterials>,
+    sim_rng: Res<SimRng>,
+    nodes: Query<(Entity, &ResourceNode, &Transform), (Without<Handle<Mesh>>, Without<GrowTree>)>,
+    mut commands: Commands,
+) {
+    for (entity, node, transform) in nodes.iter() {
+        match node.kind {
+            ResourceKind::Wood => {
+                // Seeded by where the tree stands, so it grows the same after loading a save
+                let position = transform.translation.xz();
+                let mut rng = sim_rng.stream(
+                    u64::from(position.x.to_bits()) << 32 | u64::from(position.y.to_bits()),
+                );
+                commands.entity(entity).insert(GrowTree { seed: rng.gen() });
+            }
+            ResourceKind::Stone => {
+                commands
+                    .entity(entity)
+                    .insert((node_materials.rock.clone(), node_materials.stone.clone()));
+            }
+        }
+    }
+}
+
+fn spawn_stockpile_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(12.0),
+                top: Val::Px(12.0),
+                ..default()
+            },
+            ..default()
+        },
+        Pickable::IGNORE,
+        StockpileText,
+    ));
+}
+
+fn update_stockpile_text(
+    stockpiles: Res<Stockpiles>,
+    player: Res<PlayerId>,
+    mut text_q: Query<&mut Text, With<StockpileText>>,
+) {
+    if !stockpiles.is_changed() {
+        return;
+    }
+    let stockpile = stockpiles.get(player.0);
+    for mut text in text_q.iter_mut() {
+        *text = Text::from_section(
+            format!("Wood: {}   Stone: {}", stockpile.wood, stockpile.stone),
+            TextStyle::default(),
+        );
+    }
+}
+
+// Plan a path to just inside `reach` of `target`
+pub(crate) fn walk_towards(
+    commands: &mut Commands,
+    nav_grid: &NavGrid,
+    entity: Entity,
+    from: Vec3,
+    target: Vec3,
+    reach: f32,
+) {
+    let away = (from - target).xz().normalize_or_zero() * (reach * 0.5);
+    let goal = target + Vec3::new(away.x, 0.0, away.y);
+    if let Some(waypoints) = nav_grid.find_path(from, goal) {
+        commands.entity(entity).insert(Path(waypoints.into()));
+    }
+}
+
+// Walk to the node, harvest until full, carry the load to the nearest drop-off and go back
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn gather(
+    time: Res<Time>,
+    nav_grid: Res<NavGrid>,
+    mut stockpiles: ResMut<Stockpiles>,
+    multipliers: Res<IncomeMultipliers>,
+    mut gatherers: Query<(
+        Entity,
+        &Transform,
+        &Team,
+        &mut Gatherer,
+        &mut ActiveOrder,
+        Has<Path>,
+    )>,
+    mut nodes: Query<(Entity, &Transform, &mut ResourceNode)>,
+    drop_offs: Query<(&Transform, &Team, &DropOff)>,
+    mut commands: Commands,
+) {
+    for (entity, transform, team, mut gatherer, mut order, walking) in gatherers.iter_mut() {
+        let ActiveOrder(UnitOrder::Gather(node)) = *order else {
+            continue;
+        };
+        let position = transform.translation;
+
+        if gatherer.returning {
+            let nearest = drop_offs
+                .iter()
+                .filter(|(_, owner, _)| *owner == team)
+                .min_by(|(a, ..), (b, ..)| {
+                    let a = a.translation.distance_squared(position);
+                    let b = b.translation.distance_squared(position);
+                    a.total_cmp(&b)
+                });
+            let Some((drop_off_transform, _, drop_off)) = nearest else {
+                // Nowhere to take the load
+                commands.entity(entity).remove::<ActiveOrder>();
+                continue;
+            };
+            let target = drop_off_transform.translation;
+            let reach = drop_off.radius + DROP_OFF_DISTANCE;
+            if position.xz().distance(target.xz()) <= reach {
+                if let Some(kind) = gatherer.kind {
+                    let amount = multipliers.apply(*team, gatherer.carrying);
+                    stockpiles.get_mut(*team).add(kind, amount);
+                }
+                gatherer.carrying = 0;
+                gatherer.returning = false;
+                commands.entity(entity).remove::<Path>();
+            } else if !walking {
+                walk_towards(&mut commands, &nav_grid, entity, position, target, reach);
+            }
+            continue;
+        }
+
+        // Move on to the closest node of the same kind once this one is gone
+        let node = nodes
+            .get(node)
+            .ok()
+            .filter(|(.., n)| n.amount > 0)
+            .map(|(e, ..)| e);
+        let node = node.or_else(|| {
+            let (kind, node_position) = (gatherer.kind?, position.xz());
+            nodes
+                .iter()
+                .filter(|(_, t, n)| {
+                    n.kind == kind
+                        && n.amount > 0
+                        && t.translation.xz().distance(node_position) <= NODE_SEARCH_RADIUS
+                })
+                .min_by(|(_, a, _), (_, b, _)| {
+                    let a = a.translation.xz().distance_squared(node_position);
+                    let b = b.translation.xz().distance_squared(node_position);
+                    a.total_cmp(&b)
+                })
+                .map(|(e, ..)| e)
+        });
+        let Some(node) = node else {
+            if gatherer.carrying > 0 {
+                gatherer.returning = true;
+            } else {
+                commands.entity(entity).remove::<ActiveOrder>();
+            }
+            continue;
+        };
+        order.0 = UnitOrder::Gather(node);
+
+        let (node, node_transform, mut resource) = nodes.get_mut(node).unwrap();
+        let target = node_transform.translation;
+        if position.xz().distance(target.xz()) > GATHER_DISTANCE {
+            if !walking {
+                walk_towards(
+                    &mut commands,
+                    &nav_grid,
+                    entity,
+                    position,
+                    target,
+                    GATHER_DISTANCE,
+                );
+            }
+            continue;
+        }
+        if walking {
+            commands.entity(entity).remove::<Path>();
+        }
+
+        // Switching to a different resource drops whatever was being carried
+        if gatherer.kind != Some(resource.kind) {
+            gatherer.kind = Some(resource.kind);
+            gatherer.carrying = 0;
+        }
+        let before = resource.amount;
+        gatherer.progress += gatherer.rate * time.delta_seconds();
+        while gatherer.progress >= 1.0
+            && gatherer.carrying < gatherer.capacity
+            && resource.amount > 0
+        {
+            gatherer.progress -= 1.0;
+            gatherer.carrying += 1;
+            resource.amount -= 1;
+        }
+        if resource.amount == 0 && before > 0 {
+            commands.entity(node).despawn_recursive();
+        }
+        if gatherer.carrying >= gatherer.capacity {
+            gatherer.progress = 0.0;
+            gatherer.returning = true;
+        }
+    }
+}
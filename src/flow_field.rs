@@ -0,0 +1,200 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use bevy::prelude::*;
+
+use crate::navigation::{Frontier, NavGrid};
+use crate::orders::OrderSet;
+use crate::sim::SimSet;
+use crate::steering::{Steering, SteeringSet};
+
+/// Orders given to at least this many units at once share a flow field instead of running A*
+/// for every unit.
+pub const FLOW_FIELD_MIN_GROUP: usize = 10;
+
+pub struct FlowFieldPlugin;
+
+impl Plugin for FlowFieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<FlowGoal>()
+            .init_resource::<FlowFields>()
+            .add_systems(
+                FixedUpdate,
+                (invalidate_flow_fields, follow_flow_field)
+                    .chain()
+                    .after(OrderSet)
+                    .before(SteeringSet)
+                    .in_set(SimSet::Update),
+            );
+    }
+}
+
+/// Order to walk to `position` by following the shared flow field towards `cell`.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct FlowGoal {
+    pub cell: IVec2,
+    pub position: Vec3,
+}
+
+/// Direction of travel for every cell of the `NavGrid`, all leading to a single goal cell.
+pub struct FlowField {
+    directions: Vec<Vec2>,
+}
+
+impl FlowField {
+    fn build(grid: &NavGrid, goal: IVec2) -> Self {
+        // Integration field: walking cost from every cell to the goal
+        let mut cost = vec![f32::INFINITY; grid.len()];
+        let mut open = BinaryHeap::new();
+        if let Some(index) = grid.index(goal) {
+            cost[index] = 0.0;
+            open.push(Frontier {
+                estimate: 0.0,
+                cell: goal,
+            });
+        }
+        while let Some(Frontier { estimate, cell }) = open.pop() {
+            if estimate > cost[grid.index(cell).unwrap()] {
+                continue;
+            }
+            for (next, step) in grid.neighbours(cell) {
+                let next_index = grid.index(next).unwrap();
+                if estimate + step < cost[next_index] {
+                    cost[next_index] = estimate + step;
+                    open.push(Frontier {
+                        estimate: estimate + step,
+                        cell: next,
+                    });
+                }
+            }
+        }
+
+        // Every cell the goal can be reached from points at its cheapest neighbour
+        let directions = (0..grid.len())
+            .map(|index| {
+                let cell = grid.cell_at(index);
+                if !grid.is_walkable(cell) {
+                    return Vec2::ZERO;
+                }
+                grid.neighbours(cell)
+                    .map(|(next, _)| (next, cost[grid.index(next).unwrap()]))
+                    .filter(|(_, next_cost)| *next_cost < cost[index])
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map_or(Vec2::ZERO, |(next, _)| (next - cell).as_vec2().normalize())
+            })
+            .collect();
+
+        FlowField { directions }
+    }
+
+    fn direction(&self, grid: &NavGrid, position: Vec3) -> Vec2 {
+        grid.index(grid.cell_of(position.xz()))
+            .map_or(Vec2::ZERO, |index| self.directions[index])
+    }
+}
+
+/// Flow fields keyed by goal cell, shared by every unit heading for that cell.
+#[derive(Resource, Default)]
+pub struct FlowFields {
+    fields: HashMap<IVec2, FlowField>,
+}
+
+impl FlowFields {
+    pub fn get_or_build(&mut self, grid: &NavGrid, goal: IVec2) -> &FlowField {
+        self.fields
+            .entry(goal)
+            .or_insert_with(|| FlowField::build(grid, goal))
+    }
+}
+
+fn invalidate_flow_fields(
+    grid: Res<NavGrid>,
+    mut fields: ResMut<FlowFields>,
+    goals: Query<&FlowGoal>,
+) {
+    // The grid is only rebuilt when Ground entities change, so every field is stale
+    if grid.is_changed() {
+        fields.fields.clear();
+        return;
+    }
+    // Drop fields nobody is following any more
+    fields
+        .fields
+        .retain(|cell, _| goals.iter().any(|goal| goal.cell == *cell));
+}
+
+fn follow_flow_field(
+    grid: Res<NavGrid>,
+    mut fields: ResMut<FlowFields>,
+    mut units: Query<(&Transform, &FlowGoal, &mut Steering)>,
+) {
+    for (transform, goal, mut steering) in units.iter_mut() {
+        let to_goal = (goal.position - transform.translation).xz();
+
+        // Walk straight at the goal once nothing is in the way, otherwise follow the field
+        let mut direction = to_goal.normalize_or_zero();
+        if !grid.line_of_sight(transform.translation, goal.position) {
+            let field_direction = fields
+                .get_or_build(&grid, goal.cell)
+                .direction(&grid, transform.translation);
+            if field_direction != Vec2::ZERO {
+                direction = field_direction;
+            }
+        }
+
+        steering.desired = direction;
+        steering.goal = Some(goal.position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::navigation::NavSettings;
+
+    #[test]
+    fn every_reachable_cell_leads_to_the_goal() {
+        let plane = Mesh::from(Plane3d::default().mesh().size(20.0, 20.0));
+        let mut grid = NavGrid::bake(
+            [(&plane, &GlobalTransform::IDENTITY)].into_iter(),
+            &NavSettings::default(),
+        );
+        let pocket = Rect::new(6.0, 6.0, 8.0, 8.0);
+        grid.apply_footprints(
+            [
+                // A wall to go round
+                Rect::new(-1.0, -6.0, 1.0, 6.0),
+                // Walled in on every side
+                Rect::new(5.0, 5.0, 9.0, 6.0),
+                Rect::new(5.0, 8.0, 9.0, 9.0),
+                Rect::new(5.0, 5.0, 6.0, 9.0),
+                Rect::new(8.0, 5.0, 9.0, 9.0),
+            ]
+            .into_iter(),
+        );
+        let goal = grid.cell_of(Vec2::new(5.0, 0.0));
+        let field = FlowField::build(&grid, goal);
+
+        for index in 0..grid.len() {
+            let mut cell = grid.cell_at(index);
+            let direction = field.directions[index];
+            if !grid.is_walkable(cell) || pocket.contains(grid.cell_center(cell).xz()) {
+                assert_eq!(direction, Vec2::ZERO, "{cell}");
+                continue;
+            }
+            if cell == goal {
+                continue;
+            }
+            // Following the field cell by cell gets there
+            let mut steps = 0;
+            while cell != goal {
+                let direction = field.directions[grid.index(cell).unwrap()];
+                assert_ne!(direction, Vec2::ZERO, "{cell}");
+                cell += direction.round().as_ivec2();
+                assert!(grid.is_walkable(cell));
+                steps += 1;
+                assert!(steps < grid.len());
+            }
+        }
+    }
+}
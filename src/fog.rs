@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+use bevy_mod_picking::prelude::Pickable;
+
+use crate::navigation::NavGrid;
+use crate::selection::Inspected;
+use crate::sim::SimSet;
+use crate::steering::SteeringSet;
+use crate::team::{PlayerId, Team};
+
+const FOG_CELL_SIZE: f32 = 1.0;
+// Units see from this far above the ground they stand on; terrain rising higher blocks sight
+const EYE_HEIGHT: f32 = 2.0;
+// How far above the terrain the fog overlay floats
+const OVERLAY_LIFT: f32 = 0.05;
+// Fog opacity over ground that was never seen, and over ground seen before but not right now
+const UNEXPLORED_ALPHA: u8 = 230;
+const EXPLORED_ALPHA: u8 = 140;
+
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Vision>()
+            .init_resource::<FogOfWar>()
+            .configure_sets(
+                FixedUpdate,
+                FogSet.after(SteeringSet).in_set(SimSet::Update),
+            )
+            .add_systems(FixedUpdate, (resize_fog, update_fog).chain().in_set(FogSet))
+            .add_systems(
+                Update,
+                (hide_unseen_units, spawn_fog_overlay, update_fog_texture).chain(),
+            );
+    }
+}
+
+/// Works out what every team sees once units have moved. Anything that depends on what can be
+/// seen runs after it.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct FogSet;
+
+/// How far a unit can see.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Vision(pub f32);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FogState {
+    #[default]
+    Unexplored,
+    /// Seen before, but nobody on the team is looking right now.
+    Explored,
+    Visible,
+}
+
+/// What every team can see, on a coarse grid over the `NavGrid`. Updated every frame.
+#[derive(Resource, Default)]
+pub struct FogOfWar {
+    origin: Vec2,
+    width: i32,
+    depth: i32,
+    // Terrain height of each cell, for line of sight
+    heights: Vec<f32>,
+    teams: HashMap<Team, Vec<FogState>>,
+}
+
+impl FogOfWar {
+    fn from_nav_grid(grid: &NavGrid) -> Self {
+        let bounds = grid.bounds();
+        let size = (bounds.size() / FOG_CELL_SIZE).ceil().as_ivec2();
+        let mut fog = FogOfWar {
+            origin: bounds.min,
+            width: size.x,
+            depth: size.y,
+            heights: Vec::new(),
+            teams: HashMap::new(),
+        };
+        fog.heights = (0..size.x * size.y)
+            .map(|i| {
+                let center = fog.cell_center(IVec2::new(i % size.x, i / size.x));
+                grid.surface_height(center).unwrap_or(f32::NEG_INFINITY)
+            })
+            .collect();
+        fog
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        (cell.x >= 0 && cell.y >= 0 && cell.x < self.width && cell.y < self.depth)
+            .then(|| (cell.y * self.width + cell.x) as usize)
+    }
+
+    fn cell_of(&self, position: Vec2) -> IVec2 {
+        ((position - self.origin) / FOG_CELL_SIZE)
+            .floor()
+            .as_ivec2()
+    }
+
+    fn cell_center(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + 0.5) * FOG_CELL_SIZE
+    }
+
+    /// How much of `position` the team can see. Anywhere off the grid counts as visible.
+    pub fn state(&self, team: Team, position: Vec3) -> FogState {
+        let Some(index) = self.index(self.cell_of(position.xz())) else {
+            return FogState::Visible;
+        };
+        self.teams
+            .get(&team)
+            .map_or(FogState::Unexplored, |cells| cells[index])
+    }
+
+    pub fn is_visible(&self, team: Team, position: Vec3) -> bool {
+        self.state(team, position) == FogState::Visible
+    }
+
+    /// What each team has explored, as the lengths of alternating runs of unexplored and
+    /// explored cells. Cells in view count as explored.
+    pub fn explored(&self) -> Vec<(Team, Vec<u32>)> {
+        let mut explored: Vec<(Team, Vec<u32>)> = self
+            .teams
+            .iter()
+            .map(|(team, cells)| {
+                let mut runs = vec![0];
+                let mut seen = false;
+                for cell in cells {
+                    if (*cell != FogState::Unexplored) != seen {
+                        seen = !seen;
+                        runs.push(0);
+                    }
+                    *runs.last_mut().unwrap() += 1;
+                }
+                (*team, runs)
+            })
+            .collect();
+        explored.sort_by_key(|(team, _)| team.0);
+        explored
+    }
+
+    /// Put back what `explored` returned. Nothing counts as in view until units next look.
+    pub fn restore_explored(&mut self, explored: Vec<(Team, Vec<u32>)>) {
+        let len = self.heights.len();
+        self.teams.clear();
+        for (team, runs) in explored {
+            let mut cells = Vec::with_capacity(len);
+            for (i, run) in runs.into_iter().enumerate() {
+                let state = match i % 2 {
+                    0 => FogState::Unexplored,
+                    _ => FogState::Explored,
+                };
+                cells.extend(std::iter::repeat_n(state, run as usize));
+            }
+            cells.resize(len, FogState::Unexplored);
+            self.teams.insert(team, cells);
+        }
+    }
+
+    // Terrain between the two cells, not counting either end, never rises above `eye`
+    fn line_of_sight(&self, from: IVec2, to: IVec2, eye: f32) -> bool {
+        let start = self.cell_center(from);
+        let end = self.cell_center(to);
+        let steps = (start.distance(end) / (FOG_CELL_SIZE * 0.5)).ceil() as i32;
+        (1..steps).all(|step| {
+            let cell = self.cell_of(start.lerp(end, step as f32 / steps as f32));
+            cell == from || cell == to || self.index(cell).is_none_or(|i| self.heights[i] <= eye)
+        })
+    }
+}
+
+// Marks the mesh draped over the terrain that darkens what the player can't see
+#[derive(Component)]
+struct FogOverlay {
+    image: Handle<Image>,
+}
+
+fn resize_fog(grid: Res<NavGrid>, mut fog: ResMut<FogOfWar>) {
+    if !grid.is_changed() {
+        return;
+    }
+    let mut resized = FogOfWar::from_nav_grid(&grid);
+    // Terrain edits that leave the map the same size keep what everyone has explored
+    if (resized.origin, resized.width, resized.depth) == (fog.origin, fog.width, fog.depth) {
+        resized.teams = std::mem::take(&mut fog.teams);
+    }
+    *fog = resized;
+}
+
+fn update_fog(mut fog: ResMut<FogOfWar>, viewers: Query<(&Transform, &Team, &Vision)>) {
+    let fog = &mut *fog;
+    if fog.heights.is_empty() {
+        return;
+    }
+    for cells in fog.teams.values_mut() {
+        for cell in cells.iter_mut().filter(|c| **c == FogState::Visible) {
+            *cell = FogState::Explored;
+        }
+    }
+
+    // Units in the same cell with the same vision see the same thing, only look once
+    let mut looked = HashSet::new();
+    for (transform, team, vision) in viewers.iter() {
+        let center = fog.cell_of(transform.translation.xz());
+        let Some(own) = fog.index(center) else {
+            continue;
+        };
+        if !looked.insert((*team, center, vision.0.to_bits())) {
+            continue;
+        }
+        let eye = fog.heights[own].max(0.0) + EYE_HEIGHT;
+
+        let len = fog.heights.len();
+        let mut cells = std::mem::take(fog.teams.entry(*team).or_default());
+        cells.resize(len, FogState::Unexplored);
+        let reach = (vision.0 / FOG_CELL_SIZE).ceil() as i32;
+        let in_view: Vec<(IVec2, usize)> = (-reach..=reach)
+            .flat_map(|z| (-reach..=reach).map(move |x| center + IVec2::new(x, z)))
+            .filter(|cell| fog.cell_center(*cell).distance(fog.cell_center(center)) <= vision.0)
+            .filter_map(|cell| Some((cell, fog.index(cell)?)))
+            .collect();
+        // Out in the open there's nothing to trace
+        let open = in_view.iter().all(|(_, index)| fog.heights[*index] <= eye);
+        for (cell, index) in in_view {
+            if cells[index] != FogState::Visible && (open || fog.line_of_sight(center, cell, eye)) {
+                cells[index] = FogState::Visible;
+            }
+        }
+        fog.teams.insert(*team, cells);
+    }
+}
+
+// Enemy units the player can't see aren't drawn and can't be clicked
+fn hide_unseen_units(
+    fog: Res<FogOfWar>,
+    player: Res<PlayerId>,
+    mut inspected: ResMut<Inspected>,
+    mut units: Query<(
+        Entity,
+        &Transform,
+        &Team,
+        &mut Visibility,
+        Option<&mut Pickable>,
+    )>,
+) {
+    for (entity, transform, team, mut visibility, pickable) in units.iter_mut() {
+        let seen = *team == player.0 || fog.is_visible(player.0, transform.translation);
+        let wanted = if seen {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility == wanted {
+            continue;
+        }
+        *visibility = wanted;
+        if let Some(mut pickable) = pickable {
+            *pickable = if seen {
+                Pickable::default()
+            } else {
+                Pickable::IGNORE
+            };
+        }
+        if !seen && inspected.0 == Some(entity) {
+            inspected.0 = None;
+        }
+    }
+}
+
+// One quad per fog cell at the height of the terrain under it, rebuilt whenever the terrain is
+fn spawn_fog_overlay(
+    grid: Res<NavGrid>,
+    fog: Res<FogOfWar>,
+    overlays: Query<Entity, With<FogOverlay>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    if !grid.is_changed() || fog.width == 0 {
+        return;
+    }
+    for entity in overlays.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    let texel = Vec2::new(1.0 / fog.width as f32, 1.0 / fog.depth as f32);
+    for (index, height) in fog.heights.iter().enumerate() {
+        if !height.is_finite() {
+            continue;
+        }
+        let cell = IVec2::new(index as i32 % fog.width, index as i32 / fog.width);
+        let min = fog.origin + cell.as_vec2() * FOG_CELL_SIZE;
+        let first = positions.len() as u32;
+        for corner in [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y] {
+            let xz = min + corner * FOG_CELL_SIZE;
+            positions.push([xz.x, height + OVERLAY_LIFT, xz.y]);
+            let uv = (cell.as_vec2() + corner) * texel;
+            uvs.push([uv.x, uv.y]);
+        }
+        indices.extend([first, first + 2, first + 1, first, first + 3, first + 2]);
+    }
+    let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+    let mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices));
+
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: fog.width as u32,
+            height: fog.depth as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, UNEXPLORED_ALPHA],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    // Blend between cells so the edge of vision is soft
+    image.sampler = ImageSampler::linear();
+    let image = images.add(image);
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials.add(StandardMaterial {
+                base_color_texture: Some(image.clone()),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            ..default()
+        },
+        FogOverlay { image },
+        NotShadowCaster,
+        Pickable::IGNORE,
+    ));
+}
+
+fn update_fog_texture(
+    fog: Res<FogOfWar>,
+    player: Res<PlayerId>,
+    overlays: Query<Ref<FogOverlay>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // The fog only moves on ticks, not every frame
+    let overlay_added = overlays.iter().any(|overlay| overlay.is_added());
+    if !fog.is_changed() && !player.is_changed() && !overlay_added {
+        return;
+    }
+    let Some(cells) = fog.teams.get(&player.0) else {
+        return;
+    };
+    for overlay in overlays.iter() {
+        let Some(image) = images.get_mut(&overlay.image) else {
+            continue;
+        };
+        for (pixel, state) in image.data.chunks_exact_mut(4).zip(cells) {
+            pixel[3] = match state {
+                FogState::Unexplored => UNEXPLORED_ALPHA,
+                FogState::Explored => EXPLORED_ALPHA,
+                FogState::Visible => 0,
+            };
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Distance between neighbouring slots, a little over a unit's diameter
+const SLOT_SPACING: f32 = 0.8;
+
+pub struct FormationPlugin;
+
+impl Plugin for FormationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FormationKind>()
+            .add_systems(Update, cycle_formation);
+    }
+}
+
+/// Shape used to lay out a group move order. The current choice is stored as a resource.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FormationKind {
+    #[default]
+    Box,
+    Line,
+    Wedge,
+    Column,
+}
+
+impl FormationKind {
+    fn next(self) -> Self {
+        match self {
+            FormationKind::Box => FormationKind::Line,
+            FormationKind::Line => FormationKind::Wedge,
+            FormationKind::Wedge => FormationKind::Column,
+            FormationKind::Column => FormationKind::Box,
+        }
+    }
+
+    // Offsets in formation space: x to the right, y towards the back
+    fn offsets(self, count: usize) -> Vec<Vec2> {
+        let grid = |width: usize| -> Vec<Vec2> {
+            (0..count)
+                .map(|i| {
+                    let row = i / width;
+                    // The last row may be partially filled, so centre it on its own
+                    let row_len = width.min(count - row * width);
+                    let column = i % width;
+                    Vec2::new(column as f32 - (row_len - 1) as f32 / 2.0, row as f32)
+                })
+                .collect()
+        };
+
+        match self {
+            FormationKind::Box => grid((count as f32).sqrt().ceil() as usize),
+            FormationKind::Line => grid(count.max(1)),
+            FormationKind::Column => grid(2),
+            FormationKind::Wedge => {
+                // Row n holds n + 1 units, spreading out behind a single point man
+                let mut offsets = Vec::with_capacity(count);
+                let mut row = 0;
+                while offsets.len() < count {
+                    let row_len = (row + 1).min(count - offsets.len());
+                    for column in 0..row_len {
+                        offsets.push(Vec2::new(column as f32 - row as f32 / 2.0, row as f32));
+                    }
+                    row += 1;
+                }
+                offsets
+            }
+        }
+    }
+}
+
+/// World positions of `count` slots of the formation, centred on `center` and facing along
+/// `facing` (an XZ direction).
+pub fn formation_slots(kind: FormationKind, count: usize, center: Vec3, facing: Vec2) -> Vec<Vec3> {
+    let offsets = kind.offsets(count);
+    let centroid = offsets.iter().sum::<Vec2>() / count.max(1) as f32;
+    let forward = facing.try_normalize().unwrap_or(Vec2::Y);
+    let right = Vec2::new(-forward.y, forward.x);
+
+    offsets
+        .iter()
+        .map(|offset| {
+            let offset = (*offset - centroid) * SLOT_SPACING;
+            let world = right * offset.x - forward * offset.y;
+            center + Vec3::new(world.x, 0.0, world.y)
+        })
+        .collect()
+}
+
+/// Pair every unit with a slot so that the total walking distance is as small as possible.
+/// Returns the slot index for each unit.
+pub fn assign_slots(units: &[Vec3], slots: &[Vec3]) -> Vec<usize> {
+    let n = units.len();
+    debug_assert_eq!(n, slots.len());
+    let cost = |unit: usize, slot: usize| units[unit].xz().distance(slots[slot].xz());
+
+    // The Hungarian algorithm, with 1-based arrays and a dummy column 0 as in the classic
+    // formulation
+    let mut u = vec![0.0f32; n + 1];
+    let mut v = vec![0.0f32; n + 1];
+    let mut slot_owner = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for unit in 1..=n {
+        slot_owner[0] = unit;
+        let mut slot = 0;
+        let mut min_to = vec![f32::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[slot] = true;
+            let owner = slot_owner[slot];
+            let mut delta = f32::INFINITY;
+            let mut next = 0;
+            for candidate in 1..=n {
+                if used[candidate] {
+                    continue;
+                }
+                let reduced = cost(owner - 1, candidate - 1) - u[owner] - v[candidate];
+                if reduced < min_to[candidate] {
+                    min_to[candidate] = reduced;
+                    way[candidate] = slot;
+                }
+                if min_to[candidate] < delta {
+                    delta = min_to[candidate];
+                    next = candidate;
+                }
+            }
+            for candidate in 0..=n {
+                if used[candidate] {
+                    u[slot_owner[candidate]] += delta;
+                    v[candidate] -= delta;
+                } else {
+                    min_to[candidate] -= delta;
+                }
+            }
+            slot = next;
+            if slot_owner[slot] == 0 {
+                break;
+            }
+        }
+        // Walk the augmenting path back, handing each slot to its new owner
+        while slot != 0 {
+            let previous = way[slot];
+            slot_owner[slot] = slot_owner[previous];
+            slot = previous;
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for slot in 1..=n {
+        if slot_owner[slot] != 0 {
+            assignment[slot_owner[slot] - 1] = slot - 1;
+        }
+    }
+    assignment
+}
+
+fn cycle_formation(key_input: Res<ButtonInput<KeyCode>>, mut formation: ResMut<FormationKind>) {
+    if key_input.just_pressed(KeyCode::KeyF) {
+        *formation = formation.next();
+        info!("Formation: {:?}", *formation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    const KINDS: [FormationKind; 4] = [
+        FormationKind::Box,
+        FormationKind::Line,
+        FormationKind::Wedge,
+        FormationKind::Column,
+    ];
+
+    fn total_cost(units: &[Vec3], slots: &[Vec3], assignment: &[usize]) -> f32 {
+        units
+            .iter()
+            .zip(assignment)
+            .map(|(unit, slot)| unit.xz().distance(slots[*slot].xz()))
+            .sum()
+    }
+
+    // Every ordering of 0..n
+    fn permutations(n: usize) -> Vec<Vec<usize>> {
+        if n == 0 {
+            return vec![Vec::new()];
+        }
+        permutations(n - 1)
+            .into_iter()
+            .flat_map(|shorter| {
+                (0..n).map(move |at| {
+                    let mut longer = shorter.clone();
+                    longer.insert(at, n - 1);
+                    longer
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn slots_are_assigned_at_the_least_total_cost() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut point = || Vec3::new(rng.gen_range(-10.0..10.0), 0.0, rng.gen_range(-10.0..10.0));
+        for n in 1..=6 {
+            for _ in 0..20 {
+                let units: Vec<_> = (0..n).map(|_| point()).collect();
+                let slots: Vec<_> = (0..n).map(|_| point()).collect();
+                let assignment = assign_slots(&units, &slots);
+
+                let mut taken = assignment.clone();
+                taken.sort();
+                assert_eq!(taken, (0..n).collect::<Vec<_>>());
+                let best = permutations(n)
+                    .iter()
+                    .map(|permutation| total_cost(&units, &slots, permutation))
+                    .fold(f32::INFINITY, f32::min);
+                assert!(total_cost(&units, &slots, &assignment) <= best + 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn every_shape_has_a_slot_for_each_unit_spaced_apart() {
+        let center = Vec3::new(3.0, 1.0, -2.0);
+        for kind in KINDS {
+            for count in 1..=20 {
+                let slots = formation_slots(kind, count, center, Vec2::new(1.0, 1.0));
+                assert_eq!(slots.len(), count, "{kind:?}");
+                let middle = slots.iter().sum::<Vec3>() / count as f32;
+                assert!(middle.distance(center) < 1e-3, "{kind:?} {count}");
+                for (i, a) in slots.iter().enumerate() {
+                    for b in &slots[i + 1..] {
+                        assert!(a.distance(*b) >= SLOT_SPACING - 1e-3, "{kind:?} {count}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shapes_are_laid_out_across_or_along_the_facing() {
+        let forward = Vec2::new(0.0, 1.0);
+        let along = |slots: &[Vec3]| -> Vec<f32> { slots.iter().map(|slot| slot.z).collect() };
+        let across = |slots: &[Vec3]| -> Vec<f32> { slots.iter().map(|slot| slot.x).collect() };
+        let distinct = |mut values: Vec<f32>| {
+            values.sort_by(f32::total_cmp);
+            values.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+            values.len()
+        };
+
+        let line = formation_slots(FormationKind::Line, 7, Vec3::ZERO, forward);
+        assert_eq!(distinct(along(&line)), 1);
+        assert_eq!(distinct(across(&line)), 7);
+
+        let column = formation_slots(FormationKind::Column, 7, Vec3::ZERO, forward);
+        assert_eq!(distinct(across(&column)), 3);
+        assert_eq!(distinct(along(&column)), 4);
+
+        let square = formation_slots(FormationKind::Box, 9, Vec3::ZERO, forward);
+        assert_eq!(distinct(across(&square)), 3);
+        assert_eq!(distinct(along(&square)), 3);
+
+        // One unit out in front, then rows of two, three and four
+        let wedge = formation_slots(FormationKind::Wedge, 10, Vec3::ZERO, forward);
+        let front = along(&wedge).into_iter().fold(f32::MIN, f32::max);
+        let rows: Vec<usize> = (0..4)
+            .map(|row| {
+                let z = front - row as f32 * SLOT_SPACING;
+                wedge
+                    .iter()
+                    .filter(|slot| (slot.z - z).abs() < 1e-3)
+                    .count()
+            })
+            .collect();
+        assert_eq!(rows, [1, 2, 3, 4]);
+    }
+}
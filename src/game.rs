@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use bevy_rts_camera::{RtsCamera, RtsCameraControls, RtsCameraSystemSet};
+
+use crate::ai::SkirmishAiPlugin;
+use crate::building::BuildingPlugin;
+use crate::combat::CombatPlugin;
+use crate::command::CommandPlugin;
+use crate::defs::DefsPlugin;
+use crate::economy::EconomyPlugin;
+use crate::fog::FogPlugin;
+use crate::lockstep::{Lockstep, LockstepPlugin};
+use crate::minimap::MinimapPlugin;
+use crate::movement::{Move, UnitMovementPlugin, UNIT_GROUND_OFFSET};
+use crate::orders::OrdersPlugin;
+use crate::production::ProductionPlugin;
+use crate::replay::{ReplayError, ReplayPlugin};
+use crate::save::SavePlugin;
+use crate::scenario::ScenarioPlugin;
+use crate::script::ScriptPlugin;
+use crate::selection::SelectionPlugin;
+use crate::sim::{assign_sim_id, Interpolated, SimPlugin};
+use crate::stats::StatsPlugin;
+use crate::team::TeamPlugin;
+use crate::tree::TreeGenPlugin;
+use crate::unit::UnitPlugin;
+
+/// The whole game: every gameplay plugin, and the camera, light and help text it's played with.
+/// Add it after `DefaultPlugins`, `RtsCameraPlugin` and `DefaultPickingPlugins`, or after a
+/// `HeadlessPlugin` to run without a window.
+#[derive(Clone)]
+pub struct RtsGamePlugin {
+    pub sim: SimPlugin,
+    pub scenario: ScenarioPlugin,
+    pub replay: ReplayPlugin,
+    pub lockstep: LockstepPlugin,
+    pub script: ScriptPlugin,
+    pub ai: SkirmishAiPlugin,
+}
+
+impl RtsGamePlugin {
+    /// The game as set up on the command line. Fails if it names a replay that can't be read.
+    pub fn from_args() -> Result<Self, ReplayError> {
+        let replay = ReplayPlugin::from_args()?;
+        Ok(Self {
+            sim: replay.sim_plugin(),
+            scenario: replay.scenario_plugin(),
+            replay,
+            lockstep: LockstepPlugin::from_args(),
+            script: ScriptPlugin::from_args(),
+            ai: SkirmishAiPlugin::from_args(),
+        })
+    }
+}
+
+impl Plugin for RtsGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(self.sim.clone())
+            .add_plugins(CommandPlugin)
+            .add_plugins(TeamPlugin)
+            .add_plugins(DefsPlugin)
+            .add_plugins(UnitPlugin)
+            .add_plugins(SelectionPlugin)
+            .add_plugins(UnitMovementPlugin)
+            .add_plugins(OrdersPlugin)
+            .add_plugins(CombatPlugin)
+            .add_plugins(FogPlugin)
+            .add_plugins(MinimapPlugin)
+            .add_plugins(TreeGenPlugin)
+            .add_plugins(EconomyPlugin)
+            .add_plugins(BuildingPlugin)
+            .add_plugins(ProductionPlugin)
+            .add_plugins(self.scenario.clone())
+            .add_plugins(SavePlugin)
+            .add_plugins(self.replay.clone())
+            .add_plugins(self.lockstep.clone())
+            .add_plugins(StatsPlugin);
+
+        // Only this player's own commands are passed on to the others, so scripts and AIs
+        // would be playing by themselves
+        let lockstep = app.world().contains_resource::<Lockstep>();
+        if lockstep && (!self.script.teams.is_empty() || !self.ai.players.is_empty()) {
+            error!("Scripts and AIs can't play in a lockstep game, their teams are left idle");
+            app.add_plugins(ScriptPlugin { teams: Vec::new() })
+                .add_plugins(SkirmishAiPlugin::default());
+        } else {
+            app.add_plugins(self.script.clone())
+                .add_plugins(self.ai.clone());
+        }
+
+        app.add_systems(Startup, setup).add_systems(
+            Update,
+            (lock_or_jump, toggle_controls)
+                .chain()
+                .before(RtsCameraSystemSet),
+        );
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // The moving unit, circling the middle of the map. Units can follow it, so recorded and
+    // sent orders have to be able to name it
+    commands
+        .spawn((
+            PbrBundle {
+                mesh: meshes.add(Cuboid::new(0.8, 0.8, 0.8)),
+                material: materials.add(Color::srgb(0.2, 0.3, 0.9)),
+                transform: Transform::from_xyz(0.0, UNIT_GROUND_OFFSET, 7.5),
+                ..default()
+            },
+            Interpolated::new(Vec3::new(0.0, UNIT_GROUND_OFFSET, 7.5)),
+            Move::default(),
+        ))
+        .add(assign_sim_id);
+
+    // Light
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance: 1000.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_rotation(Quat::from_euler(
+            EulerRot::YXZ,
+            150.0f32.to_radians(),
+            -40.0f32.to_radians(),
+            0.0,
+        )),
+        ..default()
+    });
+
+    // Help text
+    commands.spawn(TextBundle {
+        text: Text {
+            sections: vec![TextSection {
+                value: "\
+Press K to jump to the moving unit
+Hold L to lock onto the moving unit
+Press T to toggle controls (K and L will still work)
+Left-click to select a unit, or drag to box select
+Hold Shift to add to the selection, Ctrl to remove from it
+Ctrl+1..9 to assign a control group, 1..9 to recall it (twice to center)
+Right-click to move selected units, drag to face the formation
+Right-click a unit to follow it, or an enemy to attack it
+Left-click an enemy to see its health
+Right-click a tree or rock with workers selected to gather from it
+With workers selected, press B to place a building (again to switch, Shift+click for more)
+Right-click a construction site with workers selected to help build it
+Click a finished building, then Q/W/E to train units (Backspace cancels), right-click to rally
+Shift+right-click to queue waypoints
+Press F to cycle formations (box, line, wedge, column)
+Press S to stop, H to hold position
+Press P or A then click to patrol or attack-move there (Esc to cancel)
+Idle, holding, patrolling and attack-moving units fire at enemies in range
+Arrow keys or the screen edges pan the camera
+Click or drag on the minimap to look there, right-click it to move there
+F5 to quicksave, F9 to quickload"
+                    .to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        ..default()
+    });
+
+    // Camera
+    commands.spawn((
+        Camera3dBundle::default(),
+        RtsCamera {
+            height_max: 50.0,
+            min_angle: 35.0f32.to_radians(),
+            smoothness: 0.1,
+            ..default()
+        },
+        RtsCameraControls {
+            // Arrow keys, WASD is taken by unit orders
+            key_up: KeyCode::ArrowUp,
+            key_down: KeyCode::ArrowDown,
+            key_left: KeyCode::ArrowLeft,
+            key_right: KeyCode::ArrowRight,
+            button_rotate: MouseButton::Middle, // Changed from Right to Middle
+            lock_on_rotate: true,
+            button_drag: Some(MouseButton::Middle),
+            lock_on_drag: true,
+            edge_pan_width: 0.1,
+            pan_speed: 25.0,
+            ..default()
+        },
+    ));
+}
+
+// Either jump to the moving unit (press K) or lock onto it (hold L)
+fn lock_or_jump(
+    key_input: Res<ButtonInput<KeyCode>>,
+    cube_q: Query<&Transform, With<Move>>,
+    mut cam_q: Query<&mut RtsCamera>,
+) {
+    for cube in cube_q.iter() {
+        for mut cam in cam_q.iter_mut() {
+            if key_input.pressed(KeyCode::KeyL) {
+                cam.target_focus.translation = cube.translation;
+                cam.snap = true;
+            }
+            if key_input.just_pressed(KeyCode::KeyK) {
+                cam.target_focus.translation = cube.translation;
+                cam.target_zoom = 0.4;
+            }
+        }
+    }
+}
+
+fn toggle_controls(
+    mut controls_q: Query<&mut RtsCameraControls>,
+    key_input: Res<ButtonInput<KeyCode>>,
+) {
+    for mut controls in controls_q.iter_mut() {
+        if key_input.just_pressed(KeyCode::KeyT) {
+            controls.enabled = !controls.enabled;
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use bevy::app::{AppExit, ScheduleRunnerPlugin};
+use bevy::gizmos::GizmoPlugin;
+use bevy::hierarchy::HierarchyPlugin;
+use bevy::input::InputPlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::render::render_resource::Shader;
+use bevy::time::TimeUpdateStrategy;
+use bevy::transform::TransformPlugin;
+use serde::{Deserialize, Serialize};
+
+use crate::building::{Building, ConstructionSite};
+use crate::cli;
+use crate::economy::{Stockpile, Stockpiles};
+use crate::replay::{HashedState, Playback};
+use crate::scenario::{CurrentScenario, Outcome, Scenario};
+use crate::sim::{SimClock, SimSettings};
+use crate::stats::{MatchStats, TeamStats};
+use crate::team::Team;
+use crate::unit::Unit;
+
+/// Ticks a headless run stops after when none is given with `--ticks`: five minutes of play
+/// at the default tick rate.
+pub const DEFAULT_TICKS: u64 = 9000;
+
+/// Runs the game with no window, one tick per update as fast as it will go, until the match is
+/// decided or `ticks` have run, then prints a `Report`. Add it in place of `DefaultPlugins`.
+pub struct HeadlessPlugin {
+    /// Most ticks to run.
+    pub ticks: u64,
+    /// Where to write the report as well as printing it.
+    pub report: Option<PathBuf>,
+}
+
+impl HeadlessPlugin {
+    /// Settings from `--ticks` and `--report <path>` on the command line, or the defaults.
+    pub fn from_args() -> Self {
+        Self {
+            ticks: cli::parsed_arg("ticks").unwrap_or(DEFAULT_TICKS),
+            report: cli::arg("report").map(PathBuf::from),
+        }
+    }
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)))
+            .add_plugins((
+                LogPlugin::default(),
+                TransformPlugin,
+                HierarchyPlugin,
+                InputPlugin,
+                AssetPlugin::default(),
+            ))
+            // Gameplay plugins make meshes, materials and gizmos for what they spawn, which
+            // nothing draws here
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_asset::<Image>()
+            .init_asset::<Shader>()
+            .add_plugins(GizmoPlugin)
+            .insert_resource(BatchRun {
+                ticks: self.ticks,
+                report: self.report.clone(),
+                started: Instant::now(),
+            })
+            .add_systems(Startup, one_tick_per_update)
+            // Sees the state the last tick left, before it's blended for drawing
+            .add_systems(Update, finish_run);
+    }
+}
+
+/// How a headless run went, printed when it ends.
+#[derive(Serialize, Deserialize)]
+pub struct Report {
+    pub scenario: String,
+    pub seed: u64,
+    pub ticks: u64,
+    /// How the match went for the player's team.
+    pub outcome: Outcome,
+    /// The first tick a replay being played back went differently, if it did.
+    pub desynced_at: Option<u64>,
+    /// `HashedState` at the end, to tell runs apart by.
+    pub hash: u64,
+    pub teams: Vec<TeamReport>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct TeamReport {
+    pub team: u8,
+    pub stockpile: Stockpile,
+    /// Units standing at the end, by definition.
+    pub units: BTreeMap<String, u32>,
+    /// Finished buildings standing at the end, by definition.
+    pub buildings: BTreeMap<String, u32>,
+    pub stats: TeamStats,
+}
+
+#[derive(Resource)]
+struct BatchRun {
+    ticks: u64,
+    report: Option<PathBuf>,
+    started: Instant,
+}
+
+// Every update advances time by exactly a tick, however long it took
+fn one_tick_per_update(fixed: Res<Time<Fixed>>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    *strategy = TimeUpdateStrategy::ManualDuration(fixed.timestep());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn finish_run(
+    run: Res<BatchRun>,
+    clock: Res<SimClock>,
+    current: Res<CurrentScenario>,
+    scenarios: Res<Assets<Scenario>>,
+    outcome: Res<Outcome>,
+    settings: Res<SimSettings>,
+    playback: Option<Res<Playback>>,
+    state: HashedState,
+    stockpiles: Res<Stockpiles>,
+    stats: Res<MatchStats>,
+    units: Query<(&Unit, &Team)>,
+    buildings: Query<(&Building, &Team), Without<ConstructionSite>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !current.spawned() {
+        return;
+    }
+    // A scenario that never started failed to load, and a replay stops the clock at its end
+    if !clock.running && clock.tick == 0 {
+        error!("The scenario didn't start");
+        exit.send(AppExit::error());
+        return;
+    }
+    let decided = *outcome != Outcome::Playing;
+    if clock.running && clock.tick < run.ticks && !decided {
+        return;
+    }
+
+    let mut teams: BTreeMap<u8, TeamReport> = stockpiles
+        .0
+        .keys()
+        .chain(stats.0.keys())
+        .chain(units.iter().map(|(_, team)| team))
+        .chain(buildings.iter().map(|(_, team)| team))
+        .map(|team| {
+            let report = TeamReport {
+                team: team.0,
+                stockpile: stockpiles.get(*team),
+                stats: stats.get(*team),
+                ..default()
+            };
+            (team.0, report)
+        })
+        .collect();
+    for (unit, team) in units.iter() {
+        *teams
+            .get_mut(&team.0)
+            .unwrap()
+            .units
+            .entry(unit.0.clone())
+            .or_default() += 1;
+    }
+    for (building, team) in buildings.iter() {
+        *teams
+            .get_mut(&team.0)
+            .unwrap()
+            .buildings
+            .entry(building.0.clone())
+            .or_default() += 1;
+    }
+
+    let desynced_at = playback.and_then(|playback| playback.desynced_at());
+    let report = Report {
+        scenario: scenarios
+            .get(&current.handle)
+            .map_or_else(|| current.path.clone(), |scenario| scenario.name.clone()),
+        seed: settings.seed,
+        ticks: clock.tick,
+        outcome: *outcome,
+        desynced_at,
+        hash: state.hash(),
+        teams: teams.into_values().collect(),
+    };
+    let seconds = run.started.elapsed().as_secs_f64();
+    info!(
+        "Ran {} ticks in {seconds:.1}s, {:.0} a second",
+        clock.tick,
+        clock.tick as f64 / seconds
+    );
+
+    let mut failed = desynced_at.is_some();
+    match ron::ser::to_string_pretty(&report, ron::ser::PrettyConfig::default()) {
+        Ok(text) => {
+            println!("{text}");
+            if let Some(path) = &run.report {
+                if let Err(error) = std::fs::write(path, text) {
+                    error!("Couldn't write the report to {}: {error}", path.display());
+                    failed = true;
+                }
+            }
+        }
+        Err(error) => {
+            error!("Couldn't write the report: {error}");
+            failed = true;
+        }
+    }
+    exit.send(match failed {
+        true => AppExit::error(),
+        false => AppExit::Success,
+    });
+}
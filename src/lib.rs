@@ -0,0 +1,41 @@
+//! A real-time strategy game on Bevy, as plugins. `RtsGamePlugin` is the whole game; the others
+//! can be used on their own.
+
+pub mod ai;
+pub mod building;
+pub mod cli;
+pub mod combat;
+pub mod command;
+pub mod defs;
+pub mod economy;
+pub mod flow_field;
+pub mod fog;
+pub mod formation;
+pub mod game;
+pub mod headless;
+pub mod lockstep;
+pub mod minimap;
+pub mod movement;
+pub mod navigation;
+pub mod orders;
+pub mod particles;
+pub mod production;
+pub mod replay;
+pub mod save;
+pub mod scenario;
+pub mod script;
+pub mod selection;
+pub mod sim;
+pub mod stats;
+pub mod steering;
+pub mod team;
+pub mod throw;
+pub mod tree;
+pub mod unit;
+
+pub use game::RtsGamePlugin;
+pub use movement::UnitMovementPlugin;
+pub use particles::ParticlePlugin;
+pub use selection::SelectionPlugin;
+pub use throw::PhysicsInteractionPlugin;
+pub use tree::TreeGenPlugin;
@@ -0,0 +1,840 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::Pickable;
+use serde::{Deserialize, Serialize};
+
+use crate::cli;
+use crate::command::{CommandSet, EntitySwap, PlayerCommand, TickCommands};
+use crate::replay::{HashedState, StateHash, HASH_INTERVAL};
+use crate::sim::{SimClock, SimId, SimSet};
+use crate::team::{PlayerId, Team};
+
+/// Ticks between a command being given and carried out, when none is given with
+/// `--input-delay`.
+pub const DEFAULT_INPUT_DELAY: u64 = 4;
+// Largest packet UDP can carry
+const MAX_PACKET: usize = 65_507;
+// State hashes are kept for this many intervals, to check late ones against
+const CHECKSUMS_KEPT: u64 = 8;
+// Players not heard from for this long have left the game
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Most ticks of commands kept for other players to catch up on. Anyone further behind has left
+const MAX_UNACKNOWLEDGED: u64 = 300;
+// Fewest and most players a game can have
+const MIN_PLAYERS: usize = 2;
+const MAX_PLAYERS: usize = 4;
+
+/// Plays a match with other players in lockstep, exchanging only commands and comparing state
+/// hashes to catch anyone going out of sync. Does nothing without a `UdpSession`.
+#[derive(Clone, Default)]
+pub struct LockstepPlugin {
+    pub session: Option<UdpSession>,
+}
+
+/// How to reach the other players over UDP.
+#[derive(Clone, Debug)]
+pub struct UdpSession {
+    /// This player's index, which is also their team. Everyone's has to be different.
+    pub player: u8,
+    pub bind: SocketAddr,
+    /// Every other player.
+    pub peers: Vec<SocketAddr>,
+    pub input_delay: u64,
+}
+
+impl UdpSession {
+    /// How many players there are, if that's a number a game can have and `player` is one of
+    /// them.
+    pub fn players(&self) -> Option<u8> {
+        let players = self.peers.len() + 1;
+        let valid =
+            (MIN_PLAYERS..=MAX_PLAYERS).contains(&players) && usize::from(self.player) < players;
+        valid.then_some(players as u8)
+    }
+}
+
+impl LockstepPlugin {
+    /// A game over UDP if `--player <index> --bind <address> --peers <address>,...` are on the
+    /// command line, optionally with `--input-delay <ticks>`, or a game alone otherwise.
+    pub fn from_args() -> Self {
+        let Some(peers) = cli::arg("peers") else {
+            return Self::default();
+        };
+        let peers: Result<Vec<SocketAddr>, _> = peers.split(',').map(str::parse).collect();
+        let (Ok(peers), Some(player), Some(bind)) =
+            (peers, cli::parsed_arg("player"), cli::parsed_arg("bind"))
+        else {
+            eprintln!("Playing alone, --peers needs valid --player and --bind to go with it");
+            return Self::default();
+        };
+        let session = UdpSession {
+            player,
+            bind,
+            peers,
+            input_delay: cli::parsed_arg("input-delay").unwrap_or(DEFAULT_INPUT_DELAY),
+        };
+        if session.players().is_none() {
+            eprintln!(
+                "Playing alone, a game has {MIN_PLAYERS} to {MAX_PLAYERS} players and --player has \
+                 to be below the number of them"
+            );
+            return Self::default();
+        }
+        Self {
+            session: Some(session),
+        }
+    }
+}
+
+impl Plugin for LockstepPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(session) = &self.session {
+            match session.players() {
+                None => error!(
+                    "Playing alone, player {} can't play with {} others",
+                    session.player,
+                    session.peers.len()
+                ),
+                Some(players) => match UdpTransport::bind(session.bind, session.peers.clone()) {
+                    Ok(transport) => {
+                        app.insert_resource(PlayerId(Team(session.player)))
+                            .insert_resource(Lockstep::new(
+                                session.player,
+                                players,
+                                session.input_delay,
+                                transport,
+                            ));
+                    }
+                    Err(error) => error!("Playing alone, couldn't open {}: {error}", session.bind),
+                },
+            }
+        }
+
+        app.add_systems(
+            Startup,
+            spawn_lockstep_text.run_if(resource_exists::<Lockstep>),
+        )
+        .add_systems(
+            FixedPreUpdate,
+            exchange_commands.run_if(resource_exists::<Lockstep>),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                lockstep_commands.in_set(CommandSet::Collect),
+                exchange_checksums.in_set(SimSet::Last),
+            )
+                .run_if(resource_exists::<Lockstep>),
+        )
+        .add_systems(
+            Update,
+            update_lockstep_text.run_if(resource_exists::<Lockstep>),
+        );
+    }
+}
+
+/// What players send each other every tick.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Packet {
+    pub player: u8,
+    /// The first tick the sender is missing someone's commands for. Everything before it
+    /// needn't be sent to them again.
+    pub waiting_on: u64,
+    /// The tick the first of `commands` is for.
+    pub first_tick: u64,
+    /// The sender's commands for consecutive ticks. Entities in them stand for the `SimId` of
+    /// the same index.
+    pub commands: Vec<Vec<PlayerCommand>>,
+    /// The sender's latest state hash.
+    pub checksum: Option<StateHash>,
+}
+
+/// Carries packets between players. Packets may go missing, arrive twice or out of order;
+/// anything lost is sent again with the next packet. Packets too big to send may lose commands
+/// off the end, which are sent again once the ones before them have arrived.
+pub trait Transport: Send + Sync + 'static {
+    /// Send to every other player.
+    fn broadcast(&mut self, packet: &Packet);
+    /// The next packet to have arrived, if any.
+    fn receive(&mut self) -> Option<Packet>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peers: Vec<SocketAddr>,
+    buffer: Vec<u8>,
+}
+
+impl UdpTransport {
+    pub fn bind(address: SocketAddr, peers: Vec<SocketAddr>) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            peers,
+            buffer: vec![0; MAX_PACKET],
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn broadcast(&mut self, packet: &Packet) {
+        // Send as many of the oldest ticks as fit
+        let mut packet = Cow::Borrowed(packet);
+        let text = loop {
+            let text = match ron::to_string(&*packet) {
+                Ok(text) => text,
+                Err(error) => {
+                    error!("Couldn't write a packet: {error}");
+                    return;
+                }
+            };
+            if text.len() <= MAX_PACKET || packet.commands.len() <= 1 {
+                break text;
+            }
+            let half = packet.commands.len() / 2;
+            packet.to_mut().commands.truncate(half);
+        };
+        if text.len() > MAX_PACKET {
+            error!(
+                "Couldn't send the commands for tick {}, there are too many",
+                packet.first_tick
+            );
+            return;
+        }
+        for peer in &self.peers {
+            if let Err(error) = self.socket.send_to(text.as_bytes(), peer) {
+                warn!("Couldn't send to {peer}: {error}");
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Option<Packet> {
+        loop {
+            let (size, from) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return None,
+                Err(error) => {
+                    warn!("Couldn't receive: {error}");
+                    return None;
+                }
+            };
+            if !self.peers.contains(&from) {
+                continue;
+            }
+            let packet = std::str::from_utf8(&self.buffer[..size])
+                .ok()
+                .and_then(|text| ron::from_str(text).ok());
+            match packet {
+                Some(packet) => return Some(packet),
+                None => warn!("Ignoring a garbled packet from {from}"),
+            }
+        }
+    }
+}
+
+/// Carries packets between players in the same process, such as several clients in one test.
+pub struct ChannelTransport {
+    peers: Vec<Sender<Packet>>,
+    inbox: Mutex<Receiver<Packet>>,
+    // Lose every this many packets sent
+    drop_every: Option<usize>,
+    sent: usize,
+}
+
+impl ChannelTransport {
+    /// One transport per player, each reaching all the others.
+    pub fn connect(players: usize) -> Vec<Self> {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..players).map(|_| channel()).unzip();
+        receivers
+            .into_iter()
+            .enumerate()
+            .map(|(player, inbox)| Self {
+                peers: senders
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != player)
+                    .map(|(_, sender)| sender.clone())
+                    .collect(),
+                inbox: Mutex::new(inbox),
+                drop_every: None,
+                sent: 0,
+            })
+            .collect()
+    }
+
+    /// Lose every `n`th packet, like an unreliable network would.
+    pub fn dropping_every(self, n: usize) -> Self {
+        Self {
+            drop_every: Some(n),
+            ..self
+        }
+    }
+}
+
+impl Transport for ChannelTransport {
+    fn broadcast(&mut self, packet: &Packet) {
+        self.sent += 1;
+        if self.drop_every.is_some_and(|n| self.sent.is_multiple_of(n)) {
+            return;
+        }
+        for peer in &self.peers {
+            // A player that has left just doesn't hear from us any more
+            let _ = peer.send(packet.clone());
+        }
+    }
+
+    fn receive(&mut self) -> Option<Packet> {
+        self.inbox.get_mut().ok()?.try_recv().ok()
+    }
+}
+
+/// This player's side of a lockstep game.
+#[derive(Resource)]
+pub struct Lockstep {
+    player: u8,
+    players: u8,
+    input_delay: u64,
+    transport: Box<dyn Transport>,
+    // The tick being waited on or run
+    tick: u64,
+    // Everyone's commands for the ticks still to run, by tick and then player
+    commands: BTreeMap<u64, Vec<Option<Vec<PlayerCommand>>>>,
+    // Commands given here, by tick, until every other player has them
+    unacknowledged: BTreeMap<u64, Vec<PlayerCommand>>,
+    // Commands given here that haven't been given a tick yet
+    pending: Vec<PlayerCommand>,
+    // The next tick to give the commands given here
+    next_local_tick: u64,
+    // The first tick each player is missing commands for, as they last said
+    waiting_on: Vec<u64>,
+    // When each player was last heard from, once they have been
+    last_heard: Vec<Option<Duration>>,
+    connected: Vec<bool>,
+    // Everyone's state hashes, by tick and then player
+    checksums: BTreeMap<u64, Vec<Option<u64>>>,
+    latest_checksum: Option<StateHash>,
+    desynced_at: Option<u64>,
+}
+
+impl Lockstep {
+    /// Player `player` of `players`, sending commands `input_delay` ticks ahead. Players are
+    /// counted from 0, so `player` has to be below `players`.
+    pub fn new(player: u8, players: u8, input_delay: u64, transport: impl Transport) -> Self {
+        assert!(player < players, "player {player} isn't one of {players}");
+        Self {
+            player,
+            players,
+            input_delay,
+            transport: Box::new(transport),
+            tick: 0,
+            commands: BTreeMap::new(),
+            unacknowledged: BTreeMap::new(),
+            pending: Vec::new(),
+            next_local_tick: input_delay,
+            waiting_on: vec![0; usize::from(players)],
+            last_heard: vec![None; usize::from(players)],
+            connected: vec![true; usize::from(players)],
+            checksums: BTreeMap::new(),
+            latest_checksum: None,
+            desynced_at: None,
+        }
+    }
+
+    /// The first tick whose state hash didn't match another player's, if any has.
+    pub fn desynced_at(&self) -> Option<u64> {
+        self.desynced_at
+    }
+
+    /// Whether `player` is still in the game. Once anyone has left, the game can't go on.
+    pub fn is_connected(&self, player: u8) -> bool {
+        self.connected[usize::from(player)]
+    }
+
+    // The first player to have left, if anyone has
+    fn disconnected(&self) -> Option<u8> {
+        (0..self.players).find(|player| !self.is_connected(*player))
+    }
+
+    // Whether everyone's commands for `tick` are in. Nobody can give any before the delay
+    fn has_commands(&self, tick: u64) -> bool {
+        tick < self.input_delay
+            || self
+                .commands
+                .get(&tick)
+                .is_some_and(|players| players.iter().all(Option::is_some))
+    }
+
+    fn first_missing_tick(&self) -> u64 {
+        let mut tick = self.tick;
+        while self.has_commands(tick) {
+            tick += 1;
+        }
+        tick
+    }
+
+    fn set_commands(&mut self, tick: u64, player: usize, commands: Vec<PlayerCommand>) {
+        let players = usize::from(self.players);
+        let slot = &mut self
+            .commands
+            .entry(tick)
+            .or_insert_with(|| vec![None; players])[player];
+        if slot.is_none() {
+            *slot = Some(commands);
+        }
+    }
+
+    fn receive(&mut self, packet: Packet, now: Duration) {
+        if packet.player >= self.players
+            || packet.player == self.player
+            || !self.is_connected(packet.player)
+        {
+            return;
+        }
+        let player = usize::from(packet.player);
+        self.last_heard[player] = Some(now);
+        self.waiting_on[player] = self.waiting_on[player].max(packet.waiting_on);
+        for (tick, commands) in (packet.first_tick..).zip(packet.commands) {
+            // Already run
+            if tick < self.tick {
+                continue;
+            }
+            // Players can only command their own team
+            let commands = commands
+                .into_iter()
+                .filter(|command| command.team == Team(packet.player))
+                .collect();
+            self.set_commands(tick, player, commands);
+        }
+        if let Some(checksum) = packet.checksum {
+            self.record_checksum(player, checksum);
+        }
+    }
+
+    // Give up on players that have gone quiet, or fallen too far behind to catch up
+    fn check_connections(&mut self, now: Duration) {
+        for player in 0..self.players {
+            let other = usize::from(player);
+            if player == self.player || !self.connected[other] {
+                continue;
+            }
+            let silent = self.last_heard[other]
+                .is_some_and(|heard| now.saturating_sub(heard) > DISCONNECT_TIMEOUT);
+            let behind = self.next_local_tick.saturating_sub(self.waiting_on[other]);
+            if silent || behind > MAX_UNACKNOWLEDGED {
+                error!("Lost connection to player {player}");
+                self.connected[other] = false;
+            }
+        }
+    }
+
+    fn record_checksum(&mut self, player: usize, checksum: StateHash) {
+        let players = usize::from(self.players);
+        let hashes = self
+            .checksums
+            .entry(checksum.tick)
+            .or_insert_with(|| vec![None; players]);
+        hashes[player] = Some(checksum.hash);
+        let ours = hashes[usize::from(self.player)];
+        let differs = ours.is_some_and(|ours| hashes.iter().flatten().any(|hash| *hash != ours));
+        if differs && self.desynced_at.is_none() {
+            error!(
+                "Out of sync with the other players since tick {}",
+                checksum.tick
+            );
+            self.desynced_at = Some(checksum.tick);
+        }
+    }
+}
+
+#[derive(Component)]
+struct LockstepText;
+
+// Trade commands with the other players, and hold the tick until everyone's are in
+fn exchange_commands(
+    time: Res<Time>,
+    mut lockstep: ResMut<Lockstep>,
+    mut clock: ResMut<SimClock>,
+    mut given: EventReader<PlayerCommand>,
+    ids: Query<(Entity, &SimId)>,
+) {
+    let lockstep = &mut *lockstep;
+    let team = Team(lockstep.player);
+    let mut to_ids = None;
+    for command in given.read().filter(|command| command.team == team) {
+        let to_ids = to_ids.get_or_insert_with(|| EntitySwap::to_stand_ins(&ids));
+        let mut command = command.clone();
+        command.map_entities(to_ids);
+        lockstep.pending.push(command);
+    }
+    if !clock.running {
+        return;
+    }
+
+    lockstep.tick = clock.tick;
+    let now = time.elapsed();
+    while let Some(packet) = lockstep.transport.receive() {
+        lockstep.receive(packet, now);
+    }
+    lockstep.check_connections(now);
+
+    // Commands given now are carried out once everyone has had time to hear about them
+    let player = usize::from(lockstep.player);
+    while lockstep.next_local_tick <= clock.tick + lockstep.input_delay {
+        let tick = lockstep.next_local_tick;
+        let commands = match tick == clock.tick + lockstep.input_delay {
+            true => std::mem::take(&mut lockstep.pending),
+            false => Vec::new(),
+        };
+        lockstep.unacknowledged.insert(tick, commands.clone());
+        lockstep.set_commands(tick, player, commands);
+        lockstep.next_local_tick += 1;
+    }
+
+    // Keep sending whatever someone still in the game might be missing
+    let acknowledged = (0..usize::from(lockstep.players))
+        .filter(|other| *other != player && lockstep.connected[*other])
+        .map(|other| lockstep.waiting_on[other])
+        .min()
+        .unwrap_or(u64::MAX);
+    lockstep.unacknowledged = lockstep.unacknowledged.split_off(&acknowledged);
+    let packet = Packet {
+        player: lockstep.player,
+        waiting_on: lockstep.first_missing_tick(),
+        first_tick: lockstep
+            .unacknowledged
+            .keys()
+            .next()
+            .copied()
+            .unwrap_or(lockstep.next_local_tick),
+        commands: lockstep.unacknowledged.values().cloned().collect(),
+        checksum: lockstep.latest_checksum,
+    };
+    lockstep.transport.broadcast(&packet);
+
+    clock.held = !lockstep.has_commands(clock.tick);
+}
+
+// Carry out everyone's commands for this tick, in player order
+fn lockstep_commands(
+    clock: Res<SimClock>,
+    ids: Query<(Entity, &SimId)>,
+    mut lockstep: ResMut<Lockstep>,
+    mut commands: ResMut<TickCommands>,
+) {
+    commands.0.clear();
+    // Nothing can be given for the first few ticks
+    let Some(players) = lockstep.commands.remove(&clock.tick) else {
+        return;
+    };
+    let mut from_ids = EntitySwap::from_stand_ins(&ids);
+    for mut command in players.into_iter().flatten().flatten() {
+        command.map_entities(&mut from_ids);
+        commands.0.push(command);
+    }
+}
+
+fn exchange_checksums(clock: Res<SimClock>, state: HashedState, mut lockstep: ResMut<Lockstep>) {
+    if !clock.tick.is_multiple_of(HASH_INTERVAL) {
+        return;
+    }
+    let checksum = StateHash {
+        tick: clock.tick,
+        hash: state.hash(),
+    };
+    let player = usize::from(lockstep.player);
+    lockstep.latest_checksum = Some(checksum);
+    lockstep.record_checksum(player, checksum);
+    let oldest = clock.tick.saturating_sub(CHECKSUMS_KEPT * HASH_INTERVAL);
+    lockstep.checksums = lockstep.checksums.split_off(&oldest);
+}
+
+fn spawn_lockstep_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(40.0),
+                top: Val::Percent(30.0),
+                ..default()
+            },
+            ..default()
+        },
+        Pickable::IGNORE,
+        LockstepText,
+    ));
+}
+
+fn update_lockstep_text(
+    clock: Res<SimClock>,
+    lockstep: Res<Lockstep>,
+    mut text_q: Query<&mut Text, With<LockstepText>>,
+) {
+    let value = match (lockstep.desynced_at(), lockstep.disconnected()) {
+        (Some(tick), _) => format!("Out of sync with the other players since tick {tick}"),
+        (None, Some(player)) => format!("Lost connection to player {player}"),
+        (None, None) if clock.running && clock.held => "Waiting for the other players".to_string(),
+        (None, None) => String::new(),
+    };
+    for mut text in text_q.iter_mut() {
+        *text = Text::from_section(value.clone(), TextStyle::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::command::{Action, CommandPlugin};
+    use crate::economy::Stockpiles;
+    use crate::formation::FormationKind;
+    use crate::orders::{Destination, IssueOrder, Order};
+    use crate::sim::{assign_sim_id, SimPlugin, DEFAULT_TICK_RATE};
+
+    // A client with a unit for each of two teams, one tick per update
+    fn client(player: u8, transport: ChannelTransport) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins((
+                SimPlugin::default(),
+                CommandPlugin,
+                LockstepPlugin::default(),
+            ))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1.0 / DEFAULT_TICK_RATE,
+            )))
+            .insert_resource(Lockstep::new(player, 2, DEFAULT_INPUT_DELAY, transport))
+            .init_resource::<Stockpiles>()
+            .add_systems(FixedUpdate, teleport.in_set(CommandSet::Apply));
+        for team in 0..2 {
+            let unit = app
+                .world_mut()
+                .spawn((Transform::default(), Team(team)))
+                .id();
+            assign_sim_id(unit, app.world_mut());
+        }
+        app.world_mut().resource_mut::<SimClock>().running = true;
+        app
+    }
+
+    // Stands in for the whole of the simulation: units ordered to move are simply there
+    fn teleport(commands: Res<TickCommands>, mut units: Query<(&Team, &mut Transform)>) {
+        for command in &commands.0 {
+            let Action::Order(IssueOrder {
+                units: ordered,
+                order: Order::Move(destination),
+                ..
+            }) = &command.action
+            else {
+                continue;
+            };
+            for unit in ordered {
+                if let Ok((team, mut transform)) = units.get_mut(*unit) {
+                    if *team == command.team {
+                        transform.translation = destination.target;
+                    }
+                }
+            }
+        }
+    }
+
+    fn unit(app: &mut App, team: u8) -> Entity {
+        let world = app.world_mut();
+        world
+            .query::<(Entity, &Team)>()
+            .iter(world)
+            .find(|(_, unit_team)| unit_team.0 == team)
+            .unwrap()
+            .0
+    }
+
+    fn order_move(app: &mut App, team: u8, to: Vec3) {
+        let unit = unit(app, team);
+        let destination = Destination {
+            target: to,
+            facing: None,
+            formation: FormationKind::default(),
+        };
+        app.world_mut().send_event(PlayerCommand::order(
+            Team(team),
+            vec![unit],
+            Order::Move(destination),
+            false,
+        ));
+    }
+
+    fn position(app: &mut App, team: u8) -> Vec3 {
+        let unit = unit(app, team);
+        app.world().get::<Transform>(unit).unwrap().translation
+    }
+
+    fn tick(app: &App) -> u64 {
+        app.world().resource::<SimClock>().tick
+    }
+
+    fn run(clients: &mut [App], updates: usize) {
+        for _ in 0..updates {
+            for client in clients.iter_mut() {
+                client.update();
+            }
+        }
+    }
+
+    #[test]
+    fn commands_run_on_the_same_tick_everywhere() {
+        let mut clients: Vec<App> = ChannelTransport::connect(2)
+            .into_iter()
+            .zip(0..)
+            .map(|(transport, player)| client(player, transport))
+            .collect();
+        run(&mut clients, 10);
+
+        let (east, north) = (Vec3::X * 5.0, Vec3::Z * 3.0);
+        order_move(&mut clients[0], 0, east);
+        order_move(&mut clients[1], 1, north);
+        run(&mut clients, 1);
+        // Held back by the input delay
+        assert_eq!(position(&mut clients[0], 0), Vec3::ZERO);
+        assert_eq!(position(&mut clients[1], 0), Vec3::ZERO);
+
+        run(&mut clients, 2 * HASH_INTERVAL as usize);
+        for client in clients.iter_mut() {
+            assert_eq!(position(client, 0), east);
+            assert_eq!(position(client, 1), north);
+            assert_eq!(client.world().resource::<Lockstep>().desynced_at(), None);
+        }
+        assert!(tick(&clients[0]) > HASH_INTERVAL);
+    }
+
+    #[test]
+    fn waits_for_every_player() {
+        let mut transports = ChannelTransport::connect(2);
+        let mut alone = client(0, transports.remove(0));
+        for _ in 0..20 {
+            alone.update();
+        }
+        assert_eq!(tick(&alone), DEFAULT_INPUT_DELAY);
+        assert!(alone.world().resource::<SimClock>().held);
+    }
+
+    #[test]
+    fn makes_up_for_lost_packets() {
+        let mut clients: Vec<App> = ChannelTransport::connect(2)
+            .into_iter()
+            .zip(0..)
+            .map(|(transport, player)| client(player, transport.dropping_every(3)))
+            .collect();
+        run(&mut clients, 5);
+        order_move(&mut clients[1], 1, Vec3::ONE);
+        run(&mut clients, 100);
+        assert!(tick(&clients[0]) > 60);
+        for client in clients.iter_mut() {
+            assert_eq!(position(client, 1), Vec3::ONE);
+            assert_eq!(client.world().resource::<Lockstep>().desynced_at(), None);
+        }
+    }
+
+    #[test]
+    fn notices_going_out_of_sync() {
+        let mut clients: Vec<App> = ChannelTransport::connect(2)
+            .into_iter()
+            .zip(0..)
+            .map(|(transport, player)| client(player, transport))
+            .collect();
+        run(&mut clients, 5);
+        // Something the simulation didn't do
+        let unit = unit(&mut clients[1], 0);
+        clients[1]
+            .world_mut()
+            .get_mut::<Transform>(unit)
+            .unwrap()
+            .translation = Vec3::Y;
+        run(&mut clients, 2 * HASH_INTERVAL as usize);
+        for client in &clients {
+            assert!(client
+                .world()
+                .resource::<Lockstep>()
+                .desynced_at()
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn gives_up_on_a_player_that_goes_quiet() {
+        let mut clients: Vec<App> = ChannelTransport::connect(2)
+            .into_iter()
+            .zip(0..)
+            .map(|(transport, player)| client(player, transport))
+            .collect();
+        run(&mut clients, 10);
+        // The second player stops answering
+        clients.pop();
+        let timeout = DISCONNECT_TIMEOUT.as_secs_f64() * DEFAULT_TICK_RATE;
+        run(&mut clients, timeout as usize - 10);
+        assert!(clients[0].world().resource::<Lockstep>().is_connected(1));
+
+        run(&mut clients, 20);
+        let lockstep = clients[0].world().resource::<Lockstep>();
+        assert!(!lockstep.is_connected(1));
+        assert_eq!(lockstep.disconnected(), Some(1));
+    }
+
+    #[test]
+    fn packets_too_big_to_send_lose_ticks_off_the_end() {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let mut receiver = UdpTransport::bind(localhost, Vec::new()).unwrap();
+        let receiver_address = receiver.socket.local_addr().unwrap();
+        let mut sender = UdpTransport::bind(localhost, vec![receiver_address]).unwrap();
+        receiver.peers = vec![sender.socket.local_addr().unwrap()];
+
+        // Orders for plenty of units every tick, far more than fit in one packet
+        let units: Vec<Entity> = (0..500).map(Entity::from_raw).collect();
+        let command = PlayerCommand::order(Team(0), units, Order::Stop, false);
+        let packet = Packet {
+            player: 0,
+            waiting_on: 0,
+            first_tick: 7,
+            commands: vec![vec![command]; 40],
+            checksum: None,
+        };
+        assert!(ron::to_string(&packet).unwrap().len() > MAX_PACKET);
+        sender.broadcast(&packet);
+
+        let received = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                receiver.receive()
+            })
+            .unwrap();
+        assert_eq!(received.first_tick, 7);
+        assert!(!received.commands.is_empty());
+        assert!(received.commands.len() < packet.commands.len());
+    }
+
+    #[test]
+    fn checks_the_number_of_players() {
+        let session = |player, peers: usize| UdpSession {
+            player,
+            bind: "127.0.0.1:7000".parse().unwrap(),
+            peers: vec!["127.0.0.1:7001".parse().unwrap(); peers],
+            input_delay: DEFAULT_INPUT_DELAY,
+        };
+        assert_eq!(session(0, 1).players(), Some(2));
+        assert_eq!(session(3, 3).players(), Some(4));
+        assert_eq!(session(0, 0).players(), None);
+        assert_eq!(session(0, 4).players(), None);
+        assert_eq!(session(2, 1).players(), None);
+        assert_eq!(session(5, 1).players(), None);
+    }
+}
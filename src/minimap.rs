@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::ui::{RelativeCursorPosition, UiSystem};
+use bevy_mod_picking::prelude::Pickable;
+use bevy_rts_camera::{RtsCamera, RtsCameraSystemSet};
+
+use crate::fog::{FogOfWar, FogState};
+use crate::movement::Move;
+use crate::navigation::NavGrid;
+use crate::orders::Targeting;
+use crate::team::{PlayerId, Team};
+
+// Size of the minimap on screen, in logical pixels
+const MINIMAP_SIZE: f32 = 200.0;
+// Texture resolution, in pixels per world unit
+const PIXELS_PER_UNIT: f32 = 2.0;
+// Terrain this far above the lowest ground is drawn as raised
+const RAISED_HEIGHT: f32 = 0.3;
+
+const GROUND_COLOR: [u8; 3] = [77, 128, 77];
+const RAISED_COLOR: [u8; 3] = [204, 179, 153];
+const TEAM_DOT_COLORS: [[u8; 3]; 4] = [
+    [230, 60, 60],
+    [170, 70, 210],
+    [70, 120, 230],
+    [240, 200, 40],
+];
+const NEUTRAL_DOT_COLOR: [u8; 3] = [255, 255, 255];
+const VIEW_COLOR: [u8; 3] = [255, 255, 255];
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MinimapCursor>()
+            .add_systems(Startup, spawn_minimap)
+            .add_systems(PreUpdate, update_minimap_cursor.after(UiSystem::Focus))
+            .add_systems(
+                Update,
+                (pan_to_minimap.before(RtsCameraSystemSet), draw_minimap),
+            );
+    }
+}
+
+/// Where on the map the cursor points while it's over the minimap, at terrain height. Input
+/// handling uses it in place of raycasting into the scene.
+#[derive(Resource, Default)]
+pub struct MinimapCursor(pub Option<Vec3>);
+
+#[derive(Component)]
+struct Minimap {
+    // Terrain colours, redrawn only when the `NavGrid` changes
+    background: Vec<[u8; 3]>,
+    bounds: Rect,
+    size: UVec2,
+}
+
+impl Minimap {
+    fn pixel_of(&self, position: Vec2) -> Vec2 {
+        (position - self.bounds.min) / self.bounds.size() * self.size.as_vec2()
+    }
+}
+
+fn spawn_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    ));
+    commands.spawn((
+        ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(12.0),
+                bottom: Val::Px(12.0),
+                width: Val::Px(MINIMAP_SIZE),
+                height: Val::Px(MINIMAP_SIZE),
+                border: UiRect::all(Val::Px(2.0)),
+                ..default()
+            },
+            image: UiImage::new(image),
+            ..default()
+        },
+        BorderColor(Color::srgb(0.1, 0.1, 0.1)),
+        Minimap {
+            background: Vec::new(),
+            bounds: Rect::default(),
+            size: UVec2::ZERO,
+        },
+        RelativeCursorPosition::default(),
+        // Clicks on the minimap don't reach units behind it
+        Pickable::default(),
+    ));
+}
+
+fn update_minimap_cursor(
+    minimap_q: Query<(&Minimap, &RelativeCursorPosition)>,
+    grid: Res<NavGrid>,
+    mut cursor: ResMut<MinimapCursor>,
+) {
+    cursor.0 = minimap_q.get_single().ok().and_then(|(minimap, relative)| {
+        let normalized = relative.normalized.filter(|_| relative.mouse_over())?;
+        let xz = minimap.bounds.min + normalized * minimap.bounds.size();
+        let height = grid.height_at(xz).unwrap_or(0.0);
+        Some(Vec3::new(xz.x, height, xz.y))
+    });
+}
+
+// Left-click or drag on the minimap to move the camera there
+fn pan_to_minimap(
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    cursor: Res<MinimapCursor>,
+    targeting: Res<Targeting>,
+    mut panning: Local<bool>,
+    mut cam_q: Query<&mut RtsCamera>,
+) {
+    if mouse_button_input.just_pressed(MouseButton::Left) {
+        // A pending order takes its target from the minimap instead
+        *panning = cursor.0.is_some() && targeting.0.is_none();
+    }
+    if !mouse_button_input.pressed(MouseButton::Left) {
+        *panning = false;
+    }
+    let Some(position) = cursor.0.filter(|_| *panning) else {
+        return;
+    };
+    for mut cam in cam_q.iter_mut() {
+        cam.target_focus.translation = position;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn draw_minimap(
+    grid: Res<NavGrid>,
+    fog: Res<FogOfWar>,
+    player: Res<PlayerId>,
+    mut minimap_q: Query<(&mut Minimap, &UiImage)>,
+    units: Query<(&Transform, Option<&Team>), Or<(With<Team>, With<Move>)>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<RtsCamera>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Ok((mut minimap, ui_image)) = minimap_q.get_single_mut() else {
+        return;
+    };
+    if grid.len() == 0 {
+        return;
+    }
+
+    let Some(image) = images.get_mut(&ui_image.texture) else {
+        return;
+    };
+    if grid.is_changed() || minimap.background.is_empty() {
+        let bounds = grid.bounds();
+        let size = (bounds.size() * PIXELS_PER_UNIT).ceil().as_uvec2();
+        let texel = bounds.size() / size.as_vec2();
+        minimap.background = (0..size.x * size.y)
+            .map(|i| {
+                let pixel = Vec2::new((i % size.x) as f32, (i / size.x) as f32) + 0.5;
+                match grid.surface_height(bounds.min + pixel * texel) {
+                    Some(height) if height > RAISED_HEIGHT => RAISED_COLOR,
+                    Some(_) => GROUND_COLOR,
+                    None => [0, 0, 0],
+                }
+            })
+            .collect();
+        minimap.bounds = bounds;
+        minimap.size = size;
+        image.resize(Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        });
+    }
+
+    let size = minimap.size;
+    let texel = minimap.bounds.size() / size.as_vec2();
+    let mut canvas = Canvas {
+        data: &mut image.data,
+        size,
+    };
+
+    // Terrain, darkened where the player can't see
+    for (i, color) in minimap.background.iter().enumerate() {
+        let pixel = Vec2::new((i as u32 % size.x) as f32, (i as u32 / size.x) as f32) + 0.5;
+        let xz = minimap.bounds.min + pixel * texel;
+        let shade = match fog.state(player.0, Vec3::new(xz.x, 0.0, xz.y)) {
+            FogState::Unexplored => 0.2,
+            FogState::Explored => 0.55,
+            FogState::Visible => 1.0,
+        };
+        let shaded = color.map(|c| (c as f32 * shade) as u8);
+        canvas.data[i * 4..i * 4 + 4].copy_from_slice(&[shaded[0], shaded[1], shaded[2], 255]);
+    }
+
+    for (transform, team) in units.iter() {
+        let position = transform.translation;
+        let color = match team {
+            Some(team) if *team != player.0 && !fog.is_visible(player.0, position) => continue,
+            Some(team) => TEAM_DOT_COLORS[team.0 as usize % TEAM_DOT_COLORS.len()],
+            None => NEUTRAL_DOT_COLOR,
+        };
+        let pixel = minimap.pixel_of(position.xz()).as_ivec2();
+        for offset in [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE] {
+            canvas.plot(pixel + offset, color);
+        }
+    }
+
+    // The part of the ground the camera sees, a trapezoid when looking at an angle
+    if let Ok((camera, camera_transform)) = camera_q.get_single() {
+        if let Some(viewport) = camera.logical_viewport_size() {
+            let corners = [
+                Vec2::ZERO,
+                Vec2::new(viewport.x, 0.0),
+                viewport,
+                Vec2::new(0.0, viewport.y),
+            ]
+            .map(|corner| {
+                let ray = camera.viewport_to_world(camera_transform, corner)?;
+                // Rays above the horizon never meet the ground, cut them off at the map edge
+                let distance = ray
+                    .intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Y))
+                    .unwrap_or(minimap.bounds.size().max_element());
+                Some(minimap.pixel_of(ray.get_point(distance).xz()))
+            });
+            if let [Some(a), Some(b), Some(c), Some(d)] = corners {
+                for (from, to) in [(a, b), (b, c), (c, d), (d, a)] {
+                    canvas.line(from, to, VIEW_COLOR);
+                }
+            }
+        }
+    }
+}
+
+// Just enough drawing for the minimap, straight onto RGBA8 image data
+struct Canvas<'a> {
+    data: &'a mut [u8],
+    size: UVec2,
+}
+
+impl Canvas<'_> {
+    fn plot(&mut self, pixel: IVec2, color: [u8; 3]) {
+        if pixel.x < 0 || pixel.y < 0 || pixel.x >= self.size.x as i32 {
+            return;
+        }
+        let index = (pixel.y as usize * self.size.x as usize + pixel.x as usize) * 4;
+        if let Some(rgba) = self.data.get_mut(index..index + 4) {
+            rgba.copy_from_slice(&[color[0], color[1], color[2], 255]);
+        }
+    }
+
+    fn line(&mut self, from: Vec2, to: Vec2, color: [u8; 3]) {
+        let steps = from.distance(to).ceil().min(4096.0) as i32;
+        for step in 0..=steps {
+            let t = step as f32 / steps.max(1) as f32;
+            self.plot(from.lerp(to, t).as_ivec2(), color);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::flow_field::FlowFieldPlugin;
+use crate::formation::FormationPlugin;
+use crate::navigation::{NavigationPlugin, Path};
+use crate::orders::OrderSet;
+use crate::sim::SimSet;
+use crate::steering::{Steering, SteeringPlugin, SteeringSet};
+
+/// Height of a unit's origin above the ground it stands on.
+pub const UNIT_GROUND_OFFSET: f32 = 0.75;
+// Units cut the corner once they're this close to an intermediate waypoint
+const WAYPOINT_RADIUS: f32 = 0.4;
+
+/// Gets units where they're told to go: path finding, flow fields, formations and steering,
+/// plus following the planned paths.
+pub struct UnitMovementPlugin;
+
+impl Plugin for UnitMovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(NavigationPlugin)
+            .add_plugins(FlowFieldPlugin)
+            .add_plugins(FormationPlugin)
+            .add_plugins(SteeringPlugin)
+            .add_systems(
+                FixedUpdate,
+                (move_unit, follow_path)
+                    .after(OrderSet)
+                    .before(SteeringSet)
+                    .in_set(SimSet::Update),
+            );
+    }
+}
+
+/// A unit that circles the middle of the map on its own.
+#[derive(Component, Default)]
+pub struct Move {
+    /// How far round the circle it is, in radians.
+    pub angle: f32,
+}
+
+// Move a unit in a circle
+fn move_unit(time: Res<Time>, mut cube_q: Query<(&mut Transform, &mut Move)>) {
+    if let Ok((mut cube_tfm, mut cube)) = cube_q.get_single_mut() {
+        // Rotate 20 degrees a second, wrapping around to 0 after a full rotation
+        cube.angle = (cube.angle + 20f32.to_radians() * time.delta_seconds()) % TAU;
+        // Convert angle to position
+        let pos = Vec3::new(cube.angle.sin() * 7.5, 0.75, cube.angle.cos() * 7.5);
+        cube_tfm.translation = pos;
+    }
+}
+
+fn follow_path(
+    mut units: Query<(Entity, &Transform, &mut Path, &mut Steering)>,
+    mut commands: Commands,
+) {
+    for (entity, transform, mut path, mut steering) in units.iter_mut() {
+        // Intermediate waypoints only need passing near, the last one is the goal itself
+        let position = transform.translation.xz();
+        while path.0.len() > 1 && path.0[0].xz().distance(position) < WAYPOINT_RADIUS {
+            path.0.pop_front();
+        }
+
+        let Some(waypoint) = path.0.front().copied() else {
+            commands.entity(entity).remove::<Path>();
+            continue;
+        };
+        steering.desired = (waypoint.xz() - position).normalize_or_zero();
+        steering.goal = path.0.back().copied();
+    }
+}
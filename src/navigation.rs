@@ -0,0 +1,637 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::f32::consts::SQRT_2;
+
+use bevy::prelude::*;
+use bevy::render::mesh::{PrimitiveTopology, VertexAttributeValues};
+use bevy_rts_camera::Ground;
+
+use crate::sim::SimSet;
+
+const CELL_SIZE: f32 = 0.5;
+
+const NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(-1, 0),
+    IVec2::new(0, 1),
+    IVec2::new(0, -1),
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, -1),
+];
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Footprint>()
+            .register_type::<Path>()
+            .init_resource::<NavGrid>()
+            .init_resource::<NavSettings>()
+            // Buildings block the grid from the tick their footprint is laid down
+            .add_systems(
+                FixedUpdate,
+                bake_nav_grid.after(SimSet::Spawn).before(SimSet::Update),
+            );
+    }
+}
+
+/// Tunables for baking the `NavGrid`. Changing them rebakes the grid.
+#[derive(Resource)]
+pub struct NavSettings {
+    /// Steepest ground, in radians, that units can walk on.
+    pub max_slope: f32,
+}
+
+impl Default for NavSettings {
+    fn default() -> Self {
+        NavSettings {
+            max_slope: 40.0f32.to_radians(),
+        }
+    }
+}
+
+/// XZ area a building stands on. Nothing can walk through it.
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct Footprint(pub Rect);
+
+/// Remaining waypoints a unit will walk through, nearest first.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Path(pub VecDeque<Vec3>);
+
+/// Walkability of the `Ground` terrain, rasterised from its meshes onto a regular XZ grid.
+#[derive(Resource, Default)]
+pub struct NavGrid {
+    origin: Vec2,
+    width: i32,
+    depth: i32,
+    heights: Vec<f32>,
+    // Too steep or off the terrain, before any footprints are added
+    terrain_blocked: Vec<bool>,
+    blocked: Vec<bool>,
+    // Largest height difference between neighbouring cells that is still walkable
+    max_rise: f32,
+}
+
+impl NavGrid {
+    // Build a grid covering every triangle of the given world-space meshes
+    pub(crate) fn bake<'a>(
+        pieces: impl Iterator<Item = (&'a Mesh, &'a GlobalTransform)>,
+        settings: &NavSettings,
+    ) -> Self {
+        let triangles: Vec<[Vec3; 3]> = pieces
+            .flat_map(|(mesh, transform)| world_triangles(mesh, transform))
+            .collect();
+        if triangles.is_empty() {
+            return Self::default();
+        }
+
+        let (min, max) = triangles.iter().flatten().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), v| (min.min(v.xz()), max.max(v.xz())),
+        );
+        let size = ((max - min) / CELL_SIZE).ceil().as_ivec2().max(IVec2::ONE);
+        let mut grid = NavGrid {
+            origin: min,
+            width: size.x,
+            depth: size.y,
+            heights: vec![f32::NEG_INFINITY; (size.x * size.y) as usize],
+            terrain_blocked: Vec::new(),
+            blocked: Vec::new(),
+            max_rise: 0.0,
+        };
+
+        // Keep the highest surface under each cell centre
+        for triangle in triangles.iter() {
+            let (lo, hi) = triangle.iter().fold(
+                (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+                |(lo, hi), v| (lo.min(v.xz()), hi.max(v.xz())),
+            );
+            let first = grid.cell_of(lo).max(IVec2::ZERO);
+            let last = grid.cell_of(hi).min(size - IVec2::ONE);
+            for z in first.y..=last.y {
+                for x in first.x..=last.x {
+                    let cell = IVec2::new(x, z);
+                    if let Some(height) = height_on_triangle(triangle, grid.cell_center_xz(cell)) {
+                        let index = grid.index(cell).unwrap();
+                        grid.heights[index] = grid.heights[index].max(height);
+                    }
+                }
+            }
+        }
+
+        // A cell is too steep if the climb to any of its direct neighbours is
+        let max_rise = settings.max_slope.tan() * CELL_SIZE;
+        grid.terrain_blocked = (0..grid.heights.len())
+            .map(|index| {
+                let height = grid.heights[index];
+                let cell = grid.cell_at(index);
+                !height.is_finite()
+                    || NEIGHBOURS[..4].iter().any(|offset| {
+                        grid.index(cell + *offset)
+                            .is_some_and(|n| (grid.heights[n] - height).abs() > max_rise)
+                    })
+            })
+            .collect();
+        grid.blocked = grid.terrain_blocked.clone();
+        grid.max_rise = max_rise;
+        grid
+    }
+
+    // Block every cell whose centre lies under one of the footprints, on top of the terrain
+    pub(crate) fn apply_footprints(&mut self, footprints: impl Iterator<Item = Rect>) {
+        self.blocked.clone_from(&self.terrain_blocked);
+        for area in footprints {
+            let first = self.cell_of(area.min).max(IVec2::ZERO);
+            let last = self
+                .cell_of(area.max)
+                .min(IVec2::new(self.width, self.depth) - IVec2::ONE);
+            for z in first.y..=last.y {
+                for x in first.x..=last.x {
+                    let cell = IVec2::new(x, z);
+                    if area.contains(self.cell_center_xz(cell)) {
+                        let index = self.index(cell).unwrap();
+                        self.blocked[index] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// True if `area` lies on walkable terrain that's flat enough to build on. Footprints
+    /// already there aren't taken into account.
+    pub fn is_buildable(&self, area: Rect) -> bool {
+        let bounds = self.bounds();
+        if self.terrain_blocked.is_empty()
+            || !bounds.contains(area.min)
+            || !bounds.contains(area.max)
+        {
+            return false;
+        }
+        let first = self.cell_of(area.min);
+        let last = self.cell_of(area.max - Vec2::splat(1e-3));
+        let mut lowest = f32::MAX;
+        let mut highest = f32::MIN;
+        for z in first.y..=last.y {
+            for x in first.x..=last.x {
+                let Some(index) = self.index(IVec2::new(x, z)) else {
+                    return false;
+                };
+                if self.terrain_blocked[index] {
+                    return false;
+                }
+                lowest = lowest.min(self.heights[index]);
+                highest = highest.max(self.heights[index]);
+            }
+        }
+        highest - lowest <= self.max_rise * 2.0
+    }
+
+    /// Ground height under `position`, blended between neighbouring cells on the same walkable
+    /// surface so units glide up slopes rather than stepping cell by cell.
+    pub fn height_at(&self, position: Vec2) -> Option<f32> {
+        let own = self.heights[self.index(self.cell_of(position))?];
+        if !own.is_finite() {
+            return None;
+        }
+
+        let local = (position - self.origin) / CELL_SIZE - 0.5;
+        let base = local.floor().as_ivec2();
+        let t = local - local.floor();
+        let mut total = 0.0;
+        let mut weight = 0.0;
+        for (offset, w) in [
+            (IVec2::new(0, 0), (1.0 - t.x) * (1.0 - t.y)),
+            (IVec2::new(1, 0), t.x * (1.0 - t.y)),
+            (IVec2::new(0, 1), (1.0 - t.x) * t.y),
+            (IVec2::new(1, 1), t.x * t.y),
+        ] {
+            // Skip cells across a cliff edge, they're a different surface
+            let Some(height) = self.index(base + offset).map(|i| self.heights[i]) else {
+                continue;
+            };
+            if height.is_finite() && (height - own).abs() <= self.max_rise * 2.0 {
+                total += height * w;
+                weight += w;
+            }
+        }
+        Some(if weight > 0.0 { total / weight } else { own })
+    }
+
+    /// Height of the highest surface in the cell under `position`, without any blending.
+    pub fn surface_height(&self, position: Vec2) -> Option<f32> {
+        let height = self.heights[self.index(self.cell_of(position))?];
+        height.is_finite().then_some(height)
+    }
+
+    /// XZ area covered by the grid.
+    pub fn bounds(&self) -> Rect {
+        let size = IVec2::new(self.width, self.depth).as_vec2() * CELL_SIZE;
+        Rect::from_corners(self.origin, self.origin + size)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.blocked.len()
+    }
+
+    pub(crate) fn index(&self, cell: IVec2) -> Option<usize> {
+        (cell.x >= 0 && cell.y >= 0 && cell.x < self.width && cell.y < self.depth)
+            .then(|| (cell.y * self.width + cell.x) as usize)
+    }
+
+    pub(crate) fn cell_at(&self, index: usize) -> IVec2 {
+        IVec2::new(index as i32 % self.width, index as i32 / self.width)
+    }
+
+    pub(crate) fn cell_of(&self, position: Vec2) -> IVec2 {
+        ((position - self.origin) / CELL_SIZE).floor().as_ivec2()
+    }
+
+    fn cell_center_xz(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + 0.5) * CELL_SIZE
+    }
+
+    pub(crate) fn cell_center(&self, cell: IVec2) -> Vec3 {
+        let xz = self.cell_center_xz(cell);
+        let height = self.index(cell).map_or(0.0, |i| self.heights[i]);
+        Vec3::new(xz.x, height, xz.y)
+    }
+
+    pub(crate) fn is_walkable(&self, cell: IVec2) -> bool {
+        self.index(cell).is_some_and(|i| !self.blocked[i])
+    }
+
+    // Closest walkable cell to `cell`, searching outwards ring by ring
+    fn nearest_walkable(&self, cell: IVec2) -> Option<IVec2> {
+        let max_radius = self.width.max(self.depth);
+        (0..=max_radius).find_map(|radius| {
+            (-radius..=radius)
+                .flat_map(|dz| (-radius..=radius).map(move |dx| IVec2::new(dx, dz)))
+                .filter(|offset| offset.x.abs() == radius || offset.y.abs() == radius)
+                .map(|offset| cell + offset)
+                .filter(|c| self.is_walkable(*c))
+                .min_by_key(|c| (*c - cell).length_squared())
+        })
+    }
+
+    // Walkable neighbours of `cell` with the cost of stepping to each. Diagonal steps are only
+    // allowed when they don't cut the corner of an obstacle
+    pub(crate) fn neighbours(&self, cell: IVec2) -> impl Iterator<Item = (IVec2, f32)> + '_ {
+        NEIGHBOURS.into_iter().filter_map(move |offset| {
+            let next = cell + offset;
+            let diagonal = offset.x != 0 && offset.y != 0;
+            let clear = self.is_walkable(next)
+                && (!diagonal
+                    || (self.is_walkable(cell + IVec2::new(offset.x, 0))
+                        && self.is_walkable(cell + IVec2::new(0, offset.y))));
+            clear.then_some((next, if diagonal { SQRT_2 } else { 1.0 }))
+        })
+    }
+
+    /// Cell an order to `goal` should head for, and the exact point to stop at. Orders onto
+    /// blocked ground are moved to the nearest walkable cell.
+    pub fn resolve_goal(&self, goal: Vec3) -> Option<(IVec2, Vec3)> {
+        let goal_cell = self.cell_of(goal.xz());
+        let cell = self.nearest_walkable(goal_cell)?;
+        let position = if cell == goal_cell {
+            goal
+        } else {
+            self.cell_center(cell)
+        };
+        Some((cell, position))
+    }
+
+    // True if a straight walk from `from` to `to` never enters a blocked cell
+    pub(crate) fn line_of_sight(&self, from: Vec3, to: Vec3) -> bool {
+        let delta = to.xz() - from.xz();
+        let steps = (delta.length() / (CELL_SIZE * 0.25)).ceil() as i32;
+        (0..=steps).all(|step| {
+            let point = from.xz() + delta * (step as f32 / steps.max(1) as f32);
+            self.is_walkable(self.cell_of(point))
+        })
+    }
+
+    /// A* search over the grid. Returns the waypoints to walk through, excluding the start.
+    /// If the goal is blocked the path ends at the nearest walkable cell instead, and if that
+    /// can't be reached, at the reachable cell closest to it.
+    pub fn find_path(&self, start: Vec3, goal: Vec3) -> Option<Vec<Vec3>> {
+        if self.blocked.is_empty() {
+            return Some(vec![goal]);
+        }
+
+        let start_cell = self.cell_of(start.xz());
+        let (end_cell, mut end) = self.resolve_goal(goal)?;
+        let start_index = self.index(start_cell)?;
+        let mut end_index = self.index(end_cell)?;
+
+        let mut cost = vec![f32::INFINITY; self.heights.len()];
+        let mut came_from = vec![usize::MAX; self.heights.len()];
+        let mut open = BinaryHeap::new();
+        cost[start_index] = 0.0;
+        open.push(Frontier {
+            estimate: octile(start_cell, end_cell),
+            cell: start_cell,
+        });
+        let mut closest = (octile(start_cell, end_cell), start_index);
+
+        while let Some(Frontier { cell, .. }) = open.pop() {
+            let index = self.index(cell).unwrap();
+            if index == end_index {
+                break;
+            }
+            let remaining = octile(cell, end_cell);
+            if remaining < closest.0 {
+                closest = (remaining, index);
+            }
+            for (next, step) in self.neighbours(cell) {
+                let next_index = self.index(next).unwrap();
+                let next_cost = cost[index] + step;
+                if next_cost < cost[next_index] {
+                    cost[next_index] = next_cost;
+                    came_from[next_index] = index;
+                    open.push(Frontier {
+                        estimate: next_cost + octile(next, end_cell),
+                        cell: next,
+                    });
+                }
+            }
+        }
+
+        if start_index != end_index && came_from[end_index] == usize::MAX {
+            // Unreachable, e.g. a plateau with no way up, so get as close as possible
+            if closest.1 == start_index {
+                return None;
+            }
+            end_index = closest.1;
+            end = self.cell_center(self.cell_at(end_index));
+        }
+
+        let mut cells = vec![end_index];
+        while let Some(&index) = cells.last() {
+            if index == start_index || came_from[index] == usize::MAX {
+                break;
+            }
+            cells.push(came_from[index]);
+        }
+        cells.reverse();
+
+        let mut waypoints: Vec<Vec3> = cells
+            .iter()
+            .skip(1)
+            .map(|i| self.cell_center(self.cell_at(*i)))
+            .collect();
+        match waypoints.last_mut() {
+            Some(last) => *last = end,
+            None => waypoints.push(end),
+        }
+
+        Some(self.smooth(start, waypoints))
+    }
+
+    // Drop every waypoint that can be skipped by walking straight to a later one
+    fn smooth(&self, start: Vec3, waypoints: Vec<Vec3>) -> Vec<Vec3> {
+        let mut smoothed = Vec::new();
+        let mut anchor = start;
+        let mut i = 0;
+        while i < waypoints.len() {
+            let mut furthest = i;
+            for (j, waypoint) in waypoints.iter().enumerate().skip(i + 1) {
+                if self.line_of_sight(anchor, *waypoint) {
+                    furthest = j;
+                }
+            }
+            anchor = waypoints[furthest];
+            smoothed.push(anchor);
+            i = furthest + 1;
+        }
+        smoothed
+    }
+}
+
+type ChangedGround = (
+    With<Ground>,
+    Or<(Changed<GlobalTransform>, Changed<Handle<Mesh>>)>,
+);
+
+#[derive(PartialEq)]
+pub(crate) struct Frontier {
+    pub(crate) estimate: f32,
+    pub(crate) cell: IVec2,
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the BinaryHeap pops the cheapest estimate first
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn octile(a: IVec2, b: IVec2) -> f32 {
+    let d = (a - b).abs();
+    let (short, long) = (d.x.min(d.y) as f32, d.x.max(d.y) as f32);
+    long + (SQRT_2 - 1.0) * short
+}
+
+fn world_triangles(mesh: &Mesh, transform: &GlobalTransform) -> Vec<[Vec3; 3]> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Vec::new();
+    }
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return Vec::new();
+    };
+
+    let vertices: Vec<Vec3> = positions
+        .iter()
+        .map(|p| transform.transform_point(Vec3::from(*p)))
+        .collect();
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(indices) => indices.iter().collect(),
+        None => (0..vertices.len()).collect(),
+    };
+    indices
+        .chunks_exact(3)
+        .map(|t| [vertices[t[0]], vertices[t[1]], vertices[t[2]]])
+        .collect()
+}
+
+// Height of the triangle's surface above `point`, if the point lies within its XZ footprint
+fn height_on_triangle(triangle: &[Vec3; 3], point: Vec2) -> Option<f32> {
+    let [a, b, c] = triangle.map(|v| v.xz());
+    let det = (b.y - c.y) * (a.x - c.x) + (c.x - b.x) * (a.y - c.y);
+    if det.abs() < f32::EPSILON {
+        // Vertical faces have no footprint
+        return None;
+    }
+    let w0 = ((b.y - c.y) * (point.x - c.x) + (c.x - b.x) * (point.y - c.y)) / det;
+    let w1 = ((c.y - a.y) * (point.x - c.x) + (a.x - c.x) * (point.y - c.y)) / det;
+    let w2 = 1.0 - w0 - w1;
+    const EPSILON: f32 = -1e-4;
+    (w0 >= EPSILON && w1 >= EPSILON && w2 >= EPSILON)
+        .then(|| w0 * triangle[0].y + w1 * triangle[1].y + w2 * triangle[2].y)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn bake_nav_grid(
+    mut grid: ResMut<NavGrid>,
+    meshes: Res<Assets<Mesh>>,
+    ground: Query<(&Handle<Mesh>, &GlobalTransform), With<Ground>>,
+    changed: Query<(), ChangedGround>,
+    mut removed: RemovedComponents<Ground>,
+    footprints: Query<&Footprint>,
+    changed_footprints: Query<(), Changed<Footprint>>,
+    mut removed_footprints: RemovedComponents<Footprint>,
+    settings: Res<NavSettings>,
+) {
+    let removed_any = removed.read().count() > 0;
+    let terrain_changed = !changed.is_empty() || removed_any || settings.is_changed();
+    // Buildings going up or coming down only need the footprints redone
+    let footprints_changed =
+        !changed_footprints.is_empty() || removed_footprints.read().count() > 0;
+    if !terrain_changed && !footprints_changed {
+        return;
+    }
+
+    if terrain_changed {
+        *grid = NavGrid::bake(
+            ground
+                .iter()
+                .filter_map(|(handle, transform)| Some((meshes.get(handle)?, transform))),
+            &settings,
+        );
+    }
+    grid.apply_footprints(footprints.iter().map(|footprint| footprint.0));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A grid baked from `pieces` as the terrain, each placed by its transform
+    fn grid(pieces: &[(Mesh, Transform)]) -> NavGrid {
+        let placed: Vec<_> = pieces
+            .iter()
+            .map(|(mesh, transform)| (mesh, GlobalTransform::from(*transform)))
+            .collect();
+        NavGrid::bake(
+            placed.iter().map(|(mesh, transform)| (*mesh, transform)),
+            &NavSettings::default(),
+        )
+    }
+
+    fn flat(size: f32) -> (Mesh, Transform) {
+        (
+            Plane3d::default().mesh().size(size, size).into(),
+            Transform::IDENTITY,
+        )
+    }
+
+    fn walkable_at(grid: &NavGrid, position: Vec3) -> bool {
+        grid.is_walkable(grid.cell_of(position.xz()))
+    }
+
+    #[test]
+    fn paths_go_around_what_blocks_the_way() {
+        let mut grid = grid(&[flat(20.0)]);
+        // A wall across the direct route
+        grid.apply_footprints([Rect::new(-1.0, -6.0, 1.0, 6.0)].into_iter());
+        let start = Vec3::new(-5.0, 0.0, 0.0);
+        let goal = Vec3::new(5.0, 0.0, 0.0);
+        assert!(!grid.line_of_sight(start, goal));
+
+        let path = grid.find_path(start, goal).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+        let mut from = start;
+        for waypoint in &path {
+            assert!(grid.line_of_sight(from, *waypoint));
+            from = *waypoint;
+        }
+        assert!(path.iter().any(|waypoint| waypoint.z.abs() > 6.0));
+        // Smoothed down to the corners of the wall
+        assert!(path.len() <= 3, "{path:?}");
+    }
+
+    #[test]
+    fn goals_on_blocked_ground_move_to_the_nearest_walkable_cell() {
+        let mut grid = grid(&[flat(20.0)]);
+        let building = Rect::new(2.0, -2.0, 6.0, 2.0);
+        grid.apply_footprints([building].into_iter());
+
+        let (cell, position) = grid.resolve_goal(Vec3::new(5.5, 0.0, 0.0)).unwrap();
+        assert!(grid.is_walkable(cell));
+        assert!(!building.contains(position.xz()));
+        assert!(position.distance(Vec3::new(6.0, 0.0, 0.0)) <= CELL_SIZE);
+
+        // Where a goal is walkable it's kept exactly
+        let goal = Vec3::new(-3.3, 0.0, 1.7);
+        assert_eq!(
+            grid.resolve_goal(goal).map(|(_, position)| position),
+            Some(goal)
+        );
+    }
+
+    #[test]
+    fn unreachable_goals_end_as_close_as_can_be_reached() {
+        // A plateau with cliffs all round and no way up
+        let plateau = (
+            Cuboid::new(4.0, 3.0, 4.0).into(),
+            Transform::from_xyz(0.0, 1.5, 0.0),
+        );
+        let grid = grid(&[flat(20.0), plateau]);
+        let goal = Vec3::new(0.0, 3.0, 0.0);
+        assert!(walkable_at(&grid, goal));
+
+        let end = *grid
+            .find_path(Vec3::new(-8.0, 0.0, 0.0), goal)
+            .unwrap()
+            .last()
+            .unwrap();
+        assert!(end.y.abs() < 1e-3, "{end}");
+        // At the foot of the cliffs, on whichever side
+        let out = end.x.abs().max(end.z.abs());
+        assert!(out > 2.0 && out < 2.0 + 3.0 * CELL_SIZE, "{end}");
+    }
+
+    #[test]
+    fn steep_ground_is_blocked() {
+        let tilted = |degrees: f32| {
+            let (mesh, _) = flat(10.0);
+            (
+                mesh,
+                Transform::from_rotation(Quat::from_rotation_x(degrees.to_radians())),
+            )
+        };
+        let gentle = grid(&[tilted(30.0)]);
+        assert!(walkable_at(&gentle, Vec3::ZERO));
+        assert!(gentle
+            .find_path(Vec3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 3.0))
+            .is_some());
+
+        let steep = grid(&[tilted(60.0)]);
+        assert!(!walkable_at(&steep, Vec3::ZERO));
+        assert!(!steep.is_buildable(Rect::new(-1.0, -1.0, 1.0, 1.0)));
+
+        // The cliffs round a plateau too, but not its flat top
+        let plateau = (
+            Cuboid::new(4.0, 3.0, 4.0).into(),
+            Transform::from_xyz(0.0, 1.5, 0.0),
+        );
+        let cliffs = grid(&[flat(20.0), plateau]);
+        assert!(!walkable_at(&cliffs, Vec3::new(1.9, 0.0, 0.0)));
+        assert!(!walkable_at(&cliffs, Vec3::new(2.1, 0.0, 0.0)));
+        assert!(walkable_at(&cliffs, Vec3::new(0.0, 3.0, 0.0)));
+        assert!(walkable_at(&cliffs, Vec3::new(4.0, 0.0, 0.0)));
+    }
+}
@@ -0,0 +1,844 @@
+use std::collections::VecDeque;
+
+use bevy::ecs::entity::{EntityMapper, MapEntities};
+use bevy::ecs::reflect::ReflectMapEntities;
+use bevy::ecs::system::{EntityCommands, SystemParam};
+use bevy::prelude::*;
+use bevy_mod_raycast::prelude::{Raycast, RaycastSettings};
+use bevy_rts_camera::Ground;
+use serde::{Deserialize, Serialize};
+
+use crate::building::{Builder, ConstructionSite, PlacementSet};
+use crate::combat::{nearest_point, AttackTarget, Weapon};
+use crate::command::{Action, CommandSet, PlayerCommand, TickCommands};
+use crate::economy::{Gatherer, ResourceNode};
+use crate::flow_field::{FlowGoal, FLOW_FIELD_MIN_GROUP};
+use crate::formation::{assign_slots, formation_slots, FormationKind};
+use crate::minimap::MinimapCursor;
+use crate::movement::{Move, UNIT_GROUND_OFFSET};
+use crate::navigation::{Footprint, NavGrid, Path};
+use crate::production::RallyPoint;
+use crate::selection::{Selectable, Selected};
+use crate::sim::SimSet;
+use crate::steering::{OrderGroup, Steering, SteeringSet};
+use crate::team::{PlayerId, Team};
+
+// Right-dragging further than this orients the formation along the drag
+const MIN_FACING_DRAG: f32 = 1.0;
+// Following units keep about this far from whatever they follow
+const FOLLOW_DISTANCE: f32 = 1.5;
+// Re-plan a follow path once the followed unit has moved this far from its end
+const FOLLOW_REPATH_DISTANCE: f32 = 1.0;
+
+pub struct OrdersPlugin;
+
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ActiveOrder>()
+            .register_type::<OrderQueue>()
+            .add_event::<IssueOrder>()
+            .init_resource::<Targeting>()
+            .init_resource::<LastOrderGroup>()
+            .add_systems(
+                Update,
+                (
+                    order_hotkeys,
+                    move_selected_unit,
+                    target_order,
+                    draw_order_paths,
+                ),
+            )
+            .configure_sets(
+                FixedUpdate,
+                OrderSet.before(SteeringSet).in_set(SimSet::Update),
+            )
+            .add_systems(
+                FixedUpdate,
+                order_commands.after(PlacementSet).in_set(CommandSet::Apply),
+            )
+            .add_systems(
+                FixedUpdate,
+                (dispatch_orders, update_active_orders, advance_order_queue)
+                    .chain()
+                    .in_set(OrderSet),
+            );
+    }
+}
+
+/// Hands out orders and moves units on to their next one. Systems carrying orders out run after
+/// it, so a unit starts on an order the tick it's given.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct OrderSet;
+
+/// Where a group should go, and how to lay it out once there.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Destination {
+    pub target: Vec3,
+    /// XZ direction the formation faces; if unset it faces away from where the group is now.
+    pub facing: Option<Vec2>,
+    pub formation: FormationKind,
+}
+
+/// An order as given by a player, to a whole group of units at once.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Order {
+    Move(Destination),
+    /// Move, but engage any enemy met on the way.
+    AttackMove(Destination),
+    /// Walk back and forth between the current position and the destination.
+    Patrol(Destination),
+    /// Keep close to another unit, wherever it goes.
+    Follow(Entity),
+    /// Chase a unit until it's in weapon range and shoot it until it dies.
+    Attack(Entity),
+    /// Harvest a resource node, ferrying loads to the nearest drop-off until it runs out.
+    Gather(Entity),
+    /// Work on a construction site until it's finished.
+    Build(Entity),
+    /// Stay put, without being pushed aside by other units.
+    HoldPosition,
+    /// Drop every current and queued order.
+    Stop,
+}
+
+/// Sent by the simulation, or by `order_commands` for orders players give; `dispatch_orders`
+/// turns it into per-unit orders.
+#[derive(Event, Clone, Debug, Serialize, Deserialize)]
+pub struct IssueOrder {
+    pub units: Vec<Entity>,
+    pub order: Order,
+    /// Add to the end of each unit's queue instead of replacing its orders.
+    pub queue: bool,
+}
+
+impl MapEntities for IssueOrder {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for unit in self.units.iter_mut() {
+            *unit = entity_mapper.map_entity(*unit);
+        }
+        if let Order::Follow(target)
+        | Order::Attack(target)
+        | Order::Gather(target)
+        | Order::Build(target) = &mut self.order
+        {
+            *target = entity_mapper.map_entity(*target);
+        }
+    }
+}
+
+/// A move to a single destination, either planned with A* or by following a shared flow field.
+#[derive(Clone, Copy, Reflect)]
+pub struct MoveOrder {
+    pub position: Vec3,
+    /// Goal cell of the flow field to follow, for large group orders.
+    pub flow_cell: Option<IVec2>,
+    pub group: OrderGroup,
+}
+
+impl MoveOrder {
+    // False if there's no getting any closer to the destination
+    fn start(&self, entity: &mut EntityCommands, nav_grid: &NavGrid, from: Vec3) -> bool {
+        match self.flow_cell {
+            Some(cell) => {
+                entity.insert((
+                    FlowGoal {
+                        cell,
+                        position: self.position,
+                    },
+                    self.group,
+                ));
+                true
+            }
+            None => match nav_grid.find_path(from, self.position) {
+                Some(waypoints) => {
+                    entity.insert((Path(waypoints.into()), self.group));
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+/// What a single unit has been told to do.
+#[derive(Clone, Copy, Reflect)]
+pub enum UnitOrder {
+    Move(MoveOrder),
+    AttackMove(MoveOrder),
+    Patrol { leg: MoveOrder, other_end: Vec3 },
+    Follow(Entity),
+    Attack(Entity),
+    Gather(Entity),
+    Build(Entity),
+    HoldPosition,
+}
+
+impl UnitOrder {
+    // Where the unit will be once this order is done, if it goes anywhere
+    fn destination(&self) -> Option<Vec3> {
+        match self {
+            UnitOrder::Move(leg) | UnitOrder::AttackMove(leg) | UnitOrder::Patrol { leg, .. } => {
+                Some(leg.position)
+            }
+            UnitOrder::Follow(_)
+            | UnitOrder::Attack(_)
+            | UnitOrder::Gather(_)
+            | UnitOrder::Build(_)
+            | UnitOrder::HoldPosition => None,
+        }
+    }
+
+    /// The entity the order is aimed at, if there is one.
+    pub fn target(&self) -> Option<Entity> {
+        match self {
+            UnitOrder::Follow(target)
+            | UnitOrder::Attack(target)
+            | UnitOrder::Gather(target)
+            | UnitOrder::Build(target) => Some(*target),
+            UnitOrder::Move(_)
+            | UnitOrder::AttackMove(_)
+            | UnitOrder::Patrol { .. }
+            | UnitOrder::HoldPosition => None,
+        }
+    }
+
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        if let UnitOrder::Follow(target)
+        | UnitOrder::Attack(target)
+        | UnitOrder::Gather(target)
+        | UnitOrder::Build(target) = self
+        {
+            *target = entity_mapper.map_entity(*target);
+        }
+    }
+
+    // Replace whatever the unit is doing with this order. False if it goes somewhere the unit
+    // can't get any closer to
+    fn start(self, entity: &mut EntityCommands, nav_grid: &NavGrid, from: Vec3) -> bool {
+        entity.remove::<(Path, FlowGoal, OrderGroup)>();
+        let started = match self {
+            UnitOrder::Move(leg) | UnitOrder::AttackMove(leg) | UnitOrder::Patrol { leg, .. } => {
+                leg.start(entity, nav_grid, from)
+            }
+            _ => true,
+        };
+        match self {
+            UnitOrder::Attack(target) => entity.insert(AttackTarget(target)),
+            _ => entity.remove::<AttackTarget>(),
+        };
+        entity.insert(ActiveOrder(self));
+        started
+    }
+}
+
+/// The order a unit is carrying out right now.
+#[derive(Component, Reflect)]
+#[reflect(Component, MapEntities)]
+pub struct ActiveOrder(pub UnitOrder);
+
+impl MapEntities for ActiveOrder {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.0.map_entities(entity_mapper);
+    }
+}
+
+/// Orders to carry out, in order, once the current one is finished. Queued with Shift.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, MapEntities, Default)]
+pub struct OrderQueue(pub VecDeque<UnitOrder>);
+
+impl MapEntities for OrderQueue {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for order in self.0.iter_mut() {
+            order.map_entities(entity_mapper);
+        }
+    }
+}
+
+/// The id of the last `OrderGroup` handed out. Saved with the match, so groups ordered after a
+/// load don't share an id with ones still on their way.
+#[derive(Resource, Default)]
+pub struct LastOrderGroup(pub u32);
+
+/// An order waiting for the player to pick its target with a left click.
+#[derive(Resource, Default)]
+pub struct Targeting(pub Option<TargetedOrder>);
+
+#[derive(Clone)]
+pub enum TargetedOrder {
+    Patrol,
+    AttackMove,
+    /// Where to put a new building, by id. Handled by construction rather than `target_order`.
+    Place(String),
+}
+
+/// Where the cursor ray first meets an entity accepted by `filter`.
+pub fn cursor_hit(
+    windows: &Query<&Window>,
+    camera_q: &Query<(&Camera, &GlobalTransform)>,
+    raycast: &mut Raycast,
+    filter: &dyn Fn(Entity) -> bool,
+) -> Option<(Entity, Vec3)> {
+    let (camera, camera_transform) = camera_q.get_single().ok()?;
+    let cursor_position = windows.get_single().ok()?.cursor_position()?;
+    let ray = camera.viewport_to_world(camera_transform, cursor_position)?;
+    let settings = RaycastSettings {
+        filter,
+        ..default()
+    };
+    raycast
+        .cast_ray(ray, &settings)
+        .first()
+        .map(|(entity, hit)| (*entity, hit.position()))
+}
+
+fn order_hotkeys(
+    key_input: Res<ButtonInput<KeyCode>>,
+    player: Res<PlayerId>,
+    selected: Query<Entity, With<Selected>>,
+    mut targeting: ResMut<Targeting>,
+    mut orders: EventWriter<PlayerCommand>,
+) {
+    let units: Vec<Entity> = selected.iter().collect();
+    let queue = key_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    // Ctrl+S and friends are left for other bindings
+    if units.is_empty() || key_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    if key_input.just_pressed(KeyCode::KeyS) {
+        orders.send(PlayerCommand::order(player.0, units, Order::Stop, false));
+    } else if key_input.just_pressed(KeyCode::KeyH) {
+        orders.send(PlayerCommand::order(
+            player.0,
+            units,
+            Order::HoldPosition,
+            queue,
+        ));
+    } else if key_input.just_pressed(KeyCode::KeyP) {
+        targeting.0 = Some(TargetedOrder::Patrol);
+    } else if key_input.just_pressed(KeyCode::KeyA) {
+        targeting.0 = Some(TargetedOrder::AttackMove);
+    } else if key_input.just_pressed(KeyCode::Escape) {
+        targeting.0 = None;
+    }
+}
+
+/// What a right-click can land on.
+#[allow(clippy::type_complexity)]
+#[derive(SystemParam)]
+pub struct RightClickTargets<'w, 's> {
+    ground: Query<'w, 's, (), With<Ground>>,
+    units: Query<'w, 's, Option<&'static Team>, Or<(With<Selectable>, With<Move>)>>,
+    nodes: Query<'w, 's, (), With<ResourceNode>>,
+    sites: Query<'w, 's, &'static Team, With<ConstructionSite>>,
+    parents: Query<'w, 's, &'static Parent>,
+}
+
+impl RightClickTargets<'_, '_> {
+    // The unit, resource node or construction site `entity` is part of, if any. Trees are made
+    // of several meshes
+    fn owner(&self, entity: Entity) -> Option<Entity> {
+        if self.units.contains(entity) || self.nodes.contains(entity) || self.sites.contains(entity)
+        {
+            return Some(entity);
+        }
+        let parent = self.parents.get(entity).ok()?.get();
+        self.nodes.contains(parent).then_some(parent)
+    }
+
+    // The work a right-click on `entity` gives to the units able to do it
+    fn job(&self, entity: Entity, player: Team) -> Option<Order> {
+        if self.nodes.contains(entity) {
+            return Some(Order::Gather(entity));
+        }
+        let own_site = self.sites.get(entity).is_ok_and(|team| *team == player);
+        own_site.then_some(Order::Build(entity))
+    }
+
+    fn accepts(&self, entity: Entity) -> bool {
+        self.ground.contains(entity) || self.owner(entity).is_some()
+    }
+}
+
+// Right-click moves the selection, follows the friendly unit under the cursor, attacks the
+// enemy under it, or puts workers to work on the resource or construction site under it
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn move_selected_unit(
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    selected_units: Query<Entity, With<Selected>>,
+    workers: Query<(Has<Gatherer>, Has<Builder>)>,
+    targets: RightClickTargets,
+    player: Res<PlayerId>,
+    mut raycast: Raycast,
+    formation: Res<FormationKind>,
+    minimap: Res<MinimapCursor>,
+    mut targeting: ResMut<Targeting>,
+    mut drag_start: Local<Option<Vec3>>,
+    mut gizmos: Gizmos,
+    mut orders: EventWriter<PlayerCommand>,
+) {
+    let released = mouse_button_input.just_released(MouseButton::Right);
+    if !mouse_button_input.pressed(MouseButton::Right) && !released {
+        return;
+    }
+    let pressed = mouse_button_input.just_pressed(MouseButton::Right);
+    // Right-click backs out of picking a target rather than moving
+    if pressed && targeting.0.take().is_some() {
+        *drag_start = None;
+        return;
+    }
+
+    let queue = key_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let units: Vec<Entity> = selected_units.iter().collect();
+
+    // Orders go wherever the cursor meets the terrain, including the tops of plateaus, or to
+    // the point on the minimap under it
+    let is_target = |entity| targets.accepts(entity);
+    let Some((hit, world_position)) = minimap.0.map(|p| (None, p)).or_else(|| {
+        cursor_hit(&windows, &camera_q, &mut raycast, &is_target).map(|(e, p)| (Some(e), p))
+    }) else {
+        return;
+    };
+
+    let hit = hit.filter(|_| pressed).and_then(|e| targets.owner(e));
+    if let Some(job) = hit.and_then(|hit| targets.job(hit, player.0)) {
+        // Only gatherers harvest and only builders build, everyone else just walks over
+        let (able, others): (Vec<Entity>, Vec<Entity>) = units.into_iter().partition(|e| {
+            workers.get(*e).is_ok_and(|(gatherer, builder)| match job {
+                Order::Gather(_) => gatherer,
+                _ => builder,
+            })
+        });
+        for (units, order) in [
+            (able, job),
+            (
+                others,
+                Order::Move(Destination {
+                    target: world_position,
+                    facing: None,
+                    formation: *formation,
+                }),
+            ),
+        ] {
+            if !units.is_empty() {
+                orders.send(PlayerCommand::order(player.0, units, order, queue));
+            }
+        }
+        return;
+    }
+
+    if let Some((hit, team)) = hit.and_then(|hit| Some((hit, targets.units.get(hit).ok()?))) {
+        let order = match team {
+            Some(team) if *team != player.0 => Order::Attack(hit),
+            _ => Order::Follow(hit),
+        };
+        let units: Vec<Entity> = units.into_iter().filter(|e| *e != hit).collect();
+        if !units.is_empty() {
+            orders.send(PlayerCommand::order(player.0, units, order, queue));
+        }
+        return;
+    }
+
+    // The press sets where the formation goes, dragging before release sets which way it faces
+    if pressed {
+        *drag_start = Some(world_position);
+    }
+    let Some(target) = *drag_start else {
+        return;
+    };
+    let dragged = target.distance(world_position) > MIN_FACING_DRAG;
+    if !released {
+        if dragged {
+            gizmos.arrow(
+                target + Vec3::Y * 0.05,
+                world_position + Vec3::Y * 0.05,
+                Color::WHITE,
+            );
+        }
+        return;
+    }
+    *drag_start = None;
+
+    if units.is_empty() {
+        return;
+    }
+    let destination = Destination {
+        target,
+        facing: dragged.then(|| (world_position - target).xz()),
+        formation: *formation,
+    };
+    orders.send(PlayerCommand::order(
+        player.0,
+        units,
+        Order::Move(destination),
+        queue,
+    ));
+}
+
+// Left-click picks the target of a pending patrol or attack-move
+#[allow(clippy::too_many_arguments)]
+fn target_order(
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    selected_units: Query<Entity, With<Selected>>,
+    ground: Query<(), With<Ground>>,
+    mut raycast: Raycast,
+    formation: Res<FormationKind>,
+    minimap: Res<MinimapCursor>,
+    player: Res<PlayerId>,
+    mut targeting: ResMut<Targeting>,
+    mut orders: EventWriter<PlayerCommand>,
+) {
+    // Fire on release, so the selection systems never see the start of this click
+    let order: fn(Destination) -> Order = match &targeting.0 {
+        Some(TargetedOrder::Patrol) => Order::Patrol,
+        Some(TargetedOrder::AttackMove) => Order::AttackMove,
+        _ => return,
+    };
+    if !mouse_button_input.just_released(MouseButton::Left) {
+        return;
+    }
+    targeting.0 = None;
+
+    let is_ground = |entity| ground.contains(entity);
+    let Some(target) = minimap
+        .0
+        .or_else(|| cursor_hit(&windows, &camera_q, &mut raycast, &is_ground).map(|(_, p)| p))
+    else {
+        return;
+    };
+    let destination = Destination {
+        target,
+        facing: None,
+        formation: *formation,
+    };
+    orders.send(PlayerCommand::order(
+        player.0,
+        selected_units.iter().collect(),
+        order(destination),
+        key_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+    ));
+}
+
+// Pass the orders players gave on to their own units
+fn order_commands(
+    commands: Res<TickCommands>,
+    teams: Query<&Team>,
+    mut orders: EventWriter<IssueOrder>,
+) {
+    for command in &commands.0 {
+        let Action::Order(order) = &command.action else {
+            continue;
+        };
+        let units = order
+            .units
+            .iter()
+            .copied()
+            .filter(|unit| teams.get(*unit).is_ok_and(|team| *team == command.team))
+            .collect();
+        orders.send(IssueOrder {
+            units,
+            ..order.clone()
+        });
+    }
+}
+
+// Turn group orders into per-unit orders, laying destinations out in formation
+#[allow(clippy::type_complexity)]
+fn dispatch_orders(
+    mut events: EventReader<IssueOrder>,
+    nav_grid: Res<NavGrid>,
+    mut units: Query<(&Transform, Option<&ActiveOrder>, Option<&mut OrderQueue>), With<Steering>>,
+    mut rally_points: Query<&mut RallyPoint>,
+    mut last_group: ResMut<LastOrderGroup>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        // Buildings take the destination of anything that goes somewhere as their rally point
+        if let Order::Move(destination)
+        | Order::AttackMove(destination)
+        | Order::Patrol(destination) = event.order
+        {
+            for entity in event.units.iter() {
+                if let Ok(mut rally_point) = rally_points.get_mut(*entity) {
+                    rally_point.0 = Some(destination.target);
+                }
+            }
+        }
+
+        let members: Vec<Entity> = event
+            .units
+            .iter()
+            .copied()
+            .filter(|entity| units.contains(*entity))
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+
+        if let Order::Stop = event.order {
+            for entity in members {
+                commands.entity(entity).remove::<(
+                    ActiveOrder,
+                    OrderQueue,
+                    Path,
+                    FlowGoal,
+                    AttackTarget,
+                    OrderGroup,
+                )>();
+            }
+            continue;
+        }
+
+        // Queued orders are laid out from wherever each unit will be when they start
+        let starts: Vec<Vec3> = members
+            .iter()
+            .map(|entity| {
+                let (transform, active, queue) = units.get(*entity).unwrap();
+                let queued = queue.and_then(|q| q.0.iter().rev().find_map(|o| o.destination()));
+                let current = active.and_then(|a| a.0.destination());
+                match event.queue {
+                    true => queued.or(current).unwrap_or(transform.translation),
+                    false => transform.translation,
+                }
+            })
+            .collect();
+
+        let per_unit: Vec<UnitOrder> = match event.order {
+            Order::Move(destination)
+            | Order::AttackMove(destination)
+            | Order::Patrol(destination) => {
+                last_group.0 += 1;
+                let legs = formation_legs(&starts, &destination, &nav_grid, last_group.0);
+                legs.into_iter()
+                    .zip(starts.iter())
+                    .map(|(leg, start)| match event.order {
+                        Order::AttackMove(_) => UnitOrder::AttackMove(leg),
+                        Order::Patrol(_) => UnitOrder::Patrol {
+                            leg,
+                            other_end: *start,
+                        },
+                        _ => UnitOrder::Move(leg),
+                    })
+                    .collect()
+            }
+            Order::Follow(target) => vec![UnitOrder::Follow(target); members.len()],
+            Order::Attack(target) => vec![UnitOrder::Attack(target); members.len()],
+            Order::Gather(node) => vec![UnitOrder::Gather(node); members.len()],
+            Order::Build(site) => vec![UnitOrder::Build(site); members.len()],
+            Order::HoldPosition => vec![UnitOrder::HoldPosition; members.len()],
+            Order::Stop => unreachable!(),
+        };
+
+        for (entity, order) in members.iter().zip(per_unit) {
+            let (transform, _, queue) = units.get_mut(*entity).unwrap();
+            let mut entity_commands = commands.entity(*entity);
+            if !event.queue {
+                entity_commands.remove::<OrderQueue>();
+                order.start(&mut entity_commands, &nav_grid, transform.translation);
+            } else if let Some(mut queue) = queue {
+                queue.0.push_back(order);
+            } else {
+                entity_commands.insert(OrderQueue(VecDeque::from([order])));
+            }
+        }
+    }
+}
+
+// One move per unit, to its slot of the formation around the destination
+fn formation_legs(
+    starts: &[Vec3],
+    destination: &Destination,
+    nav_grid: &NavGrid,
+    group_id: u32,
+) -> Vec<MoveOrder> {
+    let target = destination.target;
+    let centroid = starts.iter().sum::<Vec3>() / starts.len() as f32;
+    let facing = destination
+        .facing
+        .unwrap_or_else(|| (target - centroid).xz());
+    let slots = formation_slots(destination.formation, starts.len(), target, facing);
+    let assignment = assign_slots(starts, &slots);
+    let group = OrderGroup {
+        id: group_id,
+        radius: slots
+            .iter()
+            .map(|slot| slot.distance(target))
+            .fold(0.0, f32::max)
+            + 1.0,
+    };
+
+    // Large groups share one flow field to the formation centre rather than each running A*
+    let flow_cell = (starts.len() >= FLOW_FIELD_MIN_GROUP)
+        .then(|| nav_grid.resolve_goal(target).map(|(cell, _)| cell))
+        .flatten();
+
+    assignment
+        .into_iter()
+        .map(|slot| MoveOrder {
+            position: nav_grid
+                .resolve_goal(slots[slot])
+                .map_or(slots[slot], |(_, position)| position),
+            flow_cell,
+            group,
+        })
+        .collect()
+}
+
+#[allow(clippy::type_complexity)]
+fn update_active_orders(
+    nav_grid: Res<NavGrid>,
+    mut units: Query<(
+        Entity,
+        &Transform,
+        &mut Steering,
+        Option<&ActiveOrder>,
+        Option<&Path>,
+        Has<FlowGoal>,
+        Has<AttackTarget>,
+        Option<&Weapon>,
+    )>,
+    targets: Query<(&Transform, Option<&Footprint>)>,
+    mut commands: Commands,
+) {
+    for (entity, transform, mut steering, active, path, following_flow, engaged, weapon) in
+        units.iter_mut()
+    {
+        // Attack-moving and patrolling units stand still while they fight
+        steering.anchored = match active {
+            Some(ActiveOrder(UnitOrder::HoldPosition)) => true,
+            Some(ActiveOrder(UnitOrder::AttackMove(_) | UnitOrder::Patrol { .. })) => engaged,
+            _ => false,
+        };
+        let Some(ActiveOrder(order)) = active else {
+            continue;
+        };
+        let moving = path.is_some() || following_flow;
+        let position = transform.translation;
+
+        match *order {
+            UnitOrder::Move(_) | UnitOrder::AttackMove(_) => {
+                if !moving {
+                    commands.entity(entity).remove::<ActiveOrder>();
+                }
+            }
+            UnitOrder::Patrol { leg, other_end } => {
+                if !moving {
+                    // Turn around and head back, the flow field only covers the first leg
+                    let back = UnitOrder::Patrol {
+                        leg: MoveOrder {
+                            position: other_end,
+                            flow_cell: None,
+                            group: leg.group,
+                        },
+                        other_end: leg.position,
+                    };
+                    // Once there's no getting any closer to the other end the patrol is over,
+                    // rather than searching for a way there again every tick
+                    let mut entity = commands.entity(entity);
+                    if !back.start(&mut entity, &nav_grid, position) {
+                        entity.remove::<ActiveOrder>();
+                    }
+                }
+            }
+            UnitOrder::Follow(target) | UnitOrder::Attack(target) => {
+                let Ok((target_transform, footprint)) = targets.get(target) else {
+                    commands
+                        .entity(entity)
+                        .remove::<(ActiveOrder, AttackTarget, Path)>();
+                    continue;
+                };
+                // Buildings are closed in on up to their nearest edge
+                let closest = nearest_point(position.xz(), target_transform.translation, footprint);
+                let target_position =
+                    Vec3::new(closest.x, target_transform.translation.y, closest.y);
+                // Attackers close in until just inside their weapon's reach
+                let reach = match (*order, weapon) {
+                    (UnitOrder::Attack(_), Some(weapon)) => weapon.range * 0.9,
+                    _ => FOLLOW_DISTANCE,
+                };
+                if position.xz().distance(closest) <= reach {
+                    steering.anchored = matches!(*order, UnitOrder::Attack(_));
+                    commands.entity(entity).remove::<Path>();
+                    continue;
+                }
+                // Buildings stay put, so the path to one only goes stale once it's been walked
+                let stale = match footprint {
+                    Some(_) => path.is_none(),
+                    None => path.and_then(|path| path.0.back()).is_none_or(|end| {
+                        end.xz().distance(target_position.xz()) > FOLLOW_REPATH_DISTANCE
+                    }),
+                };
+                if stale {
+                    if let Some(waypoints) = nav_grid.find_path(position, target_position) {
+                        commands.entity(entity).insert(Path(waypoints.into()));
+                    }
+                }
+            }
+            // Gatherers are driven by the economy, builders by construction
+            UnitOrder::Gather(_) | UnitOrder::Build(_) | UnitOrder::HoldPosition => {}
+        }
+    }
+}
+
+// Start the next queued order as soon as a unit has nothing else to do
+fn advance_order_queue(
+    nav_grid: Res<NavGrid>,
+    mut units: Query<(Entity, &Transform, &mut OrderQueue), Without<ActiveOrder>>,
+    mut commands: Commands,
+) {
+    for (entity, transform, mut queue) in units.iter_mut() {
+        let mut entity_commands = commands.entity(entity);
+        match queue.0.pop_front() {
+            Some(order) => {
+                order.start(&mut entity_commands, &nav_grid, transform.translation);
+            }
+            None => {
+                entity_commands.remove::<OrderQueue>();
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn draw_order_paths(
+    units: Query<
+        (
+            &Transform,
+            Option<&Path>,
+            Option<&FlowGoal>,
+            Option<&OrderQueue>,
+        ),
+        With<Selected>,
+    >,
+    mut gizmos: Gizmos,
+) {
+    let lift = Vec3::Y * 0.05;
+    let color = Color::srgba(0.2, 0.8, 0.2, 0.6);
+    for (transform, path, flow_goal, queue) in units.iter() {
+        let mut points = vec![transform.translation - Vec3::Y * UNIT_GROUND_OFFSET];
+        match path {
+            Some(path) => points.extend(path.0.iter().copied()),
+            None => points.extend(flow_goal.map(|goal| goal.position)),
+        }
+        let current_goals = points.len();
+        if let Some(queue) = queue {
+            points.extend(queue.0.iter().filter_map(|order| order.destination()));
+        }
+        if points.len() < 2 {
+            continue;
+        }
+
+        gizmos.linestrip(points.iter().map(|p| *p + lift), color);
+        // Mark every destination, but not the intermediate waypoints of the current path
+        for point in points[current_goals - 1..].iter() {
+            gizmos.circle(*point + lift, Dir3::Y, 0.2, color);
+        }
+    }
+}
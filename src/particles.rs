@@ -2,6 +2,16 @@ use bevy::prelude::*;
 use bevy::sprite::MaterialMesh2dBundle;
 use rand::prelude::*;
 
+/// Bursts of fading particles wherever the left mouse button is clicked, in 2D.
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, create_particle_assets)
+            .add_systems(Update, (spawn_particles, update_particles));
+    }
+}
+
 // Particle component
 #[derive(Component)]
 struct Particle {
@@ -10,21 +20,15 @@ struct Particle {
     initial_color: Color,
 }
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    commands.spawn(Camera2dBundle::default());
-
+fn create_particle_assets(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
     // Create a basic circle mesh for particles
     let mesh = meshes.add(Circle::new(5.));
 
     // Store the mesh and colors as resources for easy access
     commands.insert_resource(ParticleMesh(mesh));
     commands.insert_resource(ParticleColors {
-        blue: Color::rgb(0.0, 0.0, 1.0),
-        orange: Color::rgb(1.0, 0.5, 0.0),
+        blue: Color::srgb(0.0, 0.0, 1.0),
+        orange: Color::srgb(1.0, 0.5, 0.0),
     });
 }
 
@@ -116,11 +120,3 @@ struct ParticleColors {
     blue: Color,
     orange: Color,
 }
-
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_systems(Startup, setup)
-        .add_systems(Update, (spawn_particles, update_particles))
-        .run();
-}
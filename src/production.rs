@@ -0,0 +1,335 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::Pickable;
+
+use crate::building::{Building, ConstructionSite, PlacementSet};
+use crate::command::{Action, CommandSet, PlayerCommand, TickCommands};
+use crate::defs::{BuildingDefs, UnitDefs};
+use crate::economy::Stockpiles;
+use crate::formation::FormationKind;
+use crate::navigation::{Footprint, NavGrid};
+use crate::orders::{Destination, IssueOrder, Order};
+use crate::selection::Selected;
+use crate::sim::SimSet;
+use crate::steering::SteeringSet;
+use crate::team::{PlayerId, Team};
+use crate::unit::spawn_unit;
+
+/// Most units a building can have queued, including the one in training.
+pub const MAX_QUEUE: usize = 5;
+// Trained units appear this far out from the edge of the building
+const EXIT_DISTANCE: f32 = 0.6;
+// Keys that queue the first, second and third unit a building trains
+const TRAIN_KEYS: [KeyCode; 3] = [KeyCode::KeyQ, KeyCode::KeyW, KeyCode::KeyE];
+
+pub struct ProductionPlugin;
+
+impl Plugin for ProductionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ProductionQueue>()
+            .register_type::<RallyPoint>()
+            .add_event::<UnitTrained>()
+            .add_systems(Startup, spawn_production_panel)
+            .add_systems(
+                Update,
+                (
+                    production_hotkeys,
+                    update_production_panel,
+                    draw_rally_points,
+                ),
+            )
+            // Buildings are paid for before training when both are asked for on the same tick
+            .add_systems(
+                FixedUpdate,
+                training_commands
+                    .after(PlacementSet)
+                    .in_set(CommandSet::Apply),
+            )
+            // Trained units get their first order once they exist, next tick
+            .add_systems(
+                FixedUpdate,
+                train_units.after(SteeringSet).in_set(SimSet::Update),
+            );
+    }
+}
+
+/// Sent when a building finishes training a unit, as it comes out.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct UnitTrained {
+    pub entity: Entity,
+    pub team: Team,
+}
+
+/// Ids of the units a building is training, first in line first. Each is paid for when queued.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct ProductionQueue {
+    pub queue: VecDeque<String>,
+    /// Seconds spent on the first unit in the queue.
+    pub progress: f32,
+}
+
+/// Where a building sends the units it trains. Set by right-clicking with the building selected.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct RallyPoint(pub Option<Vec3>);
+
+#[derive(Component)]
+struct ProductionPanel;
+
+fn spawn_production_panel(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(35.0),
+                bottom: Val::Px(12.0),
+                ..default()
+            },
+            background_color: Color::srgba(0.0, 0.0, 0.0, 0.5).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Pickable::IGNORE,
+        ProductionPanel,
+    ));
+}
+
+// Q, W and E queue units at the selected buildings, Backspace cancels the last one queued
+#[allow(clippy::type_complexity)]
+fn production_hotkeys(
+    key_input: Res<ButtonInput<KeyCode>>,
+    player: Res<PlayerId>,
+    buildings: Query<(Entity, &Team), (With<ProductionQueue>, With<Selected>)>,
+    mut commands: EventWriter<PlayerCommand>,
+) {
+    if key_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let buildings: Vec<Entity> = buildings
+        .iter()
+        .filter(|(_, team)| **team == player.0)
+        .map(|(entity, _)| entity)
+        .collect();
+    if buildings.is_empty() {
+        return;
+    }
+
+    let action = if let Some(slot) = TRAIN_KEYS.iter().position(|k| key_input.just_pressed(*k)) {
+        Action::Train { buildings, slot }
+    } else if key_input.just_pressed(KeyCode::Backspace) {
+        Action::CancelTraining { buildings }
+    } else {
+        return;
+    };
+    commands.send(PlayerCommand {
+        team: player.0,
+        action,
+    });
+}
+
+// Queue and cancel units as players asked, paying for them up front
+fn training_commands(
+    commands: Res<TickCommands>,
+    mut stockpiles: ResMut<Stockpiles>,
+    unit_defs: UnitDefs,
+    building_defs: BuildingDefs,
+    mut buildings: Query<(Entity, &Building, &Team, &mut ProductionQueue)>,
+) {
+    for command in &commands.0 {
+        let stockpile = stockpiles.get_mut(command.team);
+        match &command.action {
+            Action::Train {
+                buildings: from,
+                slot,
+            } => {
+                // Spread the work over every building that can take it
+                let least_busy = buildings
+                    .iter_many(from)
+                    .filter(|(_, building, team, production)| {
+                        **team == command.team
+                            && production.queue.len() < MAX_QUEUE
+                            && building_defs
+                                .get(&building.0)
+                                .is_some_and(|def| def.trains.len() > *slot)
+                    })
+                    .min_by_key(|(.., production)| production.queue.len())
+                    .map(|(entity, building, ..)| (entity, building.0.clone()));
+                let Some((entity, building)) = least_busy else {
+                    continue;
+                };
+                let Some(id) = building_defs
+                    .get(&building)
+                    .map(|def| def.trains[*slot].clone())
+                else {
+                    continue;
+                };
+                let Some(unit) = unit_defs.get(&id) else {
+                    continue;
+                };
+                if stockpile.can_afford(&unit.cost) {
+                    stockpile.spend(&unit.cost);
+                    let (.., mut production) = buildings.get_mut(entity).unwrap();
+                    production.queue.push_back(id);
+                }
+            }
+            Action::CancelTraining { buildings: from } => {
+                let busiest = buildings
+                    .iter_many(from)
+                    .filter(|(_, _, team, production)| {
+                        **team == command.team && !production.queue.is_empty()
+                    })
+                    .max_by_key(|(.., production)| production.queue.len())
+                    .map(|(entity, ..)| entity);
+                let Some((.., mut production)) = busiest.and_then(|e| buildings.get_mut(e).ok())
+                else {
+                    continue;
+                };
+                if let Some(unit) = production
+                    .queue
+                    .pop_back()
+                    .and_then(|id| unit_defs.get(&id))
+                {
+                    stockpile.refund(&unit.cost);
+                    if production.queue.is_empty() {
+                        production.progress = 0.0;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Work on the first unit in each queue and send it out to the rally point once it's done
+fn train_units(
+    time: Res<Time>,
+    nav_grid: Res<NavGrid>,
+    unit_defs: UnitDefs,
+    mut buildings: Query<
+        (
+            &Transform,
+            &Team,
+            &Footprint,
+            &RallyPoint,
+            &mut ProductionQueue,
+        ),
+        Without<ConstructionSite>,
+    >,
+    mut orders: EventWriter<IssueOrder>,
+    mut trained: EventWriter<UnitTrained>,
+    mut commands: Commands,
+) {
+    for (transform, team, footprint, rally_point, mut production) in buildings.iter_mut() {
+        let Some(build_time) = production
+            .queue
+            .front()
+            .and_then(|id| unit_defs.get(id))
+            .map(|unit| unit.build_time)
+        else {
+            continue;
+        };
+        production.progress += time.delta_seconds();
+        if production.progress < build_time {
+            continue;
+        }
+        let Some(id) = production.queue.pop_front() else {
+            continue;
+        };
+        production.progress = 0.0;
+
+        // Come out on the side facing the rally point, or the front if there isn't one
+        let center = transform.translation;
+        let toward = rally_point
+            .0
+            .unwrap_or(center + Vec3::Z * footprint.0.height());
+        let exit_area = footprint.0.inflate(EXIT_DISTANCE);
+        let exit = toward.xz().clamp(exit_area.min, exit_area.max);
+        let height = nav_grid.height_at(exit).unwrap_or(center.y);
+        let unit = spawn_unit(&mut commands, &id, *team, Vec3::new(exit.x, height, exit.y));
+        trained.send(UnitTrained {
+            entity: unit,
+            team: *team,
+        });
+
+        if let Some(target) = rally_point.0 {
+            orders.send(IssueOrder {
+                units: vec![unit],
+                order: Order::Move(Destination {
+                    target,
+                    facing: None,
+                    // One unit at a time, so any formation will do
+                    formation: FormationKind::default(),
+                }),
+                queue: false,
+            });
+        }
+    }
+}
+
+fn update_production_panel(
+    unit_defs: UnitDefs,
+    building_defs: BuildingDefs,
+    buildings: Query<(&Building, &ProductionQueue), With<Selected>>,
+    mut panel_q: Query<(&mut Text, &mut Visibility), With<ProductionPanel>>,
+) {
+    let Ok((mut text, mut visibility)) = panel_q.get_single_mut() else {
+        return;
+    };
+    // Only one building's queue fits, show whichever is busiest
+    let Some((building, production)) = buildings
+        .iter()
+        .max_by_key(|(_, production)| production.queue.len())
+    else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let Some(def) = building_defs.get(&building.0) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    let mut lines = vec![def.name.clone()];
+    if let Some(unit) = production.queue.front().and_then(|id| unit_defs.get(id)) {
+        let percent = production.progress / unit.build_time * 100.0;
+        lines.push(format!("Training {} {percent:.0}%", unit.name));
+    }
+    if production.queue.len() > 1 {
+        let waiting: Vec<&str> = production
+            .queue
+            .iter()
+            .skip(1)
+            .filter_map(|id| unit_defs.get(id))
+            .map(|unit| unit.name.as_str())
+            .collect();
+        lines.push(format!("Queued: {}", waiting.join(", ")));
+    }
+    let trains = def.trains.iter().filter_map(|id| unit_defs.get(id));
+    for (key, unit) in ["Q", "W", "E"].iter().zip(trains) {
+        lines.push(format!(
+            "{key}: {} ({} wood, {} stone, {}s)",
+            unit.name, unit.cost.wood, unit.cost.stone, unit.build_time
+        ));
+    }
+    if !production.queue.is_empty() {
+        lines.push("Backspace: cancel the last unit queued".to_string());
+    }
+
+    *text = Text::from_section(lines.join("\n"), TextStyle::default());
+    *visibility = Visibility::Inherited;
+}
+
+fn draw_rally_points(
+    buildings: Query<(&Transform, &RallyPoint), With<Selected>>,
+    mut gizmos: Gizmos,
+) {
+    for (transform, rally_point) in buildings.iter() {
+        let Some(target) = rally_point.0 else {
+            continue;
+        };
+        let lift = Vec3::Y * 0.1;
+        gizmos.line(transform.translation + lift, target + lift, Color::WHITE);
+        gizmos.circle(target + lift, Dir3::Y, 0.4, Color::srgb(0.9, 0.9, 0.2));
+    }
+}
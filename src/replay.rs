@@ -0,0 +1,502 @@
+use std::hash::Hasher;
+use std::path::{Path as FilePath, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::ecs::entity::MapEntities;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::Pickable;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::cli;
+use crate::combat::Health;
+use crate::command::{CommandSet, EntitySwap, PlayerCommand, TickCommands};
+use crate::economy::{IncomeMultipliers, Stockpiles};
+use crate::save::load_game_from_str;
+use crate::scenario::{CurrentScenario, Outcome, ScenarioPlugin};
+use crate::sim::{SimClock, SimId, SimPlugin, SimSet, SimSettings};
+use crate::team::Team;
+
+/// Bump whenever what's recorded changes shape.
+pub const REPLAY_VERSION: u32 = 3;
+// Replays older than this can't be read any more. Version 2 is version 3 without `start`
+const OLDEST_REPLAY_VERSION: u32 = 2;
+/// Where the match being played is recorded to.
+pub const LAST_REPLAY_PATH: &str = "replays/last.replay.ron";
+/// The simulation state is hashed every this many ticks.
+pub const HASH_INTERVAL: u64 = 30;
+// Slowest and fastest a replay can be played back, relative to real time
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 8.0;
+
+/// Records every command given in a match, or plays a recorded match back instead of taking
+/// commands.
+#[derive(Clone)]
+pub enum ReplayPlugin {
+    /// Write the match to this file, whenever it's decided and when the game closes.
+    Record(PathBuf),
+    /// Feed a recorded match back into the simulation.
+    Play(Replay),
+}
+
+impl ReplayPlugin {
+    /// Plays back the replay given with `--replay <path>` on the command line, or records to
+    /// `LAST_REPLAY_PATH`.
+    pub fn from_args() -> Result<Self, ReplayError> {
+        match cli::arg("replay") {
+            Some(path) => Ok(Self::Play(read_replay(FilePath::new(&path))?)),
+            None => Ok(Self::Record(PathBuf::from(LAST_REPLAY_PATH))),
+        }
+    }
+
+    /// The simulation settings to run with: the replay's, or those on the command line.
+    pub fn sim_plugin(&self) -> SimPlugin {
+        match self {
+            Self::Record(_) => SimPlugin::from_args(),
+            Self::Play(replay) => SimPlugin {
+                tick_rate: replay.tick_rate,
+                seed: replay.seed,
+            },
+        }
+    }
+
+    /// The scenario to play: the replay's, or the one on the command line.
+    pub fn scenario_plugin(&self) -> ScenarioPlugin {
+        match self {
+            Self::Record(_) => ScenarioPlugin::from_args(),
+            Self::Play(replay) => ScenarioPlugin {
+                path: replay.scenario.clone(),
+            },
+        }
+    }
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match self {
+            Self::Record(path) => {
+                app.insert_resource(Recording {
+                    path: path.clone(),
+                    replay: None,
+                })
+                .add_systems(Startup, start_recording)
+                .add_systems(
+                    FixedUpdate,
+                    (
+                        record_commands
+                            .after(CommandSet::Collect)
+                            .before(CommandSet::Apply),
+                        record_hash.in_set(SimSet::Last),
+                    ),
+                )
+                .add_systems(Last, save_recording);
+            }
+            Self::Play(replay) => {
+                app.insert_resource(IncomeMultipliers(
+                    replay.income_multipliers.iter().copied().collect(),
+                ))
+                .insert_resource(Playback {
+                    replay: replay.clone(),
+                    next_command: 0,
+                    next_hash: 0,
+                    desynced_at: None,
+                })
+                .add_systems(Startup, spawn_playback_text)
+                .add_systems(PreUpdate, load_start)
+                .add_systems(
+                    FixedUpdate,
+                    (
+                        play_commands.in_set(CommandSet::Collect),
+                        check_hash.in_set(SimSet::Last),
+                    ),
+                )
+                .add_systems(FixedPostUpdate, stop_at_end)
+                .add_systems(Update, (playback_controls, update_playback_text).chain());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("could not access the replay: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write the replay: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("could not read the replay: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error(
+        "replay version {0} isn't supported, expected {OLDEST_REPLAY_VERSION} to {REPLAY_VERSION}"
+    )]
+    Version(u32),
+}
+
+/// A match as played: where it started and every command given in it. Played out again with
+/// the same settings, it ends up in exactly the same state.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    /// Path of the scenario under assets/.
+    pub scenario: String,
+    pub tick_rate: f64,
+    pub seed: u64,
+    /// `IncomeMultipliers` of the teams that have one. Defaulted so older replays get as far as
+    /// the version check.
+    #[serde(default)]
+    pub income_multipliers: Vec<(Team, f32)>,
+    /// The save file the match carried on from, if it was loaded partway through. Playback
+    /// loads it before the first tick.
+    #[serde(default)]
+    pub start: Option<String>,
+    /// Ticks the match ran for.
+    pub length: u64,
+    pub commands: Vec<RecordedCommand>,
+    /// Hashes of the simulation state along the way, to tell when playback goes differently.
+    pub hashes: Vec<StateHash>,
+}
+
+/// A command and the tick it was carried out on. Entities in it stand for the `SimId` of the
+/// same index.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    pub tick: u64,
+    pub command: PlayerCommand,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct StateHash {
+    pub tick: u64,
+    pub hash: u64,
+}
+
+/// The match being recorded.
+#[derive(Resource)]
+pub struct Recording {
+    path: PathBuf,
+    // Starts once the scenario is known
+    replay: Option<Replay>,
+}
+
+impl Recording {
+    /// Record the match from a save that's just been loaded, `save` being the text of the save
+    /// file. What came before can't be played back to reach it, so it's dropped.
+    pub(crate) fn restart_from(&mut self, save: &str) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.start = Some(save.to_string());
+            replay.commands.clear();
+            replay.hashes.clear();
+        }
+    }
+}
+
+/// A replay being played back.
+#[derive(Resource)]
+pub struct Playback {
+    replay: Replay,
+    next_command: usize,
+    next_hash: usize,
+    desynced_at: Option<u64>,
+}
+
+impl Playback {
+    /// The first tick whose state hash didn't match the recording, if any has.
+    pub fn desynced_at(&self) -> Option<u64> {
+        self.desynced_at
+    }
+}
+
+#[derive(Component)]
+struct PlaybackText;
+
+pub fn read_replay(path: &FilePath) -> Result<Replay, ReplayError> {
+    let text = std::fs::read_to_string(path)?;
+    let replay: Replay = ron::de::from_str(&text)?;
+    if !(OLDEST_REPLAY_VERSION..=REPLAY_VERSION).contains(&replay.version) {
+        return Err(ReplayError::Version(replay.version));
+    }
+    Ok(replay)
+}
+
+pub fn write_replay(path: &FilePath, replay: &Replay) -> Result<(), ReplayError> {
+    let text = ron::ser::to_string_pretty(replay, ron::ser::PrettyConfig::default())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+// FNV-1a, which unlike the standard library's hasher is the same in every build
+struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// The parts of the simulation compared to tell whether two runs of a match went the same way.
+#[derive(SystemParam)]
+pub struct HashedState<'w, 's> {
+    entities: Query<'w, 's, (&'static SimId, &'static Transform, Option<&'static Health>)>,
+    stockpiles: Res<'w, Stockpiles>,
+}
+
+impl HashedState<'_, '_> {
+    /// Where everything stands, its health and what each team has banked, boiled down to a
+    /// number.
+    pub fn hash(&self) -> u64 {
+        hash_state(&self.entities, &self.stockpiles)
+    }
+}
+
+fn hash_state(
+    entities: &Query<(&SimId, &Transform, Option<&Health>)>,
+    stockpiles: &Stockpiles,
+) -> u64 {
+    let mut hasher = StateHasher::default();
+    let mut entities: Vec<_> = entities.iter().collect();
+    entities.sort_by_key(|(id, ..)| **id);
+    for (id, transform, health) in entities {
+        hasher.write_u32(id.0);
+        for value in transform.translation.to_array() {
+            hasher.write_u32(value.to_bits());
+        }
+        if let Some(health) = health {
+            hasher.write_u32(health.current.to_bits());
+        }
+    }
+    let mut stockpiles: Vec<_> = stockpiles.0.iter().collect();
+    stockpiles.sort_by_key(|(team, _)| team.0);
+    for (team, stockpile) in stockpiles {
+        hasher.write_u8(team.0);
+        hasher.write_u32(stockpile.wood);
+        hasher.write_u32(stockpile.stone);
+    }
+    hasher.finish()
+}
+
+fn start_recording(
+    settings: Res<SimSettings>,
+    current: Res<CurrentScenario>,
+    multipliers: Res<IncomeMultipliers>,
+    mut recording: ResMut<Recording>,
+) {
+    let mut income_multipliers: Vec<_> = multipliers
+        .0
+        .iter()
+        .map(|(team, multiplier)| (*team, *multiplier))
+        .collect();
+    income_multipliers.sort_by_key(|(team, _)| team.0);
+    recording.replay = Some(Replay {
+        version: REPLAY_VERSION,
+        scenario: current.path.clone(),
+        tick_rate: settings.tick_rate,
+        seed: settings.seed,
+        income_multipliers,
+        start: None,
+        length: 0,
+        commands: Vec::new(),
+        hashes: Vec::new(),
+    });
+}
+
+fn record_commands(
+    commands: Res<TickCommands>,
+    clock: Res<SimClock>,
+    ids: Query<(Entity, &SimId)>,
+    mut recording: ResMut<Recording>,
+) {
+    let Some(replay) = recording.replay.as_mut() else {
+        return;
+    };
+    if commands.0.is_empty() {
+        return;
+    }
+    let mut to_ids = EntitySwap::to_stand_ins(&ids);
+    for command in &commands.0 {
+        let mut command = command.clone();
+        command.map_entities(&mut to_ids);
+        replay.commands.push(RecordedCommand {
+            tick: clock.tick,
+            command,
+        });
+    }
+}
+
+fn record_hash(clock: Res<SimClock>, state: HashedState, mut recording: ResMut<Recording>) {
+    let Some(replay) = recording.replay.as_mut() else {
+        return;
+    };
+    if clock.tick.is_multiple_of(HASH_INTERVAL) {
+        replay.hashes.push(StateHash {
+            tick: clock.tick,
+            hash: state.hash(),
+        });
+    }
+}
+
+// Write the match out once it's decided, and again if play carries on until the game closes
+fn save_recording(
+    outcome: Res<Outcome>,
+    mut exits: EventReader<AppExit>,
+    clock: Res<SimClock>,
+    mut recording: ResMut<Recording>,
+) {
+    let decided = outcome.is_changed() && *outcome != Outcome::Playing;
+    if !decided && exits.read().count() == 0 {
+        return;
+    }
+    let Recording { path, replay } = &mut *recording;
+    let Some(replay) = replay.as_mut() else {
+        return;
+    };
+    replay.length = clock.tick;
+    match write_replay(path, replay) {
+        Ok(()) => info!("Recorded the match to {}", path.display()),
+        Err(error) => error!("Couldn't record the match: {error}"),
+    }
+}
+
+// Replace whatever the player asked for with what was recorded for this tick
+fn play_commands(
+    clock: Res<SimClock>,
+    ids: Query<(Entity, &SimId)>,
+    mut commands: ResMut<TickCommands>,
+    mut playback: ResMut<Playback>,
+) {
+    commands.0.clear();
+    let Playback {
+        replay,
+        next_command,
+        ..
+    } = &mut *playback;
+    let due = replay.commands[*next_command..]
+        .iter()
+        .take_while(|recorded| recorded.tick <= clock.tick);
+    let mut from_ids = None;
+    for recorded in due {
+        *next_command += 1;
+        if recorded.tick < clock.tick {
+            continue;
+        }
+        let from_ids = from_ids.get_or_insert_with(|| EntitySwap::from_stand_ins(&ids));
+        let mut command = recorded.command.clone();
+        command.map_entities(from_ids);
+        commands.0.push(command);
+    }
+}
+
+fn check_hash(clock: Res<SimClock>, state: HashedState, mut playback: ResMut<Playback>) {
+    let Some(recorded) = playback.replay.hashes.get(playback.next_hash).copied() else {
+        return;
+    };
+    if recorded.tick > clock.tick {
+        return;
+    }
+    playback.next_hash += 1;
+    if recorded.tick < clock.tick || playback.desynced_at.is_some() {
+        return;
+    }
+    if state.hash() != recorded.hash {
+        error!(
+            "The replay went differently from the recorded match at tick {}",
+            clock.tick
+        );
+        playback.desynced_at = Some(clock.tick);
+    }
+}
+
+// A replay of a match that was loaded from a save starts from it, once the scenario it's of is
+// laid out and before the first tick
+fn load_start(world: &mut World) {
+    if !world.resource::<CurrentScenario>().spawned() {
+        return;
+    }
+    let Some(start) = world.resource_mut::<Playback>().replay.start.take() else {
+        return;
+    };
+    if let Err(error) = load_game_from_str(world, &start) {
+        error!("Couldn't load the save the replay starts from: {error}");
+    }
+}
+
+// Nothing more was recorded past the end, so there's nothing more to play
+fn stop_at_end(mut clock: ResMut<SimClock>, playback: Res<Playback>) {
+    if clock.running && clock.tick >= playback.replay.length {
+        clock.running = false;
+    }
+}
+
+fn spawn_playback_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(40.0),
+                top: Val::Px(12.0),
+                ..default()
+            },
+            ..default()
+        },
+        Pickable::IGNORE,
+        PlaybackText,
+    ));
+}
+
+// Space pauses and resumes, + and - speed playback up and slow it down
+fn playback_controls(key_input: Res<ButtonInput<KeyCode>>, mut time: ResMut<Time<Virtual>>) {
+    if key_input.just_pressed(KeyCode::Space) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+    let speed = time.relative_speed();
+    if key_input.just_pressed(KeyCode::Equal) {
+        time.set_relative_speed((speed * 2.0).min(MAX_SPEED));
+    } else if key_input.just_pressed(KeyCode::Minus) {
+        time.set_relative_speed((speed / 2.0).max(MIN_SPEED));
+    }
+}
+
+fn update_playback_text(
+    clock: Res<SimClock>,
+    time: Res<Time<Virtual>>,
+    playback: Res<Playback>,
+    mut text_q: Query<&mut Text, With<PlaybackText>>,
+) {
+    let mut value = format!(
+        "Replay: tick {} of {}, x{}",
+        clock.tick,
+        playback.replay.length,
+        time.relative_speed()
+    );
+    if clock.tick >= playback.replay.length {
+        value.push_str(" (finished)");
+    } else if time.is_paused() {
+        value.push_str(" (paused)");
+    }
+    value.push_str("\nSpace to pause, + and - to change speed");
+    if let Some(tick) = playback.desynced_at {
+        value.push_str(&format!("\nOut of sync since tick {tick}"));
+    }
+    for mut text in text_q.iter_mut() {
+        *text = Text::from_section(value.clone(), TextStyle::default());
+    }
+}
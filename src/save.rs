@@ -0,0 +1,694 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path as FilePath, PathBuf};
+
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet};
+use bevy::input::common_conditions::input_just_pressed;
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistry;
+use bevy::scene::serde::{SceneDeserializer, SceneSerializer};
+use bevy_rts_camera::RtsCamera;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::de::{DeserializeSeed, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+use crate::ai::{restore_squads, save_squads, SavedSquads};
+use crate::building::{Builder, Building, ConstructionSite};
+use crate::combat::{Armor, AttackTarget, Health, Projectile, Weapon};
+use crate::defs::AwaitingDef;
+use crate::economy::{DropOff, Gatherer, IncomeMultipliers, ResourceNode, Stockpile, Stockpiles};
+use crate::flow_field::FlowGoal;
+use crate::fog::{FogOfWar, Vision};
+use crate::lockstep::Lockstep;
+use crate::movement::Move;
+use crate::navigation::{Footprint, Path};
+use crate::orders::{ActiveOrder, LastOrderGroup, OrderQueue};
+use crate::production::{ProductionQueue, RallyPoint};
+use crate::replay::{Playback, Recording};
+use crate::scenario::{CurrentScenario, Outcome};
+use crate::selection::Selected;
+use crate::sim::{Interpolated, NextSimId, SimClock, SimId, SimRng, SimSettings};
+use crate::steering::{OrderGroup, Steering};
+use crate::team::Team;
+use crate::unit::Unit;
+
+/// Bump whenever what's saved changes shape, and migrate older saves in `read_save`.
+pub const SAVE_VERSION: u32 = 3;
+// Saves older than this can't be read any more
+const OLDEST_SAVE_VERSION: u32 = 1;
+/// Where F5 saves the match and F9 loads it from, unless `Quicksave` says otherwise.
+pub const QUICKSAVE_PATH: &str = "saves/quicksave.ron";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Quicksave>().add_systems(
+            Update,
+            (
+                // Only matches being played are saved, not replays of them
+                quicksave.run_if(
+                    input_just_pressed(KeyCode::F5).and_then(not(resource_exists::<Playback>)),
+                ),
+                // Loading only changes this world, which would leave other players or the replay
+                // behind. A recording starts over from the save instead
+                quickload.run_if(
+                    input_just_pressed(KeyCode::F9)
+                        .and_then(not(resource_exists::<Lockstep>))
+                        .and_then(not(resource_exists::<Playback>)),
+                ),
+            ),
+        );
+    }
+}
+
+/// The file F5 and F9 save to and load from.
+#[derive(Resource, Clone, Debug)]
+pub struct Quicksave(pub PathBuf);
+
+impl Default for Quicksave {
+    fn default() -> Self {
+        Self(PathBuf::from(QUICKSAVE_PATH))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("could not access the save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write the save: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("could not read the save: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("the save is for {saved}, not {playing}")]
+    WrongScenario { saved: String, playing: String },
+}
+
+// Where the camera was looking
+#[derive(Serialize, Deserialize)]
+struct SavedCamera {
+    focus: [f32; 3],
+    zoom: f32,
+}
+
+// Where the `SimRng` had got to
+#[derive(Serialize, Deserialize)]
+struct SavedRng {
+    seed: [u8; 32],
+    stream: u64,
+    // High and low halves, as RON can't hold 128 bit numbers
+    word_pos: [u64; 2],
+}
+
+impl SavedRng {
+    fn new(rng: &ChaCha8Rng) -> Self {
+        let word_pos = rng.get_word_pos();
+        Self {
+            seed: rng.get_seed(),
+            stream: rng.get_stream(),
+            word_pos: [(word_pos >> 64) as u64, word_pos as u64],
+        }
+    }
+
+    fn restore(&self) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::from_seed(self.seed);
+        rng.set_stream(self.stream);
+        rng.set_word_pos(u128::from(self.word_pos[0]) << 64 | u128::from(self.word_pos[1]));
+        rng
+    }
+}
+
+// Everything in a save apart from the entities, which go through reflection
+struct SaveFile {
+    scenario: String,
+    elapsed: f32,
+    tick: u64,
+    rng: SavedRng,
+    // How far round the moving unit is, if there is one
+    circling: Option<f32>,
+    stockpiles: Vec<(u8, Stockpile)>,
+    income_multipliers: Vec<(Team, f32)>,
+    outcome: Outcome,
+    // Runs of unexplored and explored fog cells, as `FogOfWar::explored` has them
+    explored: Vec<(Team, Vec<u32>)>,
+    squads: Vec<SavedSquads>,
+    last_group: u32,
+    // Entities saved ones refer to that aren't saved themselves, like the moving unit, and their
+    // `SimId`
+    others: Vec<(Entity, u32)>,
+    camera: Option<SavedCamera>,
+    world: DynamicScene,
+}
+
+#[derive(Serialize)]
+struct SaveFileSerializer<'a> {
+    version: u32,
+    scenario: &'a str,
+    elapsed: f32,
+    tick: u64,
+    rng: &'a SavedRng,
+    circling: Option<f32>,
+    stockpiles: &'a [(u8, Stockpile)],
+    income_multipliers: &'a [(Team, f32)],
+    outcome: Outcome,
+    explored: &'a [(Team, Vec<u32>)],
+    squads: &'a [SavedSquads],
+    last_group: u32,
+    others: &'a [(Entity, u32)],
+    camera: &'a Option<SavedCamera>,
+    world: SceneSerializer<'a>,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum SaveField {
+    Version,
+    Scenario,
+    Elapsed,
+    Tick,
+    Rng,
+    Circling,
+    Stockpiles,
+    IncomeMultipliers,
+    Outcome,
+    Explored,
+    Squads,
+    LastGroup,
+    Others,
+    Camera,
+    World,
+}
+
+const SAVE_FIELDS: &[&str] = &[
+    "version",
+    "scenario",
+    "elapsed",
+    "tick",
+    "rng",
+    "circling",
+    "stockpiles",
+    "income_multipliers",
+    "outcome",
+    "explored",
+    "squads",
+    "last_group",
+    "others",
+    "camera",
+    "world",
+];
+
+// The entities need the type registry to be read back, so the file is read by hand. What the
+// simulation was set up with fills in for what older saves are missing
+struct SaveFileDeserializer<'a> {
+    registry: &'a TypeRegistry,
+    settings: SimSettings,
+    income_multipliers: Vec<(Team, f32)>,
+}
+
+impl<'de> DeserializeSeed<'de> for SaveFileDeserializer<'_> {
+    type Value = SaveFile;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<SaveFile, D::Error> {
+        deserializer.deserialize_struct("SaveFile", SAVE_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for SaveFileDeserializer<'_> {
+    type Value = SaveFile;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a saved match")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<SaveFile, A::Error> {
+        use serde::de::Error;
+
+        let mut version = None;
+        let mut scenario = None;
+        let mut elapsed = None;
+        let mut tick = None;
+        let mut rng = None;
+        let mut circling = None;
+        let mut stockpiles = None;
+        let mut income_multipliers = None;
+        let mut outcome = None;
+        let mut explored = None;
+        let mut squads = None;
+        let mut last_group = None;
+        let mut others = None;
+        let mut camera = None;
+        let mut world = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                SaveField::Version => {
+                    let value: u32 = map.next_value()?;
+                    if !(OLDEST_SAVE_VERSION..=SAVE_VERSION).contains(&value) {
+                        return Err(A::Error::custom(format!(
+                            "save version {value} isn't supported, expected \
+                             {OLDEST_SAVE_VERSION} to {SAVE_VERSION}"
+                        )));
+                    }
+                    version = Some(value);
+                }
+                SaveField::Scenario => scenario = Some(map.next_value()?),
+                SaveField::Elapsed => elapsed = Some(map.next_value()?),
+                SaveField::Tick => tick = Some(map.next_value()?),
+                SaveField::Rng => rng = Some(map.next_value()?),
+                SaveField::Circling => circling = Some(map.next_value()?),
+                SaveField::Stockpiles => stockpiles = Some(map.next_value()?),
+                SaveField::IncomeMultipliers => income_multipliers = Some(map.next_value()?),
+                SaveField::Outcome => outcome = Some(map.next_value()?),
+                SaveField::Explored => explored = Some(map.next_value()?),
+                SaveField::Squads => squads = Some(map.next_value()?),
+                SaveField::LastGroup => last_group = Some(map.next_value()?),
+                SaveField::Others => others = Some(map.next_value()?),
+                SaveField::Camera => camera = Some(map.next_value()?),
+                SaveField::World => {
+                    if version.is_none() {
+                        return Err(A::Error::custom("the version has to come first"));
+                    }
+                    world = Some(map.next_value_seed(SceneDeserializer {
+                        type_registry: self.registry,
+                    })?);
+                }
+            }
+        }
+        let elapsed: f32 = elapsed.ok_or_else(|| A::Error::missing_field("elapsed"))?;
+        // Version 1 didn't save the tick, the RNG or the moving unit. The tick is worked out from
+        // how long the match had run, the RNG starts over from the seed and the unit stays put
+        if version == Some(1) {
+            let ticks = f64::from(elapsed) * self.settings.tick_rate;
+            tick = tick.or(Some(ticks.round() as u64));
+            rng = rng.or_else(|| {
+                Some(SavedRng::new(&ChaCha8Rng::seed_from_u64(
+                    self.settings.seed,
+                )))
+            });
+            circling = circling.or(Some(None));
+        }
+        // Nor did version 2 save the rest of the match. The multipliers are those the match was
+        // set up with, the match is still going, nothing has been explored and the AI forms new
+        // squads. Group ids carry on from the restored units', and orders aimed at anything not
+        // in the save are dropped
+        if version < Some(3) {
+            income_multipliers = income_multipliers.or(Some(self.income_multipliers));
+            outcome = outcome.or(Some(Outcome::Playing));
+            explored = explored.or_else(|| Some(Vec::new()));
+            squads = squads.or_else(|| Some(Vec::new()));
+            last_group = last_group.or(Some(0));
+            others = others.or_else(|| Some(Vec::new()));
+        }
+        Ok(SaveFile {
+            scenario: scenario.ok_or_else(|| A::Error::missing_field("scenario"))?,
+            elapsed,
+            tick: tick.ok_or_else(|| A::Error::missing_field("tick"))?,
+            rng: rng.ok_or_else(|| A::Error::missing_field("rng"))?,
+            circling: circling.ok_or_else(|| A::Error::missing_field("circling"))?,
+            stockpiles: stockpiles.ok_or_else(|| A::Error::missing_field("stockpiles"))?,
+            income_multipliers: income_multipliers
+                .ok_or_else(|| A::Error::missing_field("income_multipliers"))?,
+            outcome: outcome.ok_or_else(|| A::Error::missing_field("outcome"))?,
+            explored: explored.ok_or_else(|| A::Error::missing_field("explored"))?,
+            squads: squads.ok_or_else(|| A::Error::missing_field("squads"))?,
+            last_group: last_group.ok_or_else(|| A::Error::missing_field("last_group"))?,
+            others: others.ok_or_else(|| A::Error::missing_field("others"))?,
+            camera: camera.ok_or_else(|| A::Error::missing_field("camera"))?,
+            world: world.ok_or_else(|| A::Error::missing_field("world"))?,
+        })
+    }
+}
+
+fn quicksave(world: &mut World) {
+    let path = world.resource::<Quicksave>().0.clone();
+    match save_game(world, &path) {
+        Ok(()) => info!("Saved to {}", path.display()),
+        Err(error) => error!("Couldn't save: {error}"),
+    }
+}
+
+fn quickload(world: &mut World) {
+    let path = world.resource::<Quicksave>().0.clone();
+    match load_game(world, &path) {
+        Ok(()) => info!("Loaded {}", path.display()),
+        Err(error) => error!("Couldn't load: {error}"),
+    }
+}
+
+// Entities that make up the state of a match, as opposed to terrain, UI and effects
+fn saved_entities(world: &mut World) -> Vec<Entity> {
+    world
+        .query_filtered::<Entity, Or<(With<Unit>, With<Building>, With<ResourceNode>)>>()
+        .iter(world)
+        .collect()
+}
+
+/// Write every unit, building and resource node in the match to `path`, along with the
+/// stockpiles, where the simulation is up to, what each team has explored, what the AI players
+/// are up to and the camera.
+pub fn save_game(world: &mut World, path: &FilePath) -> Result<(), SaveError> {
+    // Save where the simulation has things, not where they're drawn
+    for (mut transform, interpolated) in world
+        .query::<(&mut Transform, &Interpolated)>()
+        .iter_mut(world)
+    {
+        transform.translation = interpolated.current();
+    }
+    let entities = saved_entities(world);
+    let saved: EntityHashSet = entities.iter().copied().collect();
+    let mut others: Vec<(Entity, u32)> = world
+        .query::<(Entity, &SimId)>()
+        .iter(world)
+        .filter(|(entity, _)| !saved.contains(entity))
+        .map(|(entity, id)| (entity, id.0))
+        .collect();
+    others.sort_by_key(|(_, id)| *id);
+    let scene = DynamicSceneBuilder::from_world(world)
+        .deny_all()
+        .allow::<Transform>()
+        .allow::<Team>()
+        .allow::<Unit>()
+        .allow::<Building>()
+        .allow::<ConstructionSite>()
+        .allow::<Builder>()
+        .allow::<Health>()
+        .allow::<Armor>()
+        .allow::<Weapon>()
+        .allow::<AttackTarget>()
+        .allow::<Vision>()
+        .allow::<Steering>()
+        .allow::<ActiveOrder>()
+        .allow::<OrderQueue>()
+        .allow::<Path>()
+        .allow::<FlowGoal>()
+        .allow::<OrderGroup>()
+        .allow::<Selected>()
+        .allow::<Gatherer>()
+        .allow::<ResourceNode>()
+        .allow::<DropOff>()
+        .allow::<Footprint>()
+        .allow::<ProductionQueue>()
+        .allow::<RallyPoint>()
+        .allow::<SimId>()
+        .extract_entities(entities.into_iter())
+        .build();
+
+    let mut stockpiles: Vec<(u8, Stockpile)> = world
+        .resource::<Stockpiles>()
+        .0
+        .iter()
+        .map(|(team, stockpile)| (team.0, *stockpile))
+        .collect();
+    stockpiles.sort_by_key(|(team, _)| *team);
+    let mut income_multipliers: Vec<(Team, f32)> = world
+        .resource::<IncomeMultipliers>()
+        .0
+        .iter()
+        .map(|(team, multiplier)| (*team, *multiplier))
+        .collect();
+    income_multipliers.sort_by_key(|(team, _)| team.0);
+    let explored = world.resource::<FogOfWar>().explored();
+    let squads = save_squads(world);
+    let camera = world
+        .query::<&RtsCamera>()
+        .iter(world)
+        .next()
+        .map(|camera| SavedCamera {
+            focus: camera.target_focus.translation.to_array(),
+            zoom: camera.target_zoom,
+        });
+
+    let circling = world
+        .query::<&Move>()
+        .iter(world)
+        .next()
+        .map(|cube| cube.angle);
+    let rng = SavedRng::new(&world.resource::<SimRng>().0);
+
+    let current = world.resource::<CurrentScenario>();
+    let registry = world.resource::<AppTypeRegistry>().read();
+    let save = SaveFileSerializer {
+        version: SAVE_VERSION,
+        scenario: &current.path,
+        elapsed: current.elapsed,
+        tick: world.resource::<SimClock>().tick,
+        rng: &rng,
+        circling,
+        stockpiles: &stockpiles,
+        income_multipliers: &income_multipliers,
+        outcome: *world.resource::<Outcome>(),
+        explored: &explored,
+        squads: &squads,
+        last_group: world.resource::<LastOrderGroup>().0,
+        others: &others,
+        camera: &camera,
+        world: SceneSerializer::new(&scene, &registry),
+    };
+    let text = ron::ser::to_string_pretty(&save, ron::ser::PrettyConfig::default())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+fn read_save(world: &World, text: &str) -> Result<SaveFile, SaveError> {
+    let registry = world.resource::<AppTypeRegistry>().read();
+    let mut deserializer = ron::de::Deserializer::from_str(text)?;
+    let save = SaveFileDeserializer {
+        registry: &registry,
+        settings: *world.resource::<SimSettings>(),
+        income_multipliers: world
+            .get_resource::<IncomeMultipliers>()
+            .map(|multipliers| {
+                let multipliers = multipliers.0.iter();
+                multipliers
+                    .map(|(team, multiplier)| (*team, *multiplier))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+    .deserialize(&mut deserializer)
+    .map_err(|error| deserializer.span_error(error))?;
+    Ok(save)
+}
+
+/// Replace the match with the one saved at `path`. It has to be of the scenario being played,
+/// as the terrain isn't saved. A match being recorded is recorded from the save on.
+pub fn load_game(world: &mut World, path: &FilePath) -> Result<(), SaveError> {
+    let text = std::fs::read_to_string(path)?;
+    load_game_from_str(world, &text)
+}
+
+/// `load_game` from the text of a save file.
+pub fn load_game_from_str(world: &mut World, text: &str) -> Result<(), SaveError> {
+    let save = read_save(world, text)?;
+    let playing = &world.resource::<CurrentScenario>().path;
+    if save.scenario != *playing {
+        return Err(SaveError::WrongScenario {
+            saved: save.scenario,
+            playing: playing.clone(),
+        });
+    }
+
+    // Projectiles in flight are lost
+    let mut old = saved_entities(world);
+    old.extend(
+        world
+            .query_filtered::<Entity, With<Projectile>>()
+            .iter(world),
+    );
+    for entity in old {
+        if let Some(entity) = world.get_entity_mut(entity) {
+            entity.despawn_recursive();
+        }
+    }
+
+    // What's left with the same `SimId` stands in for anything saved entities refer to that
+    // wasn't saved itself
+    let live: HashMap<u32, Entity> = world
+        .query::<(Entity, &SimId)>()
+        .iter(world)
+        .map(|(entity, id)| (id.0, entity))
+        .collect();
+    let mut entity_map: EntityHashMap<Entity> = save
+        .others
+        .iter()
+        .filter_map(|(saved, id)| Some((*saved, *live.get(id)?)))
+        .collect();
+    if let Err(error) = save.world.write_to_world(world, &mut entity_map) {
+        // Everything in the save is registered, so this means the save is broken
+        error!("Couldn't restore the saved entities: {error}");
+    }
+    // Anything else they refer to has become a dead entity in `entity_map`
+    let restored: Vec<Entity> = save
+        .world
+        .entities
+        .iter()
+        .filter_map(|saved| entity_map.get(&saved.entity).copied())
+        .collect();
+    drop_orphaned_orders(world, &restored);
+    // New entities carry on from the saved ids
+    let last_id = world
+        .query::<&SimId>()
+        .iter(world)
+        .map(|id| id.0 + 1)
+        .max()
+        .unwrap_or(0);
+    world.resource_mut::<NextSimId>().0 = last_id;
+    for entity in &restored {
+        let mut entity = world.entity_mut(*entity);
+        entity.insert((GlobalTransform::default(), VisibilityBundle::default()));
+        if entity.contains::<Unit>() || entity.contains::<Building>() {
+            entity.insert(AwaitingDef { restored: true });
+        }
+    }
+
+    let mut stockpiles = world.resource_mut::<Stockpiles>();
+    stockpiles.0 = save
+        .stockpiles
+        .into_iter()
+        .map(|(team, stockpile)| (Team(team), stockpile))
+        .collect();
+    world.resource_mut::<CurrentScenario>().elapsed = save.elapsed;
+    world.resource_mut::<SimClock>().tick = save.tick;
+    world.resource_mut::<SimRng>().0 = save.rng.restore();
+    world.resource_mut::<IncomeMultipliers>().0 = save.income_multipliers.into_iter().collect();
+    world
+        .resource_mut::<FogOfWar>()
+        .restore_explored(save.explored);
+    restore_squads(world, save.squads, &entity_map);
+    let last_group = world
+        .query::<&OrderGroup>()
+        .iter(world)
+        .map(|group| group.id)
+        .fold(save.last_group, u32::max);
+    world.resource_mut::<LastOrderGroup>().0 = last_group;
+    if let Some(angle) = save.circling {
+        for mut cube in world.query::<&mut Move>().iter_mut(world) {
+            cube.angle = angle;
+        }
+    }
+    *world.resource_mut::<Outcome>() = save.outcome;
+    if let Some(mut recording) = world.get_resource_mut::<Recording>() {
+        recording.restart_from(text);
+    }
+    if let Some(saved) = save.camera {
+        for mut camera in world.query::<&mut RtsCamera>().iter_mut(world) {
+            camera.target_focus.translation = Vec3::from(saved.focus);
+            camera.target_zoom = saved.zoom;
+            camera.snap = true;
+        }
+    }
+    Ok(())
+}
+
+// Orders and attacks aimed at entities that are gone are given up on
+fn drop_orphaned_orders(world: &mut World, restored: &[Entity]) {
+    let gone = |world: &World, target: Option<Entity>| {
+        target.is_some_and(|target| world.get_entity(target).is_none())
+    };
+    for entity in restored.iter().copied() {
+        let active = world
+            .get::<ActiveOrder>(entity)
+            .map(|order| order.0.target());
+        if gone(world, active.flatten()) {
+            world
+                .entity_mut(entity)
+                .remove::<(ActiveOrder, Path, FlowGoal, OrderGroup)>();
+        }
+        let attacking = world.get::<AttackTarget>(entity).map(|target| target.0);
+        if gone(world, attacking) {
+            world.entity_mut(entity).remove::<AttackTarget>();
+        }
+        let Some(mut queue) = world.entity_mut(entity).take::<OrderQueue>() else {
+            continue;
+        };
+        queue.0.retain(|order| !gone(world, order.target()));
+        world.entity_mut(entity).insert(queue);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A version 1 quicksave, two seconds into the default scenario, with one unit left in it
+    const VERSION_1: &str = r#"(
+    version: 1,
+    scenario: "scenarios/default.scenario.ron",
+    elapsed: 1.9999986,
+    stockpiles: [(0, (wood: 40, stone: 5))],
+    camera: None,
+    world: (
+        resources: {},
+        entities: {
+            4294967313: (
+                components: {
+                    "bevy_transform::components::transform::Transform": (
+                        translation: (x: 2.0, y: 0.75, z: -1.0),
+                        rotation: (x: 0.0, y: 0.0, z: 0.0, w: 1.0),
+                        scale: (x: 1.0, y: 1.0, z: 1.0),
+                    ),
+                    "bevy_rts::sim::SimId": (3),
+                    "bevy_rts::team::Team": (0),
+                    "bevy_rts::unit::Unit": ("soldier"),
+                    "bevy_rts::combat::Health": (current: 60.0, max: 100.0),
+                },
+            ),
+        },
+    ),
+)"#;
+
+    fn world(seed: u64) -> World {
+        let mut world = World::new();
+        let registry = AppTypeRegistry::default();
+        {
+            let mut registry = registry.write();
+            registry.register::<Transform>();
+            registry.register::<SimId>();
+            registry.register::<Team>();
+            registry.register::<Unit>();
+            registry.register::<Health>();
+        }
+        world.insert_resource(registry);
+        world.insert_resource(SimSettings {
+            tick_rate: 30.0,
+            seed,
+        });
+        world
+    }
+
+    #[test]
+    fn version_1_saves_are_migrated() {
+        let world = world(7);
+        let save = read_save(&world, VERSION_1).unwrap();
+        assert_eq!(save.scenario, "scenarios/default.scenario.ron");
+        assert_eq!(save.tick, 60);
+        // As the RNG was when the match started
+        let rng = save.rng.restore();
+        let fresh = ChaCha8Rng::seed_from_u64(7);
+        assert_eq!(rng.get_seed(), fresh.get_seed());
+        assert_eq!(rng.get_word_pos(), fresh.get_word_pos());
+        assert_eq!(save.circling, None);
+        let [(team, stockpile)] = save.stockpiles[..] else {
+            panic!("expected one stockpile");
+        };
+        assert_eq!((team, stockpile.wood, stockpile.stone), (0, 40, 5));
+        // Nor did it save anything added since
+        assert_eq!(save.outcome, Outcome::Playing);
+        assert!(save.explored.is_empty() && save.squads.is_empty() && save.others.is_empty());
+        assert_eq!(save.world.entities.len(), 1);
+    }
+
+    #[test]
+    fn unknown_versions_are_refused() {
+        let world = world(0);
+        for version in [0, SAVE_VERSION + 1] {
+            let text = VERSION_1.replace("version: 1", &format!("version: {version}"));
+            let error = read_save(&world, &text).err().unwrap();
+            assert!(error.to_string().contains("isn't supported"), "{error}");
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState};
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::Pickable;
+use bevy_rts_camera::{Ground, RtsCamera};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::building::{spawn_building, Building};
+use crate::cli;
+use crate::defs::{AwaitingDef, UnitDefs};
+use crate::economy::{spawn_resource_node, ResourceKind, Stockpile, Stockpiles};
+use crate::sim::{SimClock, SimSet};
+use crate::team::{PlayerId, Team};
+use crate::unit::{spawn_unit, Unit};
+
+/// Scenario played when none is given on the command line.
+pub const DEFAULT_SCENARIO: &str = "default";
+
+/// Loads a level from assets/scenarios/ and decides when it's been won or lost.
+#[derive(Clone)]
+pub struct ScenarioPlugin {
+    /// Path of the scenario under assets/.
+    pub path: String,
+}
+
+impl Default for ScenarioPlugin {
+    fn default() -> Self {
+        Self::named(DEFAULT_SCENARIO)
+    }
+}
+
+impl ScenarioPlugin {
+    /// The scenario called `name` in assets/scenarios/.
+    pub fn named(name: &str) -> Self {
+        Self {
+            path: format!("scenarios/{name}.scenario.ron"),
+        }
+    }
+
+    /// The scenario picked with `--scenario <name>` on the command line, or the default one.
+    /// A name ending in .ron is taken as a path under assets/ instead.
+    pub fn from_args() -> Self {
+        match cli::arg("scenario") {
+            Some(path) if path.ends_with(".ron") => Self { path },
+            Some(name) => Self::named(&name),
+            None => Self::default(),
+        }
+    }
+}
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Scenario>()
+            .register_asset_loader(ScenarioLoader)
+            .insert_resource(CurrentScenario {
+                path: self.path.clone(),
+                handle: Handle::default(),
+                spawned: false,
+                elapsed: 0.0,
+            })
+            .init_resource::<Outcome>()
+            .add_systems(Startup, (load_scenario, spawn_outcome_banner))
+            .add_systems(Update, (spawn_scenario, update_outcome_banner))
+            // Judged on the tick as it ended, with the fallen gone
+            .add_systems(FixedUpdate, check_outcome.in_set(SimSet::Last));
+    }
+}
+
+/// A level: its terrain, what each team starts with and how to win it. Read from
+/// assets/scenarios/*.scenario.ron.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub terrain: Vec<TerrainPiece>,
+    pub teams: Vec<TeamStart>,
+    #[serde(default)]
+    pub resources: Vec<ResourcePlacement>,
+    pub camera: CameraStart,
+    /// The player wins by meeting any one of these, and loses with nothing left standing.
+    #[serde(default)]
+    pub victory: Vec<Victory>,
+}
+
+/// A piece of walkable ground. Positions throughout a scenario are XZ unless they say otherwise.
+#[derive(Deserialize)]
+pub struct TerrainPiece {
+    pub shape: TerrainShape,
+    /// Centre of the piece, in 3D.
+    pub position: [f32; 3],
+    /// Rotation about X, Y and Z, in degrees.
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    pub color: [f32; 3],
+}
+
+#[derive(Deserialize)]
+pub enum TerrainShape {
+    Plane { size: [f32; 2] },
+    Cuboid { size: [f32; 3] },
+    Sphere { radius: f32 },
+}
+
+#[derive(Deserialize)]
+pub struct TeamStart {
+    pub team: u8,
+    #[serde(default)]
+    pub stockpile: Stockpile,
+    #[serde(default)]
+    pub units: Vec<UnitGroup>,
+    #[serde(default)]
+    pub buildings: Vec<BuildingPlacement>,
+}
+
+/// A block of units of one kind, `columns` along X and `rows` along Z from the corner `at`.
+#[derive(Deserialize)]
+pub struct UnitGroup {
+    pub unit: String,
+    pub at: [f32; 2],
+    #[serde(default = "one")]
+    pub columns: u32,
+    #[serde(default = "one")]
+    pub rows: u32,
+    #[serde(default)]
+    pub spacing: f32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct BuildingPlacement {
+    pub building: String,
+    pub at: [f32; 2],
+    /// Starts out as a construction site rather than finished.
+    #[serde(default)]
+    pub site: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ResourcePlacement {
+    pub kind: ResourceKind,
+    pub amount: u32,
+    pub at: [f32; 2],
+}
+
+#[derive(Deserialize)]
+pub struct CameraStart {
+    pub focus: [f32; 2],
+    pub zoom: f32,
+}
+
+#[derive(Deserialize)]
+pub enum Victory {
+    /// Every other team has no units or buildings left.
+    Conquest,
+    /// The player has banked at least this much.
+    Stockpile(Stockpile),
+    /// The player still has something standing after this many seconds.
+    Survive(f32),
+}
+
+/// How the scenario has gone for the player so far.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    #[default]
+    Playing,
+    Won,
+    Lost,
+}
+
+#[derive(Resource)]
+pub struct CurrentScenario {
+    pub path: String,
+    pub handle: Handle<Scenario>,
+    spawned: bool,
+    /// Seconds since the scenario was spawned.
+    pub elapsed: f32,
+}
+
+impl CurrentScenario {
+    /// Whether the scenario has been laid out, or given up on after failing to load.
+    pub fn spawned(&self) -> bool {
+        self.spawned
+    }
+}
+
+#[derive(Component)]
+struct OutcomeBanner;
+
+#[derive(Default)]
+struct ScenarioLoader;
+
+#[derive(Debug, Error)]
+pub enum ScenarioLoaderError {
+    #[error("could not read scenario: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse scenario: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for ScenarioLoader {
+    type Asset = Scenario;
+    type Settings = ();
+    type Error = ScenarioLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Scenario, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scenario.ron"]
+    }
+}
+
+fn load_scenario(asset_server: Res<AssetServer>, mut current: ResMut<CurrentScenario>) {
+    current.handle = asset_server.load(current.path.clone());
+}
+
+fn spawn_outcome_banner(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(45.0),
+                top: Val::Percent(40.0),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Pickable::IGNORE,
+        OutcomeBanner,
+    ));
+}
+
+fn color([r, g, b]: [f32; 3]) -> Color {
+    Color::srgb(r, g, b)
+}
+
+fn on_ground([x, z]: [f32; 2]) -> Vec3 {
+    Vec3::new(x, 0.0, z)
+}
+
+// Lay out the scenario once it and the definitions have loaded, and start the simulation
+#[allow(clippy::too_many_arguments)]
+fn spawn_scenario(
+    asset_server: Res<AssetServer>,
+    scenarios: Res<Assets<Scenario>>,
+    unit_defs: UnitDefs,
+    mut clock: ResMut<SimClock>,
+    mut current: ResMut<CurrentScenario>,
+    mut stockpiles: ResMut<Stockpiles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cameras: Query<&mut RtsCamera>,
+    mut commands: Commands,
+) {
+    if current.spawned {
+        return;
+    }
+    let Some(scenario) = scenarios.get(&current.handle) else {
+        if let Some(LoadState::Failed(error)) = asset_server.get_load_state(&current.handle) {
+            error!("Couldn't load scenario {}: {error}", current.path);
+            current.spawned = true;
+        }
+        return;
+    };
+    if !unit_defs.ready() {
+        return;
+    }
+    current.spawned = true;
+    clock.running = true;
+    info!("Starting {}", scenario.name);
+
+    for piece in &scenario.terrain {
+        let mesh = match piece.shape {
+            TerrainShape::Plane { size: [x, z] } => {
+                meshes.add(Plane3d::default().mesh().size(x, z))
+            }
+            TerrainShape::Cuboid { size: [x, y, z] } => meshes.add(Cuboid::new(x, y, z)),
+            TerrainShape::Sphere { radius } => meshes.add(Sphere::new(radius)),
+        };
+        let [rx, ry, rz] = piece.rotation.map(f32::to_radians);
+        commands.spawn((
+            PbrBundle {
+                mesh,
+                material: materials.add(color(piece.color)),
+                transform: Transform::from_translation(Vec3::from(piece.position))
+                    .with_rotation(Quat::from_euler(EulerRot::XYZ, rx, ry, rz)),
+                ..default()
+            },
+            Ground,
+        ));
+    }
+
+    for start in &scenario.teams {
+        let team = Team(start.team);
+        *stockpiles.get_mut(team) = start.stockpile;
+        for placement in &start.buildings {
+            let position = on_ground(placement.at);
+            spawn_building(
+                &mut commands,
+                &placement.building,
+                team,
+                position,
+                !placement.site,
+            );
+        }
+        for group in &start.units {
+            for z in 0..group.rows {
+                for x in 0..group.columns {
+                    let offset = Vec3::new(x as f32, 0.0, z as f32) * group.spacing;
+                    spawn_unit(
+                        &mut commands,
+                        &group.unit,
+                        team,
+                        on_ground(group.at) + offset,
+                    );
+                }
+            }
+        }
+    }
+
+    for placement in &scenario.resources {
+        let position = on_ground(placement.at);
+        spawn_resource_node(&mut commands, placement.kind, placement.amount, position);
+    }
+
+    let [x, z] = scenario.camera.focus;
+    for mut camera in cameras.iter_mut() {
+        camera.target_focus.translation = Vec3::new(x, 0.0, z);
+        camera.target_zoom = scenario.camera.zoom;
+        camera.snap = true;
+    }
+}
+
+// The player wins by meeting any victory condition, and loses once they have nothing left
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn check_outcome(
+    time: Res<Time>,
+    scenarios: Res<Assets<Scenario>>,
+    mut current: ResMut<CurrentScenario>,
+    player: Res<PlayerId>,
+    stockpiles: Res<Stockpiles>,
+    teams: Query<&Team, Or<(With<Unit>, With<Building>)>>,
+    awaiting: Query<(), With<AwaitingDef>>,
+    mut outcome: ResMut<Outcome>,
+) {
+    // Wait until everything the scenario spawned is in play
+    if *outcome != Outcome::Playing || !current.spawned || !awaiting.is_empty() {
+        return;
+    }
+    let Some(scenario) = scenarios.get(&current.handle) else {
+        return;
+    };
+    current.elapsed += time.delta_seconds();
+
+    let player_standing = teams.iter().any(|team| *team == player.0);
+    if !player_standing {
+        *outcome = Outcome::Lost;
+        return;
+    }
+    let won = scenario.victory.iter().any(|victory| match victory {
+        Victory::Conquest => teams.iter().all(|team| *team == player.0),
+        Victory::Stockpile(target) => stockpiles.get(player.0).can_afford(target),
+        Victory::Survive(seconds) => current.elapsed >= *seconds,
+    });
+    if won {
+        *outcome = Outcome::Won;
+    }
+}
+
+fn update_outcome_banner(
+    outcome: Res<Outcome>,
+    mut banner_q: Query<(&mut Text, &mut Visibility), With<OutcomeBanner>>,
+) {
+    if !outcome.is_changed() {
+        return;
+    }
+    let Ok((mut text, mut visibility)) = banner_q.get_single_mut() else {
+        return;
+    };
+    let value = match *outcome {
+        Outcome::Playing => {
+            *visibility = Visibility::Hidden;
+            return;
+        }
+        Outcome::Won => "Victory",
+        Outcome::Lost => "Defeat",
+    };
+    *text = Text::from_section(
+        value,
+        TextStyle {
+            font_size: 48.0,
+            ..default()
+        },
+    );
+    *visibility = Visibility::Inherited;
+}
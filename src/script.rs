@@ -0,0 +1,174 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+
+use crate::building::{Building, ConstructionSite};
+use crate::cli;
+use crate::combat::Weapon;
+use crate::command::{Action, PlayerCommand};
+use crate::economy::{Gatherer, ResourceNode};
+use crate::formation::FormationKind;
+use crate::orders::{ActiveOrder, Destination, Order};
+use crate::production::ProductionQueue;
+use crate::sim::{SimClock, SimId, SimSet};
+use crate::team::Team;
+use crate::unit::Unit;
+
+// Ticks between a script's turns
+const SCRIPT_INTERVAL: u64 = 30;
+
+/// Plays `teams` by a fixed script through `PlayerCommand`s: idle workers gather, idle buildings
+/// train and idle fighters attack-move on the nearest enemy.
+#[derive(Clone)]
+pub struct ScriptPlugin {
+    pub teams: Vec<Team>,
+}
+
+impl ScriptPlugin {
+    /// The teams listed with `--script <team>,...` on the command line, if any.
+    pub fn from_args() -> Self {
+        let teams = cli::arg("script")
+            .into_iter()
+            .flat_map(|teams| {
+                teams
+                    .split(',')
+                    .filter_map(|team| match team.trim().parse() {
+                        Ok(team) => Some(Team(team)),
+                        Err(_) => {
+                            eprintln!("Ignoring --script team {team}, it isn't valid");
+                            None
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Self { teams }
+    }
+}
+
+impl Plugin for ScriptPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ScriptedTeams(self.teams.clone()))
+            .add_systems(
+                FixedUpdate,
+                run_scripts
+                    .in_set(SimSet::Last)
+                    .run_if(|scripted: Res<ScriptedTeams>| !scripted.0.is_empty()),
+            );
+    }
+}
+
+/// The teams a script plays.
+#[derive(Resource)]
+pub struct ScriptedTeams(pub Vec<Team>);
+
+// The first of `candidates` nearest `to`, ties going to the lowest `SimId`
+pub(crate) fn nearest<T: Copy>(
+    to: Vec3,
+    candidates: impl Iterator<Item = (T, SimId, Vec3)>,
+) -> Option<T> {
+    candidates
+        .min_by(|(_, a_id, a), (_, b_id, b)| {
+            let by_distance = a.distance_squared(to).total_cmp(&b.distance_squared(to));
+            match by_distance {
+                Ordering::Equal => a_id.cmp(b_id),
+                unequal => unequal,
+            }
+        })
+        .map(|(candidate, ..)| candidate)
+}
+
+#[allow(clippy::type_complexity)]
+fn run_scripts(
+    clock: Res<SimClock>,
+    scripted: Res<ScriptedTeams>,
+    idle_units: Query<
+        (
+            Entity,
+            &SimId,
+            &Team,
+            &Transform,
+            Has<Gatherer>,
+            Has<Weapon>,
+        ),
+        (With<Unit>, Without<ActiveOrder>),
+    >,
+    targets: Query<(&SimId, &Team, &Transform), Or<(With<Unit>, With<Building>)>>,
+    nodes: Query<(Entity, &SimId, &Transform), With<ResourceNode>>,
+    buildings: Query<(Entity, &SimId, &Team, &ProductionQueue), Without<ConstructionSite>>,
+    mut commands: EventWriter<PlayerCommand>,
+) {
+    if !clock.tick.is_multiple_of(SCRIPT_INTERVAL) {
+        return;
+    }
+    // Queries come back in whatever order entities were stored; commands mustn't
+    let mut idle_units: Vec<_> = idle_units.iter().collect();
+    idle_units.sort_by_key(|(_, id, ..)| **id);
+    let mut buildings: Vec<_> = buildings.iter().collect();
+    buildings.sort_by_key(|(_, id, ..)| **id);
+
+    for &team in &scripted.0 {
+        let mut fighters = Vec::new();
+        let mut centre = Vec3::ZERO;
+        for &(entity, _, unit_team, transform, gatherer, weapon) in &idle_units {
+            if *unit_team != team {
+                continue;
+            }
+            if gatherer {
+                let node = nearest(
+                    transform.translation,
+                    nodes
+                        .iter()
+                        .map(|(node, id, node_transform)| (node, *id, node_transform.translation)),
+                );
+                if let Some(node) = node {
+                    commands.send(PlayerCommand::order(
+                        team,
+                        vec![entity],
+                        Order::Gather(node),
+                        false,
+                    ));
+                }
+            } else if weapon {
+                fighters.push(entity);
+                centre += transform.translation;
+            }
+        }
+
+        if !fighters.is_empty() {
+            centre /= fighters.len() as f32;
+            let enemy = nearest(
+                centre,
+                targets
+                    .iter()
+                    .filter(|(_, target_team, _)| **target_team != team)
+                    .map(|(id, _, transform)| (transform.translation, *id, transform.translation)),
+            );
+            if let Some(enemy) = enemy {
+                let destination = Destination {
+                    target: enemy,
+                    facing: None,
+                    formation: FormationKind::default(),
+                };
+                commands.send(PlayerCommand::order(
+                    team,
+                    fighters,
+                    Order::AttackMove(destination),
+                    false,
+                ));
+            }
+        }
+
+        for &(entity, _, building_team, production) in &buildings {
+            if *building_team == team && production.queue.is_empty() {
+                commands.send(PlayerCommand {
+                    team,
+                    action: Action::Train {
+                        buildings: vec![entity],
+                        slot: 0,
+                    },
+                });
+            }
+        }
+    }
+}
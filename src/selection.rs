@@ -0,0 +1,476 @@
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+use bevy_rts_camera::{RtsCamera, RtsCameraSystemSet};
+
+use crate::building::Building;
+use crate::combat::{BuildingDestroyed, Health, UnitDied};
+use crate::defs::{BuildingDefs, UnitDefs};
+use crate::minimap::MinimapCursor;
+use crate::orders::Targeting;
+use crate::team::{PlayerId, Team, TeamPalette};
+use crate::unit::Unit;
+
+// Below this many logical pixels of cursor travel a left press counts as a click, not a drag
+const DRAG_THRESHOLD: f32 = 4.0;
+// Pressing the same group key twice within this many seconds centers the camera on the group
+const DOUBLE_TAP_SECONDS: f32 = 0.3;
+
+const GROUP_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Selected>()
+            .init_resource::<DragSelection>()
+            .init_resource::<ControlGroups>()
+            .init_resource::<Inspected>()
+            .add_systems(Startup, (spawn_selection_box, spawn_inspect_panel))
+            .add_systems(
+                Update,
+                (
+                    (box_select, update_selection_box).chain(),
+                    control_groups.before(RtsCameraSystemSet),
+                    forget_dead_units,
+                    update_inspect_panel,
+                    update_selection_visual,
+                ),
+            );
+    }
+}
+
+/// Something the player can select.
+#[derive(Component)]
+pub struct Selectable;
+
+/// Part of the player's selection.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Selected;
+
+/// How a click or marquee combines with the existing selection.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SelectionMode {
+    Replace,
+    Add,
+    Subtract,
+}
+
+impl SelectionMode {
+    pub fn from_keys(keys: &ButtonInput<KeyCode>) -> Self {
+        if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            SelectionMode::Add
+        } else if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            SelectionMode::Subtract
+        } else {
+            SelectionMode::Replace
+        }
+    }
+}
+
+/// State of the left-button marquee, in window (logical pixel) coordinates.
+#[derive(Resource, Default)]
+pub struct DragSelection {
+    start: Option<Vec2>,
+    current: Vec2,
+    dragging: bool,
+}
+
+impl DragSelection {
+    fn rect(&self) -> Option<Rect> {
+        self.start
+            .filter(|_| self.dragging)
+            .map(|start| Rect::from_corners(start, self.current))
+    }
+}
+
+/// Numbered control groups, stored by key index (`Digit1` is group 0).
+#[derive(Resource, Default)]
+pub struct ControlGroups {
+    groups: [Vec<Entity>; 9],
+}
+
+impl ControlGroups {
+    pub fn get(&self, group: usize) -> &[Entity] {
+        &self.groups[group]
+    }
+
+    pub fn set(&mut self, group: usize, entities: Vec<Entity>) {
+        self.groups[group] = entities;
+    }
+
+    /// Drop an entity from every group it's in.
+    pub fn remove(&mut self, entity: Entity) {
+        for group in self.groups.iter_mut() {
+            group.retain(|e| *e != entity);
+        }
+    }
+}
+
+/// A unit the player clicked on but can't command, shown in the info panel.
+#[derive(Resource, Default)]
+pub struct Inspected(pub Option<Entity>);
+
+#[derive(Component)]
+struct SelectionBox;
+
+#[derive(Component)]
+struct InspectPanel;
+
+fn spawn_selection_box(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                border: UiRect::all(Val::Px(1.0)),
+                ..default()
+            },
+            border_color: Color::srgb(0.2, 0.8, 0.2).into(),
+            background_color: Color::srgba(0.2, 0.8, 0.2, 0.15).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Pickable::IGNORE,
+        SelectionBox,
+    ));
+}
+
+fn spawn_inspect_panel(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(12.0),
+                bottom: Val::Px(12.0),
+                ..default()
+            },
+            background_color: Color::srgba(0.0, 0.0, 0.0, 0.5).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Pickable::IGNORE,
+        InspectPanel,
+    ));
+}
+
+// Clicking a unit selects it; Shift adds it to the selection and Ctrl removes it. Clicking
+// someone else's unit only shows its info
+#[allow(clippy::too_many_arguments)]
+pub fn select_unit(
+    event: Listener<Pointer<Click>>,
+    mut commands: Commands,
+    key_input: Res<ButtonInput<KeyCode>>,
+    drag: Res<DragSelection>,
+    targeting: Res<Targeting>,
+    player: Res<PlayerId>,
+    mut inspected: ResMut<Inspected>,
+    teams: Query<&Team>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    // While an order waits for its target, left clicks belong to the order
+    if event.button != PointerButton::Primary || drag.dragging || targeting.0.is_some() {
+        return;
+    }
+
+    let entity = event.target;
+    if teams.get(entity).is_ok_and(|team| *team != player.0) {
+        inspected.0 = Some(entity);
+        return;
+    }
+    match SelectionMode::from_keys(&key_input) {
+        SelectionMode::Replace => {
+            inspected.0 = None;
+            for other in selected.iter().filter(|e| *e != entity) {
+                commands.entity(other).remove::<Selected>();
+            }
+            commands.entity(entity).insert(Selected);
+        }
+        SelectionMode::Add => {
+            commands.entity(entity).insert(Selected);
+        }
+        SelectionMode::Subtract => {
+            commands.entity(entity).remove::<Selected>();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn box_select(
+    windows: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<RtsCamera>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut drag: ResMut<DragSelection>,
+    targeting: Res<Targeting>,
+    minimap: Res<MinimapCursor>,
+    player: Res<PlayerId>,
+    mut inspected: ResMut<Inspected>,
+    units: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &Team,
+            Option<&PickingInteraction>,
+            Has<Building>,
+        ),
+        With<Selectable>,
+    >,
+    selected: Query<Entity, With<Selected>>,
+    mut commands: Commands,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let cursor_position = window.cursor_position();
+
+    // Clicks on the minimap and clicks picking an order's target aren't selections
+    if mouse_button_input.just_pressed(MouseButton::Left)
+        && targeting.0.is_none()
+        && minimap.0.is_none()
+    {
+        drag.start = cursor_position;
+        drag.dragging = false;
+    }
+
+    let Some(start) = drag.start else {
+        return;
+    };
+    if let Some(position) = cursor_position {
+        drag.current = position;
+        if start.distance(position) > DRAG_THRESHOLD {
+            drag.dragging = true;
+        }
+    }
+
+    if !mouse_button_input.just_released(MouseButton::Left) {
+        return;
+    }
+
+    let mode = SelectionMode::from_keys(&key_input);
+    match drag.rect() {
+        Some(rect) => {
+            let Ok((camera, camera_transform)) = camera_q.get_single() else {
+                return;
+            };
+            // Only the player's own units can be box selected, buildings have to be clicked
+            let inside = units
+                .iter()
+                .filter(|(_, transform, team, _, building)| {
+                    **team == player.0
+                        && !building
+                        && camera
+                            .world_to_viewport(camera_transform, transform.translation())
+                            .is_some_and(|point| rect.contains(point))
+                })
+                .map(|(entity, ..)| entity);
+
+            if mode == SelectionMode::Replace {
+                inspected.0 = None;
+                for entity in selected.iter() {
+                    commands.entity(entity).remove::<Selected>();
+                }
+            }
+            for entity in inside {
+                if mode == SelectionMode::Subtract {
+                    commands.entity(entity).remove::<Selected>();
+                } else {
+                    commands.entity(entity).insert(Selected);
+                }
+            }
+        }
+        None => {
+            // A plain click on empty ground clears the selection; clicks on units are handled
+            // by `select_unit`
+            let over_unit = units.iter().any(|(.., interaction, _)| {
+                interaction.is_some_and(|i| *i != PickingInteraction::None)
+            });
+            if mode == SelectionMode::Replace && !over_unit {
+                inspected.0 = None;
+                for entity in selected.iter() {
+                    commands.entity(entity).remove::<Selected>();
+                }
+            }
+        }
+    }
+
+    drag.start = None;
+    drag.dragging = false;
+}
+
+fn update_selection_box(
+    drag: Res<DragSelection>,
+    mut box_q: Query<(&mut Style, &mut Visibility), With<SelectionBox>>,
+) {
+    let Ok((mut style, mut visibility)) = box_q.get_single_mut() else {
+        return;
+    };
+
+    match drag.rect() {
+        Some(rect) => {
+            style.left = Val::Px(rect.min.x);
+            style.top = Val::Px(rect.min.y);
+            style.width = Val::Px(rect.width());
+            style.height = Val::Px(rect.height());
+            *visibility = Visibility::Visible;
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
+
+// Ctrl+digit stores the selection in a group, digit recalls it and a double tap centers the camera
+#[allow(clippy::too_many_arguments)]
+fn control_groups(
+    time: Res<Time>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut groups: ResMut<ControlGroups>,
+    mut last_recall: Local<Option<(usize, f32)>>,
+    units: Query<&GlobalTransform, With<Selectable>>,
+    selected: Query<Entity, With<Selected>>,
+    mut cam_q: Query<&mut RtsCamera>,
+    mut commands: Commands,
+) {
+    let Some(group) = GROUP_KEYS
+        .iter()
+        .position(|key| key_input.just_pressed(*key))
+    else {
+        return;
+    };
+
+    if key_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        groups.set(group, selected.iter().collect());
+        *last_recall = None;
+        return;
+    }
+
+    // Prune anything that has been despawned since the group was stored
+    let members: Vec<Entity> = groups
+        .get(group)
+        .iter()
+        .copied()
+        .filter(|entity| units.contains(*entity))
+        .collect();
+    groups.set(group, members.clone());
+    if members.is_empty() {
+        return;
+    }
+
+    for entity in selected.iter().filter(|e| !members.contains(e)) {
+        commands.entity(entity).remove::<Selected>();
+    }
+    for entity in members.iter() {
+        commands.entity(*entity).insert(Selected);
+    }
+
+    let now = time.elapsed_seconds();
+    let double_tap =
+        matches!(*last_recall, Some((last, at)) if last == group && now - at < DOUBLE_TAP_SECONDS);
+    if double_tap {
+        let centroid = members
+            .iter()
+            .filter_map(|entity| units.get(*entity).ok())
+            .map(|transform| transform.translation())
+            .sum::<Vec3>()
+            / members.len() as f32;
+        for mut cam in cam_q.iter_mut() {
+            cam.target_focus.translation = centroid;
+        }
+        *last_recall = None;
+    } else {
+        *last_recall = Some((group, now));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_inspect_panel(
+    mut inspected: ResMut<Inspected>,
+    unit_defs: UnitDefs,
+    building_defs: BuildingDefs,
+    units: Query<(&Team, Option<&Health>, Option<&Unit>, Option<&Building>)>,
+    mut panel_q: Query<(&mut Text, &mut Visibility), With<InspectPanel>>,
+) {
+    let Ok((mut text, mut visibility)) = panel_q.get_single_mut() else {
+        return;
+    };
+    let Some(Ok((team, health, unit, building))) = inspected.0.map(|entity| units.get(entity))
+    else {
+        // Forget units that have died since they were clicked
+        inspected.0 = None;
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    // Name it the way the player's own units and buildings are, falling back to what it is
+    let name = match (unit, building) {
+        (Some(unit), _) => unit_defs
+            .get(&unit.0)
+            .map_or("unit".to_string(), |def| def.name.clone()),
+        (_, Some(building)) => building_defs
+            .get(&building.0)
+            .map_or("building".to_string(), |def| def.name.clone()),
+        _ => "unit".to_string(),
+    };
+    let mut info = format!("Enemy {name} (team {})", team.0);
+    if let Some(health) = health {
+        info += &format!(
+            "\nHealth: {:.0} / {:.0}",
+            health.current.max(0.0),
+            health.max
+        );
+    }
+    *text = Text::from_section(info, TextStyle::default());
+    *visibility = Visibility::Visible;
+}
+
+fn forget_dead_units(
+    mut deaths: EventReader<UnitDied>,
+    mut destroyed: EventReader<BuildingDestroyed>,
+    mut groups: ResMut<ControlGroups>,
+) {
+    for death in deaths.read() {
+        groups.remove(death.entity);
+    }
+    for building in destroyed.read() {
+        groups.remove(building.entity);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_selection_visual(
+    palette: Res<TeamPalette>,
+    mut deselected: RemovedComponents<Selected>,
+    // Units loaded already selected only get a material once their def is in
+    mut selected: Query<
+        (&mut Handle<StandardMaterial>, &Team),
+        (
+            With<Selectable>,
+            With<Unit>,
+            With<Selected>,
+            Or<(Added<Selected>, Added<Handle<StandardMaterial>>)>,
+        ),
+    >,
+    mut unselected: Query<
+        (&mut Handle<StandardMaterial>, &Team),
+        (With<Selectable>, With<Unit>, Without<Selected>),
+    >,
+) {
+    // Buildings keep their own materials, only units light up
+    for (mut material, team) in selected.iter_mut() {
+        *material = palette.selected(*team);
+    }
+    for entity in deselected.read() {
+        // Gone altogether if it died
+        if let Ok((mut material, team)) = unselected.get_mut(entity) {
+            *material = palette.normal(*team);
+        }
+    }
+}
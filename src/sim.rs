@@ -0,0 +1,199 @@
+use bevy::ecs::schedule::ExecutorKind;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::cli;
+
+/// Simulation ticks per second when none is given with `--tick-rate`.
+pub const DEFAULT_TICK_RATE: f64 = 30.0;
+
+/// Runs the game simulation in `FixedUpdate` at a fixed rate, so that the same commands at the
+/// same ticks always play out the same way. Simulated entities are drawn in between ticks.
+#[derive(Clone)]
+pub struct SimPlugin {
+    /// Ticks per second.
+    pub tick_rate: f64,
+    /// Seed of the `SimRng`.
+    pub seed: u64,
+}
+
+impl Default for SimPlugin {
+    fn default() -> Self {
+        Self {
+            tick_rate: DEFAULT_TICK_RATE,
+            seed: 0,
+        }
+    }
+}
+
+impl SimPlugin {
+    /// Settings from `--tick-rate` and `--seed` on the command line, or the defaults.
+    pub fn from_args() -> Self {
+        let default = Self::default();
+        Self {
+            tick_rate: cli::parsed_arg("tick-rate").unwrap_or(default.tick_rate),
+            seed: cli::parsed_arg("seed").unwrap_or(default.seed),
+        }
+    }
+}
+
+impl Plugin for SimPlugin {
+    fn build(&self, app: &mut App) {
+        // Systems that could run in either order would otherwise be ordered differently from
+        // one run to the next
+        app.edit_schedule(FixedUpdate, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        });
+        app.insert_resource(Time::<Fixed>::from_hz(self.tick_rate))
+            .insert_resource(SimRng(ChaCha8Rng::seed_from_u64(self.seed)))
+            .insert_resource(SimSettings {
+                tick_rate: self.tick_rate,
+                seed: self.seed,
+            })
+            .init_resource::<SimClock>()
+            .init_resource::<NextSimId>()
+            .register_type::<SimId>()
+            .configure_sets(
+                FixedUpdate,
+                (
+                    SimSet::Commands,
+                    SimSet::Spawn,
+                    SimSet::Update,
+                    SimSet::Last,
+                )
+                    .chain()
+                    .run_if(|clock: Res<SimClock>| clock.ticking()),
+            )
+            .add_systems(FixedFirst, restore_sim_transforms)
+            .add_systems(FixedUpdate, advance_clock.after(SimSet::Last))
+            .add_systems(FixedLast, record_sim_transforms)
+            .add_systems(
+                PostUpdate,
+                interpolate_transforms.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Every gameplay system runs in one of these, in `FixedUpdate`.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SimSet {
+    /// Commands given since the last tick are carried out.
+    Commands,
+    /// Entities spawned last tick are filled in from their definitions.
+    Spawn,
+    /// Everything else.
+    Update,
+    /// Sees the state the tick ends with.
+    Last,
+}
+
+/// What the `SimPlugin` was set up with.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct SimSettings {
+    pub tick_rate: f64,
+    pub seed: u64,
+}
+
+/// Ticks the simulation has run.
+#[derive(Resource, Default)]
+pub struct SimClock {
+    pub tick: u64,
+    /// Ticks only run once a match has been laid out.
+    pub running: bool,
+    /// Set before a tick to skip it, while waiting on something outside the simulation.
+    pub held: bool,
+}
+
+impl SimClock {
+    /// Whether this tick runs.
+    pub fn ticking(&self) -> bool {
+        self.running && !self.held
+    }
+}
+
+/// Identifies a simulated entity the same way every time a match is played out, which an
+/// `Entity` doesn't: anything spawned outside the simulation takes entity ids too. Commands
+/// refer to entities by this once they're written down.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Reflect)]
+#[reflect(Component)]
+pub struct SimId(pub u32);
+
+/// The `SimId` the next simulated entity gets.
+#[derive(Resource, Default)]
+pub struct NextSimId(pub u32);
+
+/// Give `entity` the next `SimId`. Add it to the commands spawning a simulated entity, so ids
+/// are handed out in the order the simulation spawns things.
+pub fn assign_sim_id(entity: Entity, world: &mut World) {
+    let mut next = world.resource_mut::<NextSimId>();
+    let id = SimId(next.0);
+    next.0 += 1;
+    world.entity_mut(entity).insert(id);
+}
+
+/// The only source of randomness the simulation may use.
+#[derive(Resource)]
+pub struct SimRng(pub ChaCha8Rng);
+
+impl SimRng {
+    /// A generator of its own for `key`, from the same seed. Drawing from it leaves the shared
+    /// sequence alone, so things outside the simulation can be random yet repeatable.
+    pub fn stream(&self, key: u64) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::from_seed(self.0.get_seed());
+        rng.set_stream(key);
+        rng
+    }
+}
+
+/// Where a simulated entity stood after the last two ticks. Its `Transform` is drawn somewhere
+/// between the two, and put back where the simulation left it before the next tick.
+#[derive(Component)]
+pub struct Interpolated {
+    previous: Vec3,
+    current: Vec3,
+}
+
+impl Interpolated {
+    pub fn new(translation: Vec3) -> Self {
+        Self {
+            previous: translation,
+            current: translation,
+        }
+    }
+
+    /// Where the simulation has the entity.
+    pub fn current(&self) -> Vec3 {
+        self.current
+    }
+}
+
+fn advance_clock(mut clock: ResMut<SimClock>) {
+    if clock.ticking() {
+        clock.tick += 1;
+    }
+}
+
+fn restore_sim_transforms(mut entities: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in entities.iter_mut() {
+        transform.translation = interpolated.current;
+        interpolated.previous = interpolated.current;
+    }
+}
+
+fn record_sim_transforms(mut entities: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in entities.iter_mut() {
+        interpolated.current = transform.translation;
+    }
+}
+
+fn interpolate_transforms(
+    time: Res<Time<Fixed>>,
+    mut entities: Query<(&mut Transform, &Interpolated)>,
+) {
+    let blend = time.overstep_fraction();
+    for (mut transform, interpolated) in entities.iter_mut() {
+        transform.translation = interpolated.previous.lerp(interpolated.current, blend);
+    }
+}
@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::combat::{BuildingDestroyed, UnitDied};
+use crate::production::UnitTrained;
+use crate::sim::SimSet;
+use crate::team::Team;
+
+/// Keeps count of how each team fares over a match.
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchStats>()
+            .add_systems(FixedUpdate, count_units.in_set(SimSet::Last));
+    }
+}
+
+/// Every team's `TeamStats`.
+#[derive(Resource, Default)]
+pub struct MatchStats(pub HashMap<Team, TeamStats>);
+
+impl MatchStats {
+    pub fn get(&self, team: Team) -> TeamStats {
+        self.0.get(&team).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct TeamStats {
+    /// Units that came out of the team's buildings. Ones the scenario began with, or a save
+    /// brought back, don't count.
+    pub units_trained: u32,
+    pub units_lost: u32,
+    pub buildings_lost: u32,
+}
+
+fn count_units(
+    mut trained: EventReader<UnitTrained>,
+    mut deaths: EventReader<UnitDied>,
+    mut destroyed: EventReader<BuildingDestroyed>,
+    mut stats: ResMut<MatchStats>,
+) {
+    for unit in trained.read() {
+        stats.0.entry(unit.team).or_default().units_trained += 1;
+    }
+    for death in deaths.read() {
+        stats.0.entry(death.team).or_default().units_lost += 1;
+    }
+    for building in destroyed.read() {
+        stats.0.entry(building.team).or_default().buildings_lost += 1;
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::flow_field::FlowGoal;
+use crate::movement::UNIT_GROUND_OFFSET;
+use crate::navigation::{NavGrid, Path};
+use crate::sim::{SimId, SimSet};
+
+// Units closer than this push each other apart
+const SEPARATION_RADIUS: f32 = 0.7;
+const SEPARATION_STRENGTH: f32 = 2.0;
+// Two capsules of radius 0.25 touch at 0.5, allow a little slack
+const TOUCH_DISTANCE: f32 = 0.6;
+// A unit this close to its goal has arrived regardless of its group
+const ARRIVAL_DISTANCE: f32 = 0.15;
+// How quickly the applied velocity catches up with the steering target, per second
+const ACCELERATION: f32 = 12.0;
+
+pub struct SteeringPlugin;
+
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Steering>()
+            .register_type::<OrderGroup>()
+            .init_resource::<SpatialHash>()
+            .configure_sets(FixedUpdate, SteeringSet.in_set(SimSet::Update))
+            .add_systems(
+                FixedUpdate,
+                (build_spatial_hash, steer_units)
+                    .chain()
+                    .in_set(SteeringSet),
+            );
+    }
+}
+
+/// Moves units the way order-following systems set their `Steering` to, before it.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct SteeringSet;
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Steering {
+    /// Top speed, in world units per second.
+    pub max_speed: f32,
+    /// Unit direction the current order wants to move in, zero when idle.
+    pub desired: Vec2,
+    /// Final destination of the current order, if any.
+    pub goal: Option<Vec3>,
+    /// Velocity applied last frame, after avoidance.
+    pub velocity: Vec2,
+    /// Holding its ground: never moves and isn't pushed aside by other units.
+    pub anchored: bool,
+}
+
+impl Steering {
+    pub fn new(max_speed: f32) -> Self {
+        Self {
+            max_speed,
+            desired: Vec2::ZERO,
+            goal: None,
+            velocity: Vec2::ZERO,
+            anchored: false,
+        }
+    }
+}
+
+/// Shared by every unit moved by the same order, so they can agree on when they've arrived.
+#[derive(Component, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct OrderGroup {
+    pub id: u32,
+    /// Distance from the goal within which a unit may stop once it bumps into an arrived
+    /// group member, roughly the size of the formation.
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Neighbour {
+    pub entity: Entity,
+    pub position: Vec2,
+    moving: bool,
+    anchored: bool,
+    group: Option<u32>,
+}
+
+/// Unit positions bucketed on a coarse XZ grid, rebuilt every frame for neighbour queries.
+#[derive(Resource, Default)]
+pub struct SpatialHash {
+    cells: HashMap<IVec2, Vec<Neighbour>>,
+}
+
+impl SpatialHash {
+    const CELL_SIZE: f32 = 2.0;
+
+    fn cell(position: Vec2) -> IVec2 {
+        (position / Self::CELL_SIZE).floor().as_ivec2()
+    }
+
+    /// Every unit within `radius` of `position`.
+    pub fn query(&self, position: Vec2, radius: f32) -> impl Iterator<Item = &Neighbour> {
+        let min = Self::cell(position - radius);
+        let max = Self::cell(position + radius);
+        (min.y..=max.y)
+            .flat_map(move |z| (min.x..=max.x).map(move |x| IVec2::new(x, z)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |n| n.position.distance_squared(position) <= radius * radius)
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn build_spatial_hash(
+    mut hash: ResMut<SpatialHash>,
+    units: Query<(
+        Entity,
+        &Transform,
+        &Steering,
+        Option<&OrderGroup>,
+        Option<&SimId>,
+    )>,
+) {
+    hash.cells.clear();
+    // Neighbours are added up in the order they're stored, which has to be the same every run
+    let mut units: Vec<_> = units.iter().collect();
+    units.sort_by_key(|(.., id)| *id);
+    for (entity, transform, steering, group, _) in units {
+        let position = transform.translation.xz();
+        hash.cells
+            .entry(SpatialHash::cell(position))
+            .or_default()
+            .push(Neighbour {
+                entity,
+                position,
+                moving: steering.goal.is_some(),
+                anchored: steering.anchored,
+                group: group.map(|g| g.id),
+            });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn steer_units(
+    time: Res<Time>,
+    hash: Res<SpatialHash>,
+    grid: Res<NavGrid>,
+    mut units: Query<(
+        Entity,
+        &mut Transform,
+        &mut Steering,
+        Option<&OrderGroup>,
+        Option<&SimId>,
+    )>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut transform, mut steering, group, id) in units.iter_mut() {
+        let position = transform.translation.xz();
+        let mut desired = std::mem::take(&mut steering.desired);
+        let mut goal = steering.goal.take();
+        let moving = goal.is_some();
+
+        let mut separation = Vec2::ZERO;
+        let mut touching_arrived = false;
+        for other in hash.query(position, SEPARATION_RADIUS) {
+            if other.entity == entity {
+                continue;
+            }
+            let offset = position - other.position;
+            let distance = offset.length();
+            // Units exactly on top of each other split along an arbitrary but stable axis
+            let away = offset
+                .try_normalize()
+                .unwrap_or_else(|| Vec2::from_angle(id.map_or(0, |id| id.0) as f32 * 2.399_963));
+            // Idle units make way for moving ones, moving units mostly brush past idle ones
+            // but have to go around anyone holding position
+            let weight = match (moving, other.moving) {
+                (false, true) => 2.0,
+                (true, false) if !other.anchored => 0.5,
+                _ => 1.0,
+            };
+            separation += away * (1.0 - distance / SEPARATION_RADIUS) * weight;
+
+            let same_group = group.is_some_and(|g| other.group == Some(g.id));
+            if same_group && !other.moving && distance < TOUCH_DISTANCE {
+                touching_arrived = true;
+            }
+        }
+
+        // A unit arrives at its own goal, or when it's near enough and bumps into a member of
+        // its group that already stopped, so the last few don't fight over occupied space
+        if let Some(destination) = goal {
+            let remaining = destination.xz().distance(position);
+            let near_group = group.is_some_and(|g| remaining < g.radius);
+            if remaining < ARRIVAL_DISTANCE || (near_group && touching_arrived) {
+                goal = None;
+                desired = Vec2::ZERO;
+                commands.entity(entity).remove::<(Path, FlowGoal)>();
+            }
+        }
+
+        let target = if steering.anchored {
+            Vec2::ZERO
+        } else {
+            (desired + separation * SEPARATION_STRENGTH).clamp_length_max(1.0) * steering.max_speed
+        };
+        steering.velocity = steering.velocity.lerp(target, (ACCELERATION * dt).min(1.0));
+        let mut movement = steering.velocity * dt;
+        if let Some(goal) = goal {
+            // Don't overshoot the goal on the final approach
+            movement = movement.clamp_length_max(goal.xz().distance(position));
+        }
+
+        // Slide along obstacles instead of walking into them
+        if grid.len() > 0 {
+            let walkable = |p: Vec2| grid.is_walkable(grid.cell_of(p));
+            if walkable(position) && !walkable(position + movement) {
+                movement = if walkable(position + Vec2::new(movement.x, 0.0)) {
+                    Vec2::new(movement.x, 0.0)
+                } else if walkable(position + Vec2::new(0.0, movement.y)) {
+                    Vec2::new(0.0, movement.y)
+                } else {
+                    Vec2::ZERO
+                };
+            }
+        }
+
+        transform.translation += Vec3::new(movement.x, 0.0, movement.y);
+        // Keep the unit standing on whatever terrain it's over
+        let ground = grid.height_at(transform.translation.xz()).unwrap_or(0.0);
+        transform.translation.y = ground + UNIT_GROUND_OFFSET;
+    }
+}
@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Base colour of each team, in team order
+const TEAM_COLORS: [Color; 4] = [
+    Color::srgb(0.8, 0.2, 0.2),
+    Color::srgb(0.4, 0.1, 0.5),
+    Color::srgb(0.2, 0.4, 0.8),
+    Color::srgb(0.9, 0.7, 0.1),
+];
+
+pub struct TeamPlugin;
+
+impl Plugin for TeamPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Team>()
+            .init_resource::<PlayerId>()
+            .init_resource::<TeamPalette>();
+    }
+}
+
+/// The side a unit fights for.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct Team(pub u8);
+
+/// The team the local player controls.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct PlayerId(pub Team);
+
+impl Default for PlayerId {
+    fn default() -> Self {
+        Self(Team(0))
+    }
+}
+
+struct TeamMaterials {
+    normal: Handle<StandardMaterial>,
+    selected: Handle<StandardMaterial>,
+}
+
+/// Unit materials for every team, plain and highlighted for selection.
+#[derive(Resource)]
+pub struct TeamPalette {
+    teams: Vec<TeamMaterials>,
+}
+
+impl TeamPalette {
+    fn materials(&self, team: Team) -> &TeamMaterials {
+        &self.teams[team.0 as usize % self.teams.len()]
+    }
+
+    pub fn normal(&self, team: Team) -> Handle<StandardMaterial> {
+        self.materials(team).normal.clone()
+    }
+
+    pub fn selected(&self, team: Team) -> Handle<StandardMaterial> {
+        self.materials(team).selected.clone()
+    }
+}
+
+impl FromWorld for TeamPalette {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let teams = TEAM_COLORS
+            .iter()
+            .map(|color| TeamMaterials {
+                normal: materials.add(*color),
+                // Selected units glow in a paler shade of their team colour
+                selected: materials.add(StandardMaterial {
+                    base_color: color.mix(&Color::WHITE, 0.5),
+                    emissive: color.to_linear() * 0.5,
+                    ..default()
+                }),
+            })
+            .collect();
+        Self { teams }
+    }
+}
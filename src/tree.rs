@@ -4,85 +4,72 @@ use bevy::{
 };
 use noise::{NoiseFn, Perlin};
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_systems(Startup, setup)
-        .add_systems(Update, rotate_tree)
-        .run();
+/// Grows a generated tree on every entity given a `GrowTree`: the trunk and branches as its mesh,
+/// with the leaves as a child.
+pub struct TreeGenPlugin;
+
+impl Plugin for TreeGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TreeMaterials>()
+            .add_systems(Update, grow_trees);
+    }
 }
 
+/// Asks for a tree grown from `seed`. The same seed always grows the same tree.
 #[derive(Component)]
-struct RotatingTree;
+pub struct GrowTree {
+    pub seed: u64,
+}
 
-fn setup(
-    mut commands: Commands,
+/// What grown trees are drawn with.
+#[derive(Resource)]
+pub struct TreeMaterials {
+    pub bark: Handle<StandardMaterial>,
+    pub leaves: Handle<StandardMaterial>,
+}
+
+impl FromWorld for TreeMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self {
+            bark: materials.add(Color::srgb(0.4, 0.25, 0.1)),
+            leaves: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.2, 0.6, 0.2),
+                cull_mode: None,
+                double_sided: true,
+                ..default()
+            }),
+        }
+    }
+}
+
+fn grow_trees(
+    tree_materials: Res<TreeMaterials>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    trees: Query<(Entity, &GrowTree), Added<GrowTree>>,
+    mut commands: Commands,
 ) {
-    // Camera
-    commands.spawn(Camera3dBundle {
-        transform: Transform::from_xyz(-5.0, 7.0, 12.0).looking_at(Vec3::new(0., 3., 0.), Vec3::Y),
-        ..default()
-    });
-
-    // Light
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            illuminance: 10000.0,
-            shadows_enabled: true,
-            ..default()
-        },
-        transform: Transform::from_rotation(Quat::from_euler(EulerRot::XYZ, -0.5, 0.5, 0.0)),
-        ..default()
-    });
-
-    // Tree
-    let tree = generate_tree();
-    let (trunk_mesh, leaf_mesh) = create_tree_mesh(&tree);
-
-    commands
-        .spawn((
-            PbrBundle {
-                mesh: meshes.add(trunk_mesh),
-                material: materials.add(Color::rgb(0.45, 0.3, 0.2)),
-                transform: Transform::from_xyz(0.0, 0.0, 0.0),
-                ..default()
-            },
-            RotatingTree,
-        ))
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh: meshes.add(leaf_mesh),
-                material: materials.add(StandardMaterial {
-                    base_color: Color::rgb(0.2, 0.8, 0.2),
-                    alpha_mode: AlphaMode::Blend,
+    for (entity, grow) in trees.iter() {
+        let tree = generate_tree(&mut ChaCha8Rng::seed_from_u64(grow.seed));
+        let (trunk, leaves) = create_tree_mesh(&tree);
+        commands
+            .entity(entity)
+            .insert((meshes.add(trunk), tree_materials.bark.clone()))
+            .with_children(|tree| {
+                tree.spawn(PbrBundle {
+                    mesh: meshes.add(leaves),
+                    material: tree_materials.leaves.clone(),
                     ..default()
-                }),
-                ..default()
+                });
             });
-        });
-
-    // Ground plane
-    commands.spawn(PbrBundle {
-        mesh: meshes.add(Mesh::from(Plane3d::new(
-            Vec3::new(0., 1., 0.),
-            Vec2::new(10., 10.),
-        ))),
-        material: materials.add(Color::rgb(0.3, 0.5, 0.3)),
-        ..default()
-    });
-}
-
-fn rotate_tree(time: Res<Time>, mut query: Query<&mut Transform, With<RotatingTree>>) {
-    for mut transform in &mut query {
-        transform.rotate_y(0.1 * time.delta_seconds());
     }
 }
 
+/// A branch point of a generated tree, with the branches growing out of it.
 #[derive(Clone)]
-struct TreeNode {
+pub struct TreeNode {
     position: Vec3,
     direction: Vec3,
     radius: f32,
@@ -90,8 +77,9 @@ struct TreeNode {
     is_leaf: bool,
 }
 
-fn generate_tree() -> TreeNode {
-    let mut rng = rand::thread_rng();
+/// Grow a random tree from a single trunk at the origin, pointing up. The same `rng` state
+/// always grows the same tree.
+pub fn generate_tree(rng: &mut impl Rng) -> TreeNode {
     let noise = Perlin::new(rng.gen());
 
     fn generate_branch(
@@ -99,7 +87,7 @@ fn generate_tree() -> TreeNode {
         direction: Vec3,
         radius: f32,
         depth: i32,
-        rng: &mut ThreadRng,
+        rng: &mut impl Rng,
         noise: &Perlin,
     ) -> TreeNode {
         let mut node = TreeNode {
@@ -153,12 +141,13 @@ fn generate_tree() -> TreeNode {
         Vec3::Y,
         0.2,
         7, // Adjust this value to control the overall complexity of the tree
-        &mut rng,
+        rng,
         &noise,
     )
 }
 
-fn create_tree_mesh(tree: &TreeNode) -> (Mesh, Mesh) {
+/// Meshes for the trunk and branches, and for the leaves, of a generated tree.
+pub fn create_tree_mesh(tree: &TreeNode) -> (Mesh, Mesh) {
     let mut trunk_positions = Vec::new();
     let mut trunk_normals = Vec::new();
     let mut trunk_indices = Vec::new();
@@ -167,6 +156,7 @@ fn create_tree_mesh(tree: &TreeNode) -> (Mesh, Mesh) {
     let mut leaf_uvs = Vec::new();
     let mut leaf_indices = Vec::new();
 
+    #[allow(clippy::too_many_arguments)]
     fn process_node(
         node: &TreeNode,
         trunk_positions: &mut Vec<[f32; 3]>,
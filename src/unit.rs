@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+use serde::Deserialize;
+
+use crate::building::Builder;
+use crate::combat::{Armor, Delivery, Health, Weapon};
+use crate::defs::{AwaitingDef, DefsSet, UnitDefs};
+use crate::economy::{Gatherer, Stockpile};
+use crate::fog::Vision;
+use crate::movement::UNIT_GROUND_OFFSET;
+use crate::selection::{select_unit, Selectable};
+use crate::sim::{assign_sim_id, Interpolated, SimSet};
+use crate::steering::Steering;
+use crate::team::{Team, TeamPalette};
+
+pub struct UnitPlugin;
+
+impl Plugin for UnitPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Unit>()
+            .add_systems(PreUpdate, reload_unit_defs.after(DefsSet))
+            .add_systems(FixedUpdate, build_units.in_set(SimSet::Spawn));
+    }
+}
+
+/// Everything about a kind of unit that doesn't change between units, read from
+/// assets/units/*.unit.ron.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct UnitDef {
+    pub name: String,
+    /// Radius and length of the capsule the unit is drawn as.
+    pub radius: f32,
+    pub length: f32,
+    /// Top speed, in world units per second.
+    pub speed: f32,
+    pub health: f32,
+    #[serde(default)]
+    pub armor: f32,
+    pub vision: f32,
+    #[serde(default)]
+    pub weapon: Option<WeaponDef>,
+    /// How much the unit can carry and how fast it harvests, if it gathers at all.
+    #[serde(default)]
+    pub gatherer: Option<GathererDef>,
+    /// Seconds of construction work per second, if it builds at all.
+    #[serde(default)]
+    pub build_rate: Option<f32>,
+    pub cost: Stockpile,
+    /// Seconds it takes a building to train.
+    pub build_time: f32,
+    #[serde(skip)]
+    pub mesh: Handle<Mesh>,
+}
+
+#[derive(Deserialize)]
+pub struct WeaponDef {
+    pub range: f32,
+    pub damage: f32,
+    pub cooldown: f32,
+    pub delivery: Delivery,
+}
+
+#[derive(Deserialize)]
+pub struct GathererDef {
+    pub capacity: u32,
+    pub rate: f32,
+}
+
+/// The id of the definition a unit was made from.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Unit(pub String);
+
+/// Spawn a unit of the definition `def_id` standing on the ground at `position`. It's filled in
+/// from the definition once that has loaded.
+pub fn spawn_unit(commands: &mut Commands, def_id: &str, team: Team, position: Vec3) -> Entity {
+    commands
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_translation(
+                position + Vec3::Y * UNIT_GROUND_OFFSET,
+            )),
+            Unit(def_id.to_string()),
+            team,
+            AwaitingDef::default(),
+        ))
+        .add(assign_sim_id)
+        .id()
+}
+
+fn build_units(
+    unit_defs: UnitDefs,
+    palette: Res<TeamPalette>,
+    units: Query<(Entity, &Unit, &Team, &Transform, &AwaitingDef)>,
+    mut commands: Commands,
+) {
+    for (entity, unit, team, transform, awaiting) in units.iter() {
+        let Some(def) = unit_defs.get(&unit.0) else {
+            if unit_defs.ready() {
+                warn!("There's no unit called `{}`", unit.0);
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        };
+        let mut unit = commands.entity(entity);
+        unit.remove::<AwaitingDef>().insert((
+            def.mesh.clone(),
+            palette.normal(*team),
+            Selectable,
+            PickableBundle::default(),
+            On::<Pointer<Click>>::run(select_unit),
+            Interpolated::new(transform.translation),
+        ));
+        if awaiting.restored {
+            continue;
+        }
+        unit.insert((
+            Steering::new(def.speed),
+            Health::new(def.health),
+            Armor(def.armor),
+            Vision(def.vision),
+        ));
+        if let Some(weapon) = &def.weapon {
+            unit.insert(Weapon::new(
+                weapon.range,
+                weapon.damage,
+                weapon.cooldown,
+                weapon.delivery,
+            ));
+        }
+        if let Some(gatherer) = &def.gatherer {
+            unit.insert(Gatherer::new(gatherer.capacity, gatherer.rate));
+        }
+        if let Some(rate) = def.build_rate {
+            unit.insert(Builder { rate });
+        }
+    }
+}
+
+// Carry edits to a definition file over to the units already out there
+#[allow(clippy::type_complexity)]
+fn reload_unit_defs(
+    mut events: EventReader<AssetEvent<UnitDef>>,
+    unit_defs: UnitDefs,
+    mut units: Query<
+        (
+            &Unit,
+            &mut Steering,
+            &mut Health,
+            &mut Armor,
+            &mut Vision,
+            Option<&mut Weapon>,
+            Option<&mut Gatherer>,
+            Option<&mut Builder>,
+        ),
+        Without<AwaitingDef>,
+    >,
+) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(def_id) = unit_defs.id_of(*id) else {
+            continue;
+        };
+        let Some(def) = unit_defs.get(def_id) else {
+            continue;
+        };
+        for (_, mut steering, mut health, mut armor, mut vision, weapon, gatherer, builder) in
+            units.iter_mut().filter(|(unit, ..)| unit.0 == def_id)
+        {
+            steering.max_speed = def.speed;
+            health.max = def.health;
+            health.current = health.current.min(def.health);
+            armor.0 = def.armor;
+            vision.0 = def.vision;
+            // Units only pick up new abilities when they're spawned
+            if let (Some(mut weapon), Some(stats)) = (weapon, &def.weapon) {
+                weapon.range = stats.range;
+                weapon.damage = stats.damage;
+                weapon.cooldown = stats.cooldown;
+                weapon.delivery = stats.delivery;
+            }
+            if let (Some(mut gatherer), Some(stats)) = (gatherer, &def.gatherer) {
+                gatherer.capacity = stats.capacity;
+                gatherer.rate = stats.rate;
+            }
+            if let (Some(mut builder), Some(rate)) = (builder, def.build_rate) {
+                builder.rate = rate;
+            }
+        }
+    }
+}
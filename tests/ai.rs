@@ -0,0 +1,45 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::ai::{AiPlayer, Difficulty, SkirmishAiPlugin};
+use bevy_rts::building::Building;
+use bevy_rts::stats::MatchStats;
+use bevy_rts::team::Team;
+
+use common::{game, headless, run};
+
+// Long enough for both sides to build an army and send it at the other
+const TICKS: u64 = 4200;
+
+#[test]
+fn ais_build_up_and_fight_it_out() {
+    let mut game = game("skirmish");
+    game.ai = SkirmishAiPlugin {
+        players: (0..2)
+            .map(|team| AiPlayer {
+                team: Team(team),
+                difficulty: Difficulty::Hard,
+            })
+            .collect(),
+    };
+    let mut app = headless(game, TICKS);
+    run(&mut app, |_, _| {});
+
+    let world = app.world_mut();
+    let mut buildings = world.query_filtered::<&Team, With<Building>>();
+    let stats = world.resource::<MatchStats>();
+    let (first, second) = (stats.get(Team(0)), stats.get(Team(1)));
+    for (team, stats) in [(Team(0), first), (Team(1), second)] {
+        assert!(stats.units_trained > 0, "{team:?} trained nothing");
+        // Each started with just a town hall
+        let standing = buildings
+            .iter(world)
+            .filter(|owner| **owner == team)
+            .count();
+        let put_up = standing + stats.buildings_lost as usize;
+        assert!(put_up > 1, "{team:?} built nothing");
+    }
+    let losses =
+        first.units_lost + first.buildings_lost + second.units_lost + second.buildings_lost;
+    assert!(losses > 0, "nobody attacked");
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::building::ConstructionSite;
+use bevy_rts::command::{Action, PlayerCommand};
+use bevy_rts::team::Team;
+
+use common::{game, headless, run_to};
+
+#[test]
+fn sites_placed_on_the_same_tick_dont_overlap() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    for x in [-15.0, -14.5] {
+        app.world_mut().send_event(PlayerCommand {
+            team: Team(0),
+            action: Action::Place {
+                building: "storehouse".to_string(),
+                position: Vec3::new(x, 0.0, -15.0),
+                builders: Vec::new(),
+                queue: false,
+            },
+        });
+    }
+    run_to(&mut app, 10);
+    let world = app.world_mut();
+    let sites = world
+        .query_filtered::<(), With<ConstructionSite>>()
+        .iter(world)
+        .count();
+    assert_eq!(sites, 1);
+}
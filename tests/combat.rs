@@ -0,0 +1,78 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::building::Building;
+use bevy_rts::combat::Health;
+use bevy_rts::command::PlayerCommand;
+use bevy_rts::formation::FormationKind;
+use bevy_rts::orders::{Destination, Order};
+use bevy_rts::sim::Interpolated;
+use bevy_rts::stats::MatchStats;
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+
+use common::{game, headless, run_to};
+
+// The skirmish with team 1 down to a badly damaged town hall, and team 0's soldiers standing
+// a little way south of it, out of reach of its middle but not of its nearest edge
+fn siege() -> (App, Vec<Entity>, Entity) {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+
+    let mut units = world.query::<(Entity, &Unit, &Team, &mut Health)>();
+    let mut soldiers = Vec::new();
+    for (entity, unit, team, mut health) in units.iter_mut(world) {
+        if *team == Team(1) {
+            health.current = 0.0;
+        } else if unit.0 == "soldier" {
+            soldiers.push(entity);
+        }
+    }
+    for (i, soldier) in soldiers.iter().enumerate() {
+        let position = Vec3::new(24.0 + i as f32, 0.75, 19.5);
+        world
+            .entity_mut(*soldier)
+            .insert(Interpolated::new(position));
+        world.get_mut::<Transform>(*soldier).unwrap().translation = position;
+    }
+
+    let mut buildings = world.query_filtered::<(Entity, &Team, &mut Health), With<Building>>();
+    let (hall, _, mut health) = buildings
+        .iter_mut(world)
+        .find(|(_, team, _)| **team == Team(1))
+        .unwrap();
+    health.current = 30.0;
+    (app, soldiers, hall)
+}
+
+fn order(app: &mut App, soldiers: Vec<Entity>, order: Order) {
+    app.world_mut()
+        .send_event(PlayerCommand::order(Team(0), soldiers, order, false));
+}
+
+fn assert_destroyed(app: &mut App, hall: Entity) {
+    run_to(app, 90);
+    assert!(app.world().get_entity(hall).is_none());
+    let stats = app.world().resource::<MatchStats>().get(Team(1));
+    assert_eq!(stats.buildings_lost, 1);
+}
+
+#[test]
+fn attacking_a_building_knocks_it_down() {
+    let (mut app, soldiers, hall) = siege();
+    order(&mut app, soldiers, Order::Attack(hall));
+    assert_destroyed(&mut app, hall);
+}
+
+#[test]
+fn attack_moving_units_fire_on_buildings_in_range() {
+    let (mut app, soldiers, hall) = siege();
+    let here = Destination {
+        target: Vec3::new(25.0, 0.0, 19.5),
+        facing: None,
+        formation: FormationKind::default(),
+    };
+    order(&mut app, soldiers, Order::AttackMove(here));
+    assert_destroyed(&mut app, hall);
+}
@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy_rts::ai::SkirmishAiPlugin;
+use bevy_rts::headless::HeadlessPlugin;
+use bevy_rts::lockstep::LockstepPlugin;
+use bevy_rts::replay::ReplayPlugin;
+use bevy_rts::scenario::ScenarioPlugin;
+use bevy_rts::script::ScriptPlugin;
+use bevy_rts::sim::{SimClock, SimPlugin};
+use bevy_rts::RtsGamePlugin;
+
+// Somewhere for a test to write `name` to.
+pub fn temp_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+// The scenario called `name` played alone, recording to a file of its own.
+#[allow(dead_code)]
+pub fn game(name: &str) -> RtsGamePlugin {
+    RtsGamePlugin {
+        sim: SimPlugin::default(),
+        scenario: ScenarioPlugin::named(name),
+        replay: ReplayPlugin::Record(temp_path(&format!("{name}.replay.ron"))),
+        lockstep: LockstepPlugin::default(),
+        script: ScriptPlugin { teams: Vec::new() },
+        ai: SkirmishAiPlugin::default(),
+    }
+}
+
+// `game` with no window, ready to be updated by hand, stopping after `ticks`.
+#[allow(dead_code)]
+pub fn headless(game: RtsGamePlugin, ticks: u64) -> App {
+    let mut app = App::new();
+    app.add_plugins(HeadlessPlugin {
+        ticks,
+        report: None,
+    })
+    .add_plugins(game);
+    app.finish();
+    app.cleanup();
+    app
+}
+
+// Updates until the simulation has run `tick` ticks.
+#[allow(dead_code)]
+pub fn run_to(app: &mut App, tick: u64) {
+    while app.world().resource::<SimClock>().tick < tick {
+        app.update();
+    }
+}
+
+// Updates until the headless run is over, giving `on_tick` the world and its tick before each
+// update.
+#[allow(dead_code)]
+pub fn run(app: &mut App, mut on_tick: impl FnMut(&mut World, u64)) {
+    while app.should_exit().is_none() {
+        let tick = app.world().resource::<SimClock>().tick;
+        on_tick(app.world_mut(), tick);
+        app.update();
+    }
+}
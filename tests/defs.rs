@@ -0,0 +1,46 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::combat::{Delivery, Health, Weapon};
+use bevy_rts::economy::Gatherer;
+use bevy_rts::fog::Vision;
+use bevy_rts::steering::Steering;
+use bevy_rts::team::Team;
+use bevy_rts::unit::spawn_unit;
+
+use common::{game, headless, run_to};
+
+#[test]
+fn units_are_made_from_their_definitions() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+    let mut commands = world.commands();
+    let archer = spawn_unit(
+        &mut commands,
+        "archer",
+        Team(0),
+        Vec3::new(-15.0, 0.75, -15.0),
+    );
+    let unknown = spawn_unit(
+        &mut commands,
+        "catapult",
+        Team(0),
+        Vec3::new(-15.0, 0.75, -17.0),
+    );
+    world.flush();
+    run_to(&mut app, 7);
+
+    // As assets/units/archer.unit.ron has it
+    let world = app.world();
+    assert_eq!(world.get::<Health>(archer).unwrap().max, 80.0);
+    assert_eq!(world.get::<Vision>(archer).unwrap().0, 7.0);
+    assert_eq!(world.get::<Steering>(archer).unwrap().max_speed, 5.0);
+    let weapon = world.get::<Weapon>(archer).unwrap();
+    assert_eq!((weapon.range, weapon.damage), (5.0, 8.0));
+    assert!(matches!(weapon.delivery, Delivery::Projectile { .. }));
+    assert!(world.get::<Gatherer>(archer).is_none());
+
+    // There's no such unit
+    assert!(world.get_entity(unknown).is_none());
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::command::PlayerCommand;
+use bevy_rts::economy::{Gatherer, ResourceKind, ResourceNode, Stockpiles};
+use bevy_rts::orders::Order;
+use bevy_rts::team::Team;
+
+use common::{game, headless, run_to};
+
+#[test]
+fn workers_carry_what_they_gather_to_the_stockpile() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+    let wood_before = world.resource::<Stockpiles>().get(Team(0)).wood;
+
+    // The wood nearest the player's town hall
+    let hall = Vec2::new(-25.0, -25.0);
+    let mut nodes = world.query::<(Entity, &ResourceNode, &Transform)>();
+    let (node, amount_before) = nodes
+        .iter(world)
+        .filter(|(_, node, _)| node.kind == ResourceKind::Wood)
+        .min_by(|(.., a), (.., b)| {
+            let (a, b) = (a.translation.xz(), b.translation.xz());
+            a.distance(hall).total_cmp(&b.distance(hall))
+        })
+        .map(|(entity, node, _)| (entity, node.amount))
+        .unwrap();
+    let mut workers = world.query_filtered::<(Entity, &Team), With<Gatherer>>();
+    let workers: Vec<Entity> = workers
+        .iter(world)
+        .filter(|(_, team)| **team == Team(0))
+        .map(|(entity, _)| entity)
+        .collect();
+    world.send_event(PlayerCommand::order(
+        Team(0),
+        workers.clone(),
+        Order::Gather(node),
+        false,
+    ));
+    run_to(&mut app, 600);
+
+    let world = app.world_mut();
+    let gained = world.resource::<Stockpiles>().get(Team(0)).wood - wood_before;
+    assert!(gained > 0);
+    // Whatever left the tree is in the stockpile or on its way there
+    let left = world
+        .get::<ResourceNode>(node)
+        .map_or(0, |node| node.amount);
+    let carried: u32 = workers
+        .iter()
+        .filter_map(|worker| world.get::<Gatherer>(*worker))
+        .map(|gatherer| gatherer.carrying)
+        .sum();
+    assert_eq!(left + gained + carried, amount_before);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::fog::{FogOfWar, FogState};
+use bevy_rts::sim::Interpolated;
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+
+use common::{game, headless, run_to};
+
+fn place(world: &mut World, unit: Entity, at: Vec3) {
+    world.entity_mut(unit).insert(Interpolated::new(at));
+    world.get_mut::<Transform>(unit).unwrap().translation = at;
+}
+
+#[test]
+fn ground_is_seen_while_a_unit_is_near_and_remembered_after() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    // Out on the open ground, well away from the player's base
+    let out = Vec3::new(0.0, 0.75, -30.0);
+    let state = |app: &App| app.world().resource::<FogOfWar>().state(Team(0), out);
+    assert_eq!(state(&app), FogState::Unexplored);
+
+    let world = app.world_mut();
+    let mut units = world.query::<(Entity, &Team, &Unit)>();
+    let (scout, ..) = units
+        .iter(world)
+        .find(|(_, team, unit)| **team == Team(0) && unit.0 == "soldier")
+        .unwrap();
+    let home = world.get::<Transform>(scout).unwrap().translation;
+    place(world, scout, out);
+    run_to(&mut app, 10);
+    assert_eq!(state(&app), FogState::Visible);
+
+    place(app.world_mut(), scout, home);
+    run_to(&mut app, 15);
+    assert_eq!(state(&app), FogState::Explored);
+}
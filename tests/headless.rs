@@ -0,0 +1,52 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::headless::{HeadlessPlugin, Report};
+use bevy_rts::scenario::Outcome;
+use bevy_rts::script::ScriptPlugin;
+use bevy_rts::team::Team;
+
+use common::{game, run};
+
+const TICKS: u64 = 300;
+
+// Scripts playing both sides of the skirmish for `TICKS`, and what the run reported
+fn script_match(name: &str) -> Report {
+    let path = common::temp_path(&format!("{name}.report.ron"));
+    let mut game = game("skirmish");
+    game.script = ScriptPlugin {
+        teams: vec![Team(0), Team(1)],
+    };
+    let mut app = App::new();
+    app.add_plugins(HeadlessPlugin {
+        ticks: TICKS,
+        report: Some(path.clone()),
+    })
+    .add_plugins(game);
+    app.finish();
+    app.cleanup();
+    run(&mut app, |_, _| {});
+    assert_eq!(app.should_exit(), Some(AppExit::Success));
+
+    let text = std::fs::read_to_string(&path).expect("the report was written");
+    ron::from_str(&text).expect("the report reads back")
+}
+
+#[test]
+fn script_matches_report_the_same_way_twice() {
+    let first = script_match("first");
+    assert_eq!(first.scenario, "Skirmish");
+    assert_eq!(first.ticks, TICKS);
+    assert_eq!(first.outcome, Outcome::Playing);
+    assert_eq!(first.desynced_at, None);
+    let teams: Vec<_> = first.teams.iter().map(|team| team.team).collect();
+    assert_eq!(teams, [0, 1]);
+    for team in &first.teams {
+        assert_eq!(team.buildings.get("town_hall"), Some(&1));
+        assert!(!team.units.is_empty());
+    }
+
+    let second = script_match("second");
+    assert_eq!(second.seed, first.seed);
+    assert_eq!(second.hash, first.hash);
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use bevy::input::mouse::MouseButtonInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use bevy_rts::navigation::NavGrid;
+use bevy_rts::orders::{ActiveOrder, UnitOrder};
+use bevy_rts::selection::Selected;
+use bevy_rts::sim::SimClock;
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+use bevy_rts_camera::RtsCamera;
+
+use common::{game, headless, run_to};
+
+// Clicks `button` with the cursor `normalized` across the minimap, and returns where on the map
+// that is
+fn click_minimap(app: &mut App, normalized: Vec2, button: MouseButton) -> Vec2 {
+    let world = app.world_mut();
+    let mut minimaps = world.query::<&mut RelativeCursorPosition>();
+    *minimaps.single_mut(world) = RelativeCursorPosition {
+        normalized_visible_node_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+        normalized: Some(normalized),
+    };
+    for state in [ButtonState::Pressed, ButtonState::Released] {
+        app.world_mut().send_event(MouseButtonInput {
+            button,
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+    }
+    let bounds = app.world().resource::<NavGrid>().bounds();
+    bounds.min + normalized * bounds.size()
+}
+
+#[test]
+fn left_clicking_the_minimap_looks_there() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let there = click_minimap(&mut app, Vec2::new(0.75, 0.25), MouseButton::Left);
+
+    let world = app.world_mut();
+    let camera = world.query::<&RtsCamera>().single(world);
+    assert!(camera.target_focus.translation.xz().distance(there) < 0.01);
+}
+
+#[test]
+fn right_clicking_the_minimap_moves_the_selection_there() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+    let mut units = world.query::<(Entity, &Team, &Unit)>();
+    let soldiers: Vec<Entity> = units
+        .iter(world)
+        .filter(|(_, team, unit)| **team == Team(0) && unit.0 == "soldier")
+        .map(|(entity, ..)| entity)
+        .collect();
+    for soldier in &soldiers {
+        world.entity_mut(*soldier).insert(Selected);
+    }
+
+    let there = click_minimap(&mut app, Vec2::new(0.25, 0.75), MouseButton::Right);
+    let tick = app.world().resource::<SimClock>().tick;
+    run_to(&mut app, tick + 5);
+    for soldier in soldiers {
+        let order = app
+            .world()
+            .get::<ActiveOrder>(soldier)
+            .map(|order| &order.0);
+        let Some(UnitOrder::Move(leg)) = order else {
+            panic!("the soldier wasn't sent anywhere");
+        };
+        // Somewhere in a formation around the point clicked
+        assert!(leg.position.xz().distance(there) < 5.0);
+    }
+}
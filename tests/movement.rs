@@ -0,0 +1,83 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::command::PlayerCommand;
+use bevy_rts::formation::FormationKind;
+use bevy_rts::movement::UNIT_GROUND_OFFSET;
+use bevy_rts::navigation::NavSettings;
+use bevy_rts::orders::{Destination, Order};
+use bevy_rts::sim::Interpolated;
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+
+use common::{game, headless, run_to};
+
+fn place(world: &mut World, unit: Entity, at: Vec3) {
+    world.entity_mut(unit).insert(Interpolated::new(at));
+    world.get_mut::<Transform>(unit).unwrap().translation = at;
+}
+
+fn soldiers(world: &mut World) -> Vec<Entity> {
+    let mut units = world.query::<(Entity, &Team, &Unit)>();
+    units
+        .iter(world)
+        .filter(|(_, team, unit)| **team == Team(0) && unit.0 == "soldier")
+        .map(|(entity, ..)| entity)
+        .collect()
+}
+
+fn position(app: &App, unit: Entity) -> Vec3 {
+    app.world().get::<Transform>(unit).unwrap().translation
+}
+
+#[test]
+fn units_on_top_of_each_other_spread_out() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+    let soldiers = soldiers(world);
+    let (a, b) = (soldiers[0], soldiers[1]);
+    place(world, a, Vec3::new(-10.0, 0.75, -10.0));
+    place(world, b, Vec3::new(-10.05, 0.75, -10.0));
+
+    run_to(&mut app, 35);
+    let apart = position(&app, a).xz().distance(position(&app, b).xz());
+    assert!(apart > 0.5, "still {apart} apart");
+}
+
+// Sends a soldier from the foot of the ramp in the default scenario to the plateau it leads up
+// to, returning where it ends up
+fn walk_up_the_ramp(max_slope: f32) -> Vec3 {
+    let mut app = headless(game("default"), u64::MAX);
+    app.insert_resource(NavSettings {
+        max_slope: max_slope.to_radians(),
+    });
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+    let soldier = soldiers(world)[0];
+    place(world, soldier, Vec3::new(2.0, 0.75, -5.0));
+    let destination = Destination {
+        target: Vec3::new(15.0, 1.0, -5.0),
+        facing: None,
+        formation: FormationKind::default(),
+    };
+    world.send_event(PlayerCommand::order(
+        Team(0),
+        vec![soldier],
+        Order::Move(destination),
+        false,
+    ));
+    run_to(&mut app, 200);
+    position(&app, soldier)
+}
+
+#[test]
+fn units_walk_up_slopes_gentle_enough() {
+    let on_top = walk_up_the_ramp(40.0);
+    assert!(on_top.xz().distance(Vec2::new(15.0, -5.0)) < 0.5);
+    assert!((on_top.y - (1.0 + UNIT_GROUND_OFFSET)).abs() < 0.05);
+
+    // Too steep to climb with a lower limit
+    let below = walk_up_the_ramp(10.0);
+    assert!((below.y - UNIT_GROUND_OFFSET).abs() < 0.05);
+}
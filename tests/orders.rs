@@ -0,0 +1,92 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::building::Building;
+use bevy_rts::command::PlayerCommand;
+use bevy_rts::formation::FormationKind;
+use bevy_rts::orders::{ActiveOrder, Destination, Order, OrderQueue};
+use bevy_rts::sim::Interpolated;
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+
+use common::{game, headless, run_to};
+
+#[test]
+fn a_patrol_with_no_way_there_is_given_up() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+
+    // Stuck in the middle of the town hall, with nowhere to go
+    let mut halls = world.query_filtered::<(&Team, &Transform), With<Building>>();
+    let (_, hall) = halls
+        .iter(world)
+        .find(|(team, _)| **team == Team(0))
+        .unwrap();
+    let stuck = Vec3::new(hall.translation.x, 0.75, hall.translation.z);
+    let mut units = world.query_filtered::<(Entity, &Team), With<Unit>>();
+    let (unit, _) = units
+        .iter(world)
+        .find(|(_, team)| **team == Team(0))
+        .unwrap();
+    world.entity_mut(unit).insert(Interpolated::new(stuck));
+    world.get_mut::<Transform>(unit).unwrap().translation = stuck;
+
+    let there = Destination {
+        target: stuck + Vec3::new(10.0, 0.0, 10.0),
+        facing: None,
+        formation: FormationKind::default(),
+    };
+    world.send_event(PlayerCommand::order(
+        Team(0),
+        vec![unit],
+        Order::Patrol(there),
+        false,
+    ));
+    run_to(&mut app, 10);
+    assert!(app.world().get::<ActiveOrder>(unit).is_none());
+}
+
+#[test]
+fn queued_moves_are_walked_one_after_another() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+    let mut units = world.query_filtered::<(Entity, &Team), With<Unit>>();
+    let (unit, _) = units
+        .iter(world)
+        .find(|(_, team)| **team == Team(0))
+        .unwrap();
+    let start = Vec3::new(-15.0, 0.75, -15.0);
+    world.entity_mut(unit).insert(Interpolated::new(start));
+    world.get_mut::<Transform>(unit).unwrap().translation = start;
+
+    // Out one way, then round the corner
+    let first = Vec3::new(-5.0, 0.0, -15.0);
+    let second = Vec3::new(-5.0, 0.0, -5.0);
+    for (target, queue) in [(first, false), (second, true)] {
+        let there = Destination {
+            target,
+            facing: None,
+            formation: FormationKind::default(),
+        };
+        world.send_event(PlayerCommand::order(
+            Team(0),
+            vec![unit],
+            Order::Move(there),
+            queue,
+        ));
+    }
+    run_to(&mut app, 8);
+    assert_eq!(app.world().get::<OrderQueue>(unit).unwrap().0.len(), 1);
+
+    let mut closest_to_first = f32::MAX;
+    for tick in 9..200 {
+        run_to(&mut app, tick);
+        let position = app.world().get::<Transform>(unit).unwrap().translation;
+        closest_to_first = closest_to_first.min(position.xz().distance(first.xz()));
+    }
+    assert!(closest_to_first < 0.5);
+    let end = app.world().get::<Transform>(unit).unwrap().translation;
+    assert!(end.xz().distance(second.xz()) < 0.5);
+}
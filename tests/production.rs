@@ -0,0 +1,89 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::building::Building;
+use bevy_rts::command::{Action, PlayerCommand};
+use bevy_rts::economy::Stockpiles;
+use bevy_rts::formation::FormationKind;
+use bevy_rts::orders::{Destination, Order};
+use bevy_rts::production::ProductionQueue;
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+
+use common::{game, headless, run_to};
+
+fn workers(world: &mut World) -> Vec<Entity> {
+    let mut units = world.query::<(Entity, &Team, &Unit)>();
+    units
+        .iter(world)
+        .filter(|(_, team, unit)| **team == Team(0) && unit.0 == "worker")
+        .map(|(entity, ..)| entity)
+        .collect()
+}
+
+fn command(world: &mut World, action: Action) {
+    world.send_event(PlayerCommand {
+        team: Team(0),
+        action,
+    });
+}
+
+#[test]
+fn trained_units_are_paid_for_and_walk_to_the_rally_point() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+    let world = app.world_mut();
+    let before = workers(world);
+    let wood = world.resource::<Stockpiles>().get(Team(0)).wood;
+    let mut halls = world.query_filtered::<(Entity, &Team), With<Building>>();
+    let (hall, _) = halls
+        .iter(world)
+        .find(|(_, team)| **team == Team(0))
+        .unwrap();
+
+    // Rally out in the open, queue three workers and think better of the last one
+    let rally = Vec3::new(-12.0, 0.0, -22.0);
+    let there = Destination {
+        target: rally,
+        facing: None,
+        formation: FormationKind::default(),
+    };
+    world.send_event(PlayerCommand::order(
+        Team(0),
+        vec![hall],
+        Order::Move(there),
+        false,
+    ));
+    for _ in 0..3 {
+        let buildings = vec![hall];
+        command(world, Action::Train { buildings, slot: 0 });
+    }
+    run_to(&mut app, 7);
+    let world = app.world_mut();
+    command(
+        world,
+        Action::CancelTraining {
+            buildings: vec![hall],
+        },
+    );
+    run_to(&mut app, 9);
+
+    let world = app.world_mut();
+    assert_eq!(world.get::<ProductionQueue>(hall).unwrap().queue.len(), 2);
+    // Workers cost 30 wood, and the one taken back was refunded
+    let spent = wood - world.resource::<Stockpiles>().get(Team(0)).wood;
+    assert_eq!(spent, 60);
+
+    // Six seconds each, then the walk over
+    run_to(&mut app, 600);
+    let world = app.world_mut();
+    let trained: Vec<Entity> = workers(world)
+        .into_iter()
+        .filter(|worker| !before.contains(worker))
+        .collect();
+    assert_eq!(trained.len(), 2);
+    for worker in trained {
+        let position = world.get::<Transform>(worker).unwrap().translation;
+        assert!(position.xz().distance(rally.xz()) < 2.0);
+    }
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy_rts::command::PlayerCommand;
+use bevy_rts::formation::FormationKind;
+use bevy_rts::movement::Move;
+use bevy_rts::orders::{Destination, Order};
+use bevy_rts::replay::{read_replay, Playback, ReplayPlugin};
+use bevy_rts::save::{save_game, Quicksave};
+use bevy_rts::sim::SimClock;
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+
+use common::{game, headless, run};
+
+const TICKS: u64 = 150;
+
+// Records a match in which the commands `give` sends are the only ones, then plays it back
+fn record_and_play(name: &str, give: impl FnMut(&mut World, u64)) -> Option<u64> {
+    let mut game = game("default");
+    let path = common::temp_path(&format!("{name}.replay.ron"));
+    game.replay = ReplayPlugin::Record(path.clone());
+    let mut recording = headless(game.clone(), TICKS);
+    run(&mut recording, give);
+
+    let replay = read_replay(&path).expect("the match was recorded");
+    assert!(!replay.commands.is_empty());
+    let length = replay.length;
+    let replay = ReplayPlugin::Play(replay);
+    game.sim = replay.sim_plugin();
+    game.scenario = replay.scenario_plugin();
+    game.replay = replay;
+    let mut playback = headless(game, TICKS);
+    run(&mut playback, |_, _| {});
+    let world = playback.world();
+    assert_eq!(world.resource::<SimClock>().tick, length);
+    world.resource::<Playback>().desynced_at()
+}
+
+fn units_of(world: &mut World, team: u8) -> Vec<Entity> {
+    world
+        .query_filtered::<(Entity, &Team), With<Unit>>()
+        .iter(world)
+        .filter(|(_, unit_team)| unit_team.0 == team)
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+#[test]
+fn following_the_moving_unit_plays_back() {
+    let desynced_at = record_and_play("follow", |world, tick| {
+        if tick != 10 {
+            return;
+        }
+        let units = units_of(world, 0);
+        let cube = world.query_filtered::<Entity, With<Move>>().single(world);
+        world.send_event(PlayerCommand::order(
+            Team(0),
+            units,
+            Order::Follow(cube),
+            false,
+        ));
+    });
+    assert_eq!(desynced_at, None);
+}
+
+fn to(x: f32, z: f32, formation: FormationKind) -> Destination {
+    Destination {
+        target: Vec3::new(x, 0.0, z),
+        facing: None,
+        formation,
+    }
+}
+
+#[test]
+fn a_recorded_match_plays_back_the_same() {
+    let desynced_at = record_and_play("orders", |world, tick| {
+        let soldiers = units_of(world, 0);
+        let archers = units_of(world, 1);
+        let (order, units, team) = match tick {
+            5 => (
+                Order::AttackMove(to(0.0, 16.0, FormationKind::Wedge)),
+                soldiers.clone(),
+                0,
+            ),
+            20 => (
+                Order::Patrol(to(10.0, -10.0, FormationKind::Line)),
+                soldiers[..5].to_vec(),
+                0,
+            ),
+            40 => (Order::Attack(archers[0]), soldiers[5..8].to_vec(), 0),
+            60 => (Order::Stop, soldiers[..2].to_vec(), 0),
+            80 => (
+                Order::Move(to(-5.0, 10.0, FormationKind::Column)),
+                archers,
+                1,
+            ),
+            _ => return,
+        };
+        world.send_event(PlayerCommand::order(Team(team), units, order, false));
+    });
+    assert_eq!(desynced_at, None);
+}
+
+#[test]
+fn a_match_quickloaded_while_recording_plays_back_from_the_save() {
+    let quicksave = common::temp_path("recorded_quicksave.ron");
+    let mut loaded = false;
+    let desynced_at = record_and_play("quickload", |world, tick| match (tick, loaded) {
+        (0, false) => world.insert_resource(Quicksave(quicksave.clone())),
+        (20, false) => {
+            let soldiers = units_of(world, 0);
+            let order = Order::Move(to(6.0, 6.0, FormationKind::Line));
+            world.send_event(PlayerCommand::order(Team(0), soldiers, order, false));
+        }
+        (30, false) => save_game(world, &quicksave).unwrap(),
+        (60, false) => {
+            world.send_event(KeyboardInput {
+                key_code: KeyCode::F9,
+                logical_key: Key::F9,
+                state: ButtonState::Pressed,
+                window: Entity::PLACEHOLDER,
+            });
+            loaded = true;
+        }
+        (45, true) => {
+            let archers = units_of(world, 1);
+            let order = Order::Move(to(-5.0, 10.0, FormationKind::Column));
+            world.send_event(PlayerCommand::order(Team(1), archers, order, false));
+        }
+        _ => {}
+    });
+    assert_eq!(desynced_at, None);
+
+    // Only what happened after the load was kept, starting from the save
+    let replay = read_replay(&common::temp_path("quickload.replay.ron")).unwrap();
+    assert!(replay.start.is_some());
+    assert!(replay.commands.iter().all(|recorded| recorded.tick >= 30));
+    assert!(replay.hashes.iter().all(|hash| hash.tick >= 30));
+}
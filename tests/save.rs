@@ -0,0 +1,127 @@
+mod common;
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy_rts::command::PlayerCommand;
+use bevy_rts::economy::IncomeMultipliers;
+use bevy_rts::fog::FogOfWar;
+use bevy_rts::formation::FormationKind;
+use bevy_rts::movement::Move;
+use bevy_rts::orders::{ActiveOrder, Destination, LastOrderGroup, Order, UnitOrder};
+use bevy_rts::replay::HashedState;
+use bevy_rts::save::{load_game, save_game};
+use bevy_rts::scenario::Outcome;
+use bevy_rts::sim::{SimClock, SimRng};
+use bevy_rts::stats::MatchStats;
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+use rand::RngCore;
+
+use common::{game, headless, run_to};
+
+// Where the match is: its tick, the moving unit, the next random number and everything hashed
+fn state(app: &mut App) -> (u64, f32, u64, u64) {
+    let world = app.world_mut();
+    let tick = world.resource::<SimClock>().tick;
+    let angle = world.query::<&Move>().single(world).angle;
+    let random = world.resource::<SimRng>().0.clone().next_u64();
+    let hash = world.run_system_once(|state: HashedState| state.hash());
+    (tick, angle, random, hash)
+}
+
+#[test]
+fn a_loaded_game_carries_on_from_the_save() {
+    let path = common::temp_path("carry_on.ron");
+    let mut app = headless(game("default"), u64::MAX);
+    run_to(&mut app, 60);
+    save_game(app.world_mut(), &path).unwrap();
+    let saved = state(&mut app);
+    run_to(&mut app, 120);
+    let later = state(&mut app);
+
+    load_game(app.world_mut(), &path).unwrap();
+    let (tick, angle, random, _) = state(&mut app);
+    assert_eq!((tick, angle, random), (saved.0, saved.1, saved.2));
+    run_to(&mut app, 120);
+    assert_eq!(state(&mut app), later);
+}
+
+// Everything about the match that lives outside the entities
+#[allow(clippy::type_complexity)]
+fn resources(app: &App) -> (Outcome, Vec<(Team, f32)>, Vec<(Team, Vec<u32>)>, u32) {
+    let world = app.world();
+    let mut multipliers: Vec<(Team, f32)> = world
+        .resource::<IncomeMultipliers>()
+        .0
+        .iter()
+        .map(|(team, multiplier)| (*team, *multiplier))
+        .collect();
+    multipliers.sort_by_key(|(team, _)| team.0);
+    (
+        *world.resource::<Outcome>(),
+        multipliers,
+        world.resource::<FogOfWar>().explored(),
+        world.resource::<LastOrderGroup>().0,
+    )
+}
+
+#[test]
+fn loading_restores_the_rest_of_the_match() {
+    let path = common::temp_path("rest_of_the_match.ron");
+    let mut app = headless(game("default"), u64::MAX);
+    run_to(&mut app, 30);
+    let world = app.world_mut();
+    let cube = world.query_filtered::<Entity, With<Move>>().single(world);
+    let unit = world
+        .query_filtered::<Entity, With<Unit>>()
+        .iter(world)
+        .next()
+        .unwrap();
+    // Told to move first, so a group id is handed out
+    let somewhere = Destination {
+        target: Vec3::new(4.0, 0.0, 4.0),
+        facing: None,
+        formation: FormationKind::default(),
+    };
+    for order in [Order::Move(somewhere), Order::Follow(cube)] {
+        world.send_event(PlayerCommand::order(Team(0), vec![unit], order, false));
+    }
+    world
+        .resource_mut::<IncomeMultipliers>()
+        .0
+        .insert(Team(1), 2.0);
+    run_to(&mut app, 60);
+    *app.world_mut().resource_mut::<Outcome>() = Outcome::Won;
+    save_game(app.world_mut(), &path).unwrap();
+    let saved = resources(&app);
+    assert!(saved.3 > 0, "no group orders were given");
+
+    run_to(&mut app, 120);
+    let world = app.world_mut();
+    *world.resource_mut::<Outcome>() = Outcome::Playing;
+    world.resource_mut::<IncomeMultipliers>().0.clear();
+    world.resource_mut::<LastOrderGroup>().0 = 0;
+
+    load_game(app.world_mut(), &path).unwrap();
+    assert_eq!(resources(&app), saved);
+    // The moving unit isn't saved, but the unit following it still follows it
+    let world = app.world_mut();
+    let following = world
+        .query::<&ActiveOrder>()
+        .iter(world)
+        .filter(|order| matches!(order.0, UnitOrder::Follow(target) if target == cube))
+        .count();
+    assert_eq!(following, 1);
+}
+
+#[test]
+fn loaded_units_dont_count_as_trained() {
+    let path = common::temp_path("trained.ron");
+    let mut app = headless(game("default"), u64::MAX);
+    run_to(&mut app, 5);
+    save_game(app.world_mut(), &path).unwrap();
+    load_game(app.world_mut(), &path).unwrap();
+    run_to(&mut app, 10);
+    let stats = app.world().resource::<MatchStats>();
+    assert_eq!(stats.get(Team(0)).units_trained, 0);
+}
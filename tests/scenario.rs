@@ -0,0 +1,34 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_rts::building::Building;
+use bevy_rts::combat::Health;
+use bevy_rts::scenario::Outcome;
+use bevy_rts::team::Team;
+
+use common::{game, headless, run_to};
+
+// Runs every one of `team`'s units, or buildings, out of health
+fn kill(world: &mut World, team: Team, buildings: bool) {
+    let mut targets = world.query::<(&Team, &mut Health, Has<Building>)>();
+    for (owner, mut health, building) in targets.iter_mut(world) {
+        if *owner == team && building == buildings {
+            health.current = 0.0;
+        }
+    }
+}
+
+#[test]
+fn conquest_is_won_once_the_last_enemy_building_falls() {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    run_to(&mut app, 5);
+
+    kill(app.world_mut(), Team(1), false);
+    run_to(&mut app, 10);
+    // Their town hall still stands
+    assert_eq!(*app.world().resource::<Outcome>(), Outcome::Playing);
+
+    kill(app.world_mut(), Team(1), true);
+    run_to(&mut app, 15);
+    assert_eq!(*app.world().resource::<Outcome>(), Outcome::Won);
+}
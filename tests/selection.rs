@@ -0,0 +1,270 @@
+mod common;
+
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::mouse::MouseButtonInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::render::camera::{camera_system, ManualTextureViews};
+use bevy::window::{
+    PrimaryWindow, WindowCreated, WindowResized, WindowResolution, WindowScaleFactorChanged,
+};
+use bevy_rts::building::Building;
+use bevy_rts::selection::{Inspected, Selected};
+use bevy_rts::sim::Interpolated;
+use bevy_rts::team::{Team, TeamPalette};
+use bevy_rts::unit::Unit;
+use bevy_rts_camera::RtsCamera;
+
+use common::{game, headless, run_to};
+
+const SCREEN: Vec2 = Vec2::new(1280.0, 720.0);
+
+// The skirmish with a window for the cursor to be in, looking straight down on the whole map
+fn on_screen() -> (App, Entity) {
+    let mut app = headless(game("skirmish"), u64::MAX);
+    // Nothing draws here, but the camera still needs to know the window it'd draw to
+    app.add_event::<WindowCreated>()
+        .add_event::<WindowResized>()
+        .add_event::<WindowScaleFactorChanged>()
+        .init_resource::<ManualTextureViews>()
+        .add_systems(PostUpdate, camera_system::<Projection>);
+    run_to(&mut app, 5);
+
+    let world = app.world_mut();
+    let window = world
+        .spawn((
+            Window {
+                resolution: WindowResolution::new(SCREEN.x, SCREEN.y),
+                ..default()
+            },
+            PrimaryWindow,
+        ))
+        .id();
+    world.send_event(WindowCreated { window });
+    let above = Transform::from_xyz(0.0, 120.0, 0.0).looking_at(Vec3::ZERO, Vec3::Z);
+    let mut cameras = world.query_filtered::<&mut Transform, With<RtsCamera>>();
+    *cameras.single_mut(world) = above;
+    app.update();
+    (app, window)
+}
+
+fn click(app: &mut App, window: Entity, state: ButtonState) {
+    app.world_mut().send_event(MouseButtonInput {
+        button: MouseButton::Left,
+        state,
+        window,
+    });
+}
+
+fn move_cursor(app: &mut App, window: Entity, position: Vec2) {
+    let mut window = app.world_mut().get_mut::<Window>(window).unwrap();
+    window.set_cursor_position(Some(position));
+}
+
+// Left-drags a marquee from `from` to `to`
+fn drag(app: &mut App, window: Entity, from: Vec2, to: Vec2) {
+    move_cursor(app, window, from);
+    click(app, window, ButtonState::Pressed);
+    app.update();
+    move_cursor(app, window, to);
+    app.update();
+    click(app, window, ButtonState::Released);
+    app.update();
+    // For what goes by the selection to catch up with it
+    app.update();
+}
+
+fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+    entities.sort();
+    entities
+}
+
+fn selected(world: &mut World) -> Vec<Entity> {
+    let mut selected = world.query_filtered::<Entity, With<Selected>>();
+    sorted(selected.iter(world).collect())
+}
+
+fn units_of(world: &mut World, team: Team) -> Vec<Entity> {
+    let mut units = world.query_filtered::<(Entity, &Team), With<Unit>>();
+    let units = units.iter(world).filter(|(_, owner)| **owner == team);
+    sorted(units.map(|(entity, _)| entity).collect())
+}
+
+#[test]
+fn only_the_players_own_units_are_box_selected() {
+    let (mut app, window) = on_screen();
+    drag(&mut app, window, Vec2::ZERO, SCREEN - 1.0);
+
+    let world = app.world_mut();
+    let own = units_of(world, Team(0));
+    assert!(!own.is_empty());
+    assert_eq!(selected(world), own);
+
+    // Only they light up, the town hall keeps its colours
+    let palette = world.resource::<TeamPalette>();
+    let (highlighted, plain) = (palette.selected(Team(0)), palette.normal(Team(0)));
+    for unit in own {
+        assert_eq!(
+            world.get::<Handle<StandardMaterial>>(unit),
+            Some(&highlighted)
+        );
+    }
+    let mut buildings =
+        world.query_filtered::<(&Team, &Handle<StandardMaterial>), With<Building>>();
+    let (_, hall) = buildings
+        .iter(world)
+        .find(|(team, _)| **team == Team(0))
+        .unwrap();
+    assert_eq!(*hall, plain);
+}
+
+#[test]
+fn enemies_are_shown_by_name_but_not_selected() {
+    let (mut app, window) = on_screen();
+    let world = app.world_mut();
+
+    // One of their soldiers wanders up to the player's base, where it can be seen
+    let mut soldiers = world.query::<(Entity, &Team, &Unit)>();
+    let (soldier, ..) = soldiers
+        .iter(world)
+        .find(|(_, team, unit)| **team == Team(1) && unit.0 == "soldier")
+        .unwrap();
+    let near = Vec3::new(-20.0, 0.75, -18.0);
+    world.entity_mut(soldier).insert(Interpolated::new(near));
+    world.get_mut::<Transform>(soldier).unwrap().translation = near;
+    run_to(&mut app, 10);
+
+    drag(&mut app, window, Vec2::ZERO, SCREEN - 1.0);
+    assert!(!selected(app.world_mut()).contains(&soldier));
+
+    // As clicking on it does
+    app.world_mut().resource_mut::<Inspected>().0 = Some(soldier);
+    app.update();
+    let world = app.world_mut();
+    let mut texts = world.query::<(&Text, &Visibility)>();
+    let shown = texts.iter(world).any(|(text, visibility)| {
+        *visibility == Visibility::Visible
+            && text.sections[0].value.starts_with("Enemy Soldier (team 1)")
+    });
+    assert!(shown);
+}
+
+fn key(app: &mut App, key_code: KeyCode, logical_key: Key, state: ButtonState) {
+    app.world_mut().send_event(KeyboardInput {
+        key_code,
+        logical_key,
+        state,
+        window: Entity::PLACEHOLDER,
+    });
+}
+
+// Where `entity` is on screen
+fn screen_position(world: &mut World, entity: Entity) -> Vec2 {
+    let position = world.get::<GlobalTransform>(entity).unwrap().translation();
+    let mut cameras = world.query_filtered::<(&Camera, &GlobalTransform), With<RtsCamera>>();
+    let (camera, transform) = cameras.single(world);
+    camera.world_to_viewport(transform, position).unwrap()
+}
+
+#[test]
+fn ctrl_dragging_takes_units_out_and_clicking_the_ground_clears() {
+    let (mut app, window) = on_screen();
+    drag(&mut app, window, Vec2::ZERO, SCREEN - 1.0);
+
+    // Everything left of the middle of the group comes out of the selection
+    let own = units_of(app.world_mut(), Team(0));
+    let positions: Vec<Vec2> = own
+        .iter()
+        .map(|unit| screen_position(app.world_mut(), *unit))
+        .collect();
+    let middle = positions.iter().map(|p| p.x).sum::<f32>() / positions.len() as f32;
+    let right: Vec<Entity> = own
+        .iter()
+        .zip(&positions)
+        .filter(|(_, position)| position.x > middle)
+        .map(|(unit, _)| *unit)
+        .collect();
+    assert!(!right.is_empty() && right.len() < own.len());
+
+    key(
+        &mut app,
+        KeyCode::ControlLeft,
+        Key::Control,
+        ButtonState::Pressed,
+    );
+    drag(
+        &mut app,
+        window,
+        Vec2::ZERO,
+        Vec2::new(middle, SCREEN.y - 1.0),
+    );
+    key(
+        &mut app,
+        KeyCode::ControlLeft,
+        Key::Control,
+        ButtonState::Released,
+    );
+    app.update();
+    assert_eq!(selected(app.world_mut()), right);
+
+    // A click that doesn't drag, on nothing in particular
+    move_cursor(&mut app, window, Vec2::new(5.0, 5.0));
+    click(&mut app, window, ButtonState::Pressed);
+    app.update();
+    click(&mut app, window, ButtonState::Released);
+    app.update();
+    app.update();
+    assert!(selected(app.world_mut()).is_empty());
+}
+
+fn tap(app: &mut App, key_code: KeyCode, logical_key: Key) {
+    key(app, key_code, logical_key.clone(), ButtonState::Pressed);
+    app.update();
+    key(app, key_code, logical_key, ButtonState::Released);
+    app.update();
+}
+
+#[test]
+fn control_groups_bring_their_units_back_and_double_taps_look_at_them() {
+    let (mut app, window) = on_screen();
+    drag(&mut app, window, Vec2::ZERO, SCREEN - 1.0);
+    let one = || Key::Character("1".into());
+    key(
+        &mut app,
+        KeyCode::ControlLeft,
+        Key::Control,
+        ButtonState::Pressed,
+    );
+    tap(&mut app, KeyCode::Digit1, one());
+    key(
+        &mut app,
+        KeyCode::ControlLeft,
+        Key::Control,
+        ButtonState::Released,
+    );
+
+    // One of them dies, then the selection is cleared
+    let mut group = units_of(app.world_mut(), Team(0));
+    let fallen = group.pop().unwrap();
+    app.world_mut().despawn(fallen);
+    move_cursor(&mut app, window, Vec2::new(5.0, 5.0));
+    click(&mut app, window, ButtonState::Pressed);
+    app.update();
+    click(&mut app, window, ButtonState::Released);
+    app.update();
+    assert!(selected(app.world_mut()).is_empty());
+
+    tap(&mut app, KeyCode::Digit1, one());
+    assert_eq!(selected(app.world_mut()), group);
+
+    tap(&mut app, KeyCode::Digit1, one());
+    let world = app.world_mut();
+    let mut positions = world.query::<&GlobalTransform>();
+    let centroid = group
+        .iter()
+        .map(|unit| positions.get(world, *unit).unwrap().translation())
+        .sum::<Vec3>()
+        / group.len() as f32;
+    let camera = world.query::<&RtsCamera>().single(world);
+    assert!(camera.target_focus.translation.distance(centroid) < 0.5);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_rts::command::PlayerCommand;
+use bevy_rts::formation::FormationKind;
+use bevy_rts::orders::{Destination, Order};
+use bevy_rts::replay::ReplayPlugin;
+use bevy_rts::sim::{Interpolated, SimClock, SimId};
+use bevy_rts::team::Team;
+use bevy_rts::unit::Unit;
+
+use common::{game, headless, run};
+
+const TICKS: u64 = 120;
+
+// Where everything the simulation moves stands after a short battle, drawn `frames_per_tick`
+// times a tick
+fn battle(frames_per_tick: f64) -> Vec<(SimId, Vec3)> {
+    let mut game = game("default");
+    let replay = format!("drawn_{frames_per_tick}.replay.ron");
+    game.replay = ReplayPlugin::Record(common::temp_path(&replay));
+    let mut app = headless(game, TICKS);
+    app.update();
+    let tick = app.world().resource::<Time<Fixed>>().timestep();
+    let frame = Duration::from_secs_f64(tick.as_secs_f64() / frames_per_tick);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(frame));
+
+    let mut sent = false;
+    run(&mut app, |world, tick| {
+        if tick != 10 || sent {
+            return;
+        }
+        sent = true;
+        let mut units = world.query::<(Entity, &Team, &Unit)>();
+        let army: Vec<Entity> = units
+            .iter(world)
+            .filter(|(_, team, _)| **team == Team(0))
+            .map(|(entity, ..)| entity)
+            .collect();
+        let there = Destination {
+            target: Vec3::new(0.0, 0.0, 14.0),
+            facing: None,
+            formation: FormationKind::Wedge,
+        };
+        world.send_event(PlayerCommand::order(
+            Team(0),
+            army,
+            Order::AttackMove(there),
+            false,
+        ));
+    });
+
+    let world = app.world_mut();
+    assert_eq!(world.resource::<SimClock>().tick, TICKS);
+    let mut entities = world.query::<(&SimId, &Interpolated)>();
+    let mut positions: Vec<(SimId, Vec3)> = entities
+        .iter(world)
+        .map(|(id, interpolated)| (*id, interpolated.current()))
+        .collect();
+    positions.sort_by_key(|(id, _)| *id);
+    positions
+}
+
+#[test]
+fn the_simulation_goes_the_same_however_fast_it_is_drawn() {
+    let once = battle(1.0);
+    assert_eq!(battle(1.7), once);
+    assert_eq!(battle(4.0), once);
+}